@@ -1,9 +1,31 @@
-mod device;
-mod devices;
-mod server;
+use lesson8_lib::{Socket, Thermometer};
+
 mod request;
 mod response;
+mod server;
+
+fn main() -> std::io::Result<()> {
+    let mut server = server::Server::new("127.0.0.1", 9872)?;
+    // optional path of file home state is saved to on shutdown
+    if let Some(path) = std::env::args().nth(1) {
+        server = server.with_state_file(path);
+    }
+    lesson18_shutdown::shutdown_on_signals(server.shutdown_handle())?;
+    println!("Connected to 127.0.0.1:9872");
 
-fn main() {
-    println!("Hello, world!");
+    let mut socket1 = Socket::new("socket1");
+    server
+        .add_device("kitchen", &mut socket1)
+        .expect("should be unique");
+    let mut thermo1 = Thermometer::new("thermo1");
+    server
+        .add_device("kitchen", &mut thermo1)
+        .expect("should be unique");
+    let mut socket2 = Socket::new("socket2");
+    server
+        .add_device("garage", &mut socket2)
+        .expect("should be unique");
+    server.run()?;
+    println!("Server stopped");
+    Ok(())
 }
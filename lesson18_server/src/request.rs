@@ -1,12 +1,12 @@
 use std::str::FromStr;
 
-
 pub enum Request {
     GetDeviceNames,
     StatusAll,
     StatusDevice { device_name: String },
     TurnOn { device_name: String },
     TurnOff { device_name: String },
+    Query { query: String },
     Error { reason: String },
 }
 
@@ -44,9 +44,31 @@ impl FromStr for Request {
                     reason: "No device name specified".to_string(),
                 }),
             },
+            "query" => Ok(Self::Query {
+                query: s
+                    .trim()
+                    .strip_prefix(command)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            }),
             _ => Ok(Self::Error {
                 reason: "Unknown command".to_string(),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query() {
+        let request = Request::from_str("query state = On and power > 50\n").unwrap();
+        assert!(matches!(
+            request,
+            Request::Query { query } if query == "state = On and power > 50"
+        ));
+    }
+}
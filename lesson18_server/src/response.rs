@@ -1,6 +1,5 @@
 use std::{fmt::Display, str::FromStr};
 
-
 pub enum Response {
     Ok { result: Option<String> },
     Error { reason: String },
@@ -1,115 +1,200 @@
 use std::{
+    fs,
     io::{self, BufRead, BufReader, BufWriter, Write},
     net::{TcpListener, TcpStream},
+    path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 
-use crate::{device::Device, request::Request, response::Response};
+use lesson18_shutdown::{accept_until_shutdown, ShutdownHandle};
+use lesson8_lib::{Device, DeviceInfo, Home, Query, UserContext, OWNER};
 
+use crate::{request::Request, response::Response};
+
+/// Time client is given to send request and receive response
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server struct
+///
+/// Runs TCP server and controlls states of home devices.
+/// Devices are addressed by name, so names are unique in the whole home
 pub struct Server<'a> {
     address: String,
-    devices: Vec<&'a mut dyn Device>,
+    home: Home<'a>,
+    shutdown: ShutdownHandle,
+    /// File home snapshot is written to after shutdown
+    state_file: Option<PathBuf>,
 }
 
 impl<'a> Server<'a> {
     pub fn new(address: &str, port: u32) -> io::Result<Self> {
         Ok(Self {
             address: format!("{}:{}", address, port),
-            devices: vec![],
+            home: Home::new("server"),
+            shutdown: ShutdownHandle::default(),
+            state_file: None,
         })
     }
+    /// Sets file home snapshot is written to in JSON after shutdown
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+    /// Returns handle stopping `run`
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
 
-    /// Adds device to device vector
+    /// Adds device to room, creates the room if needed
     /// Returns `Err(String)` if device name is not unique
-    pub fn add_device(&mut self, device: &'a mut dyn Device) -> Result<(), String> {
-        if self.devices.iter().any(|d| d.name() == device.name()) {
+    pub fn add_device(
+        &mut self,
+        room_name: &str,
+        device: &'a mut dyn Device,
+    ) -> Result<(), String> {
+        if self.find_device(device.name()).is_some() {
             return Err(format!("Device with name {} exists!", device.name()));
         }
-        self.devices.push(device);
-        Ok(())
+        if !self.home.rooms().any(|r| r.name() == room_name) {
+            self.owner()
+                .add_room(room_name)
+                .map_err(|e| e.to_string())?;
+        }
+        self.owner()
+            .add_device(room_name, device)
+            .map_err(|e| e.to_string())
+    }
+    /// Returns home context of its owner, server changes home on behalf of the owner
+    fn owner(&mut self) -> UserContext<'_, 'a> {
+        self.home.acting_as(OWNER).expect("home should have owner")
     }
+    /// Returns position of device with `device_name` in home
+    fn find_device(&self, device_name: &str) -> Option<DeviceInfo> {
+        self.home
+            .devices()
+            .find(|(_, device)| device.name() == device_name)
+            .map(|(room_name, _)| DeviceInfo::new(device_name, room_name))
+    }
+    /// Returns all devices in home
+    fn device_infos(&self) -> Vec<DeviceInfo> {
+        self.home
+            .devices()
+            .map(|(room_name, device)| DeviceInfo::new(device.name(), room_name))
+            .collect()
+    }
+    /// Main worker
+    ///
+    /// Serves connections one by one until shutdown is requested,
+    /// connection served at that moment is finished within `CONNECTION_TIMEOUT`.
+    /// Writes home snapshot if state file is set.
+    /// Returns `io::Err` if it cannot connect to given address or write state file
     pub fn run(&mut self) -> io::Result<()> {
         let listener = TcpListener::bind(self.address.as_str())?;
-        for stream in listener.incoming() {
-            let mut stream = stream?;
-            let request = Self::get_request(&mut stream)?;
-            let request = Request::from_str(request.as_str()).unwrap();
-            let response = match request {
-                Request::GetDeviceNames => {
-                    if self.devices.is_empty() {
-                        Response::Ok {
-                            result: Some("".to_string()),
-                        }
-                    } else {
-                        let result = self
-                            .devices
-                            .iter()
-                            .map(|d| d.name())
-                            .collect::<Vec<&str>>()
-                            .join(", ");
-                        Response::Ok {
-                            result: Some(result),
-                        }
-                    }
-                }
-                Request::StatusAll => {
-                    if self.devices.is_empty() {
-                        Response::Ok {
-                            result: Some("".to_string()),
-                        }
-                    } else {
-                        Response::Ok {
-                            result: Some(
-                                self.devices
-                                    .iter()
-                                    .map(|d| d.to_string())
-                                    .collect::<Vec<String>>()
-                                    .join(";;;"),
-                            ),
-                        }
-                    }
-                }
-                Request::StatusDevice { device_name } => {
-                    let dev = self.devices.iter().find(|d| d.name() == device_name);
-                    if let Some(dev) = dev {
-                        Response::Ok {
-                            result: Some(dev.to_string()),
-                        }
-                    } else {
-                        Response::Error {
-                            reason: format!("Device with name '{device_name}' does not exist"),
-                        }
-                    }
-                }
-                Request::TurnOn { device_name } => {
-                    let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
-                    if let Some(dev) = dev {
-                        dev.turn_on();
-                        Response::Ok { result: None }
-                    } else {
-                        Response::Error {
-                            reason: format!("Device with name '{device_name}' does not exist"),
-                        }
-                    }
+        listener.set_nonblocking(true)?;
+        let shutdown = self.shutdown.clone();
+        for stream in accept_until_shutdown(&listener, &shutdown) {
+            let result = stream.and_then(|stream| self.serve(stream));
+            if let Err(e) = result {
+                eprintln!("Connection error: {}", e);
+            }
+        }
+        drop(listener);
+        self.persist_state()
+    }
+    /// Reads request from connection and writes response
+    /// Returns `io::Err` if it failes to read from or write to connection
+    fn serve(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let request = Self::get_request(&mut stream)?;
+        let request = Request::from_str(request.as_str()).unwrap();
+        let response = match request {
+            Request::GetDeviceNames => {
+                let result = self
+                    .device_infos()
+                    .iter()
+                    .map(|d| d.device_name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                Response::Ok {
+                    result: Some(result),
                 }
-                Request::TurnOff { device_name } => {
-                    let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
-                    if let Some(dev) = dev {
-                        dev.turn_off();
-                        Response::Ok { result: None }
-                    } else {
-                        Response::Error {
-                            reason: format!("Device with name '{device_name}' does not exist"),
-                        }
-                    }
+            }
+            Request::StatusAll => {
+                let device_infos = self.device_infos();
+                let result = self
+                    .owner()
+                    .get_devices_report(device_infos.iter().collect())
+                    .into_iter()
+                    .filter_map(|r| r.ok())
+                    .collect::<Vec<String>>()
+                    .join(";;;");
+                Response::Ok {
+                    result: Some(result),
                 }
-                _ => Response::Error {
-                    reason: "".to_string(),
+            }
+            Request::StatusDevice { device_name } => match self.find_device(&device_name) {
+                Some(device_info) => match self.owner().get_device_report(&device_info) {
+                    Ok(report) => Response::Ok {
+                        result: Some(report),
+                    },
+                    Err(e) => Response::Error {
+                        reason: e.to_string(),
+                    },
+                },
+                None => Response::Error {
+                    reason: format!("Device with name '{device_name}' does not exist"),
+                },
+            },
+            Request::TurnOn { device_name } => match self.find_device(&device_name) {
+                Some(device_info) => match self.owner().turn_on(&device_info) {
+                    Ok(()) => Response::Ok { result: None },
+                    Err(e) => Response::Error {
+                        reason: e.to_string(),
+                    },
+                },
+                None => Response::Error {
+                    reason: format!("Device with name '{device_name}' does not exist"),
+                },
+            },
+            Request::TurnOff { device_name } => match self.find_device(&device_name) {
+                Some(device_info) => match self.owner().turn_off(&device_info) {
+                    Ok(()) => Response::Ok { result: None },
+                    Err(e) => Response::Error {
+                        reason: e.to_string(),
+                    },
                 },
-            };
-            Self::send_response(&mut stream, response.to_string().as_str())?;
+                None => Response::Error {
+                    reason: format!("Device with name '{device_name}' does not exist"),
+                },
+            },
+            Request::Query { query } => match Query::from_str(&query) {
+                Ok(query) => Response::Ok {
+                    result: Some(
+                        self.home
+                            .query(&query)
+                            .iter()
+                            .map(|m| format!("{}/{}", m.room_name, m.device.name))
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                    ),
+                },
+                Err(e) => Response::Error {
+                    reason: e.to_string(),
+                },
+            },
+            Request::Error { reason } => Response::Error { reason },
+        };
+        Self::send_response(&mut stream, response.to_string().as_str())
+    }
+    /// Writes home snapshot in JSON into state file if it is set
+    /// Returns `io::Err` if it failes to write the file
+    fn persist_state(&self) -> io::Result<()> {
+        match &self.state_file {
+            Some(path) => fs::write(path, serde_json::to_string_pretty(&self.home.snapshot())?),
+            None => Ok(()),
         }
-        Ok(())
     }
     fn get_request(stream: &mut TcpStream) -> io::Result<String> {
         let mut buf_reader = BufReader::new(stream);
@@ -0,0 +1,85 @@
+//! Graceful shutdown of lesson 18 servers on request or signal
+
+use std::{
+    io,
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Time between checks of shutdown request while nothing happens
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Set by signal handler
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/// Handle requesting server shutdown from another thread
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    /// Asks server to stop accepting connections and finish served requests
+    pub fn shutdown(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+/// Requests shutdown of `handle` on the first SIGINT or SIGTERM,
+/// the second signal terminates process
+///
+/// Returns `io::Err` if signal handlers cannot be installed
+pub fn shutdown_on_signals(handle: ShutdownHandle) -> io::Result<()> {
+    #[cfg(unix)]
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: handler only stores to an atomic and resets disposition,
+        // both are async-signal-safe
+        let previous =
+            unsafe { libc::signal(signal, on_signal as *const () as libc::sighandler_t) };
+        if previous == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    thread::spawn(move || {
+        while !SIGNALLED.load(Ordering::SeqCst) {
+            thread::sleep(POLL_INTERVAL);
+        }
+        eprintln!("Shutting down");
+        handle.shutdown();
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    SIGNALLED.store(true, Ordering::SeqCst);
+    // SAFETY: `signal` is async-signal-safe
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+/// Returns connections accepted by non-blocking `listener` until shutdown is requested
+pub fn accept_until_shutdown<'l>(
+    listener: &'l TcpListener,
+    shutdown: &'l ShutdownHandle,
+) -> impl Iterator<Item = io::Result<TcpStream>> + 'l {
+    std::iter::from_fn(move || loop {
+        if shutdown.is_requested() {
+            return None;
+        }
+        match listener.accept() {
+            Ok((stream, _)) => return Some(stream.set_nonblocking(false).map(|_| stream)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Some(Err(e)),
+        }
+    })
+}
@@ -0,0 +1,147 @@
+//! Client of running tcp socket server, switches its devices and prints responses
+//!
+//! Credentials are taken from `TCP_SOCKET_USER` and `TCP_SOCKET_PASSWORD` if server requires them
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    net::TcpStream,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Returns device name percent-encoded for use as URL path segment
+fn encode(device_name: &str) -> String {
+    device_name
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Client struct
+///
+/// Sends http requests to `Server` and returns raw responses
+pub struct Client {
+    address: String,
+    /// Value of `Authorization` header sent with every request
+    authorization: Option<String>,
+}
+
+impl Client {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            authorization: None,
+        }
+    }
+    /// Authenticates requests with HTTP Basic credentials
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Self {
+        let credentials = STANDARD.encode(format!("{user}:{password}"));
+        self.authorization = Some(format!("Basic {credentials}"));
+        self
+    }
+    /// Returns main page with device names
+    pub fn get_device_names(&self) -> io::Result<String> {
+        self.get("/")
+    }
+    pub fn get_status_all(&self) -> io::Result<String> {
+        self.get("/status_all")
+    }
+    pub fn get_status_device(&self, device_name: &str) -> io::Result<String> {
+        self.get(&format!("/status_device/{}", encode(device_name)))
+    }
+    pub fn turn_on_device(&self, device_name: &str) -> io::Result<String> {
+        self.send(
+            "POST",
+            &format!("/turn_on/{}", encode(device_name)),
+            "text/html",
+            "",
+        )
+    }
+    pub fn turn_off_device(&self, device_name: &str) -> io::Result<String> {
+        self.send(
+            "POST",
+            &format!("/turn_off/{}", encode(device_name)),
+            "text/html",
+            "",
+        )
+    }
+    /// Sets device state and returns JSON device object
+    pub fn set_device_state_json(&self, device_name: &str, on: bool) -> io::Result<String> {
+        let state = match on {
+            true => "on",
+            false => "off",
+        };
+        self.send(
+            "PUT",
+            &format!("/api/devices/{}/state", encode(device_name)),
+            "application/json",
+            &format!(r#"{{"state":"{state}"}}"#),
+        )
+    }
+    pub fn get_metrics(&self) -> io::Result<String> {
+        self.get("/metrics")
+    }
+    /// Returns JSON array of all devices
+    pub fn get_devices_json(&self) -> io::Result<String> {
+        self.send("GET", "/api/devices", "application/json", "")
+    }
+    /// Turns device on or off and returns JSON device object
+    pub fn switch_device_json(&self, device_name: &str, on: bool) -> io::Result<String> {
+        let action = match on {
+            true => "on",
+            false => "off",
+        };
+        self.send(
+            "POST",
+            &format!("/api/devices/{}/{action}", encode(device_name)),
+            "application/json",
+            "",
+        )
+    }
+    fn get(&self, path: &str) -> io::Result<String> {
+        self.send("GET", path, "text/html", "")
+    }
+    /// Sends request to `path` and reads response until server closes connection
+    /// Returns `io::Err` if it cannot connect to server or serve stream
+    fn send(&self, method: &str, path: &str, accept: &str, body: &str) -> io::Result<String> {
+        let mut stream = TcpStream::connect(&self.address)?;
+        let authorization = match &self.authorization {
+            Some(authorization) => format!("Authorization: {authorization}\r\n"),
+            None => String::new(),
+        };
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nAccept: {accept}\r\nConnection: close\r\n\
+             {authorization}Content-Length: {}\r\n\r\n{body}",
+            self.address,
+            body.len()
+        );
+        stream.write_all(request.as_bytes())?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut client = Client::new("127.0.0.1:9871");
+    if let (Ok(user), Ok(password)) = (env::var("TCP_SOCKET_USER"), env::var("TCP_SOCKET_PASSWORD"))
+    {
+        client = client.with_basic_auth(&user, &password);
+    }
+    println!("{}", client.get_device_names()?);
+    println!("{}", client.turn_on_device("socket1")?);
+    println!("{}", client.get_status_device("socket1")?);
+    println!("{}", client.turn_off_device("socket1")?);
+    println!("{}", client.get_status_all()?);
+    println!("{}", client.get_metrics()?);
+    println!("{}", client.switch_device_json("socket2", true)?);
+    println!("{}", client.set_device_state_json("socket1", true)?);
+    println!("{}", client.get_devices_json()?);
+    Ok(())
+}
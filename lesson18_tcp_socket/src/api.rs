@@ -0,0 +1,117 @@
+use lesson8_lib::{Device, DeviceState, Reading};
+use serde::{Deserialize, Serialize};
+
+use crate::events::DeviceEvent;
+
+/// Device object returned by JSON API
+#[derive(Debug, Serialize)]
+pub struct DeviceObject {
+    pub name: String,
+    pub room: String,
+    pub state: DeviceState,
+    /// Socket power in watts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power: Option<u32>,
+    /// Thermometer temperature in degrees Celsius
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+impl DeviceObject {
+    pub fn new(room_name: &str, device: &dyn Device) -> Self {
+        Self::with_reading(room_name, device, device.reading())
+    }
+    /// Returns object with reading already taken from device
+    pub fn with_reading(room_name: &str, device: &dyn Device, reading: Option<Reading>) -> Self {
+        Self {
+            name: device.name().to_string(),
+            room: room_name.to_string(),
+            state: *device.state(),
+            power: match reading {
+                Some(Reading::Power(power)) => Some(power),
+                _ => None,
+            },
+            temperature: match reading {
+                Some(Reading::Temperature(temperature)) => Some(temperature.celsius()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// State object accepted by JSON API
+#[derive(Debug, Deserialize)]
+struct StateObject {
+    state: String,
+}
+
+/// Returns state from `on` or `off` text or JSON state object, ignoring case
+pub fn parse_state(body: &[u8]) -> Option<DeviceState> {
+    let text = std::str::from_utf8(body).ok()?.trim();
+    let state = match serde_json::from_str::<StateObject>(text) {
+        Ok(object) => object.state,
+        Err(_) => text.to_string(),
+    };
+    match state.to_lowercase().as_str() {
+        "on" => Some(DeviceState::On),
+        "off" => Some(DeviceState::Off),
+        _ => None,
+    }
+}
+
+/// Error object returned by JSON API
+#[derive(Debug, Serialize)]
+pub struct ErrorObject {
+    pub error: String,
+}
+
+impl ErrorObject {
+    pub fn new(error: &str) -> Self {
+        Self {
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Message of WebSocket control channel sent by client
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Asks for all devices
+    List,
+    /// Asks for events published after `last_event_id` or from now on
+    Subscribe {
+        #[serde(default)]
+        last_event_id: Option<u64>,
+    },
+    Unsubscribe,
+    /// Switches device to the opposite state
+    Toggle {
+        device: String,
+    },
+}
+
+/// Message of WebSocket control channel sent by server
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Devices {
+        devices: Vec<DeviceEvent>,
+    },
+    Subscribed {
+        last_event_id: u64,
+    },
+    Unsubscribed,
+    /// Event streamed from `/events` too
+    Event {
+        id: u64,
+        event: &'static str,
+        data: serde_json::Value,
+    },
+    Toggled {
+        device: DeviceEvent,
+    },
+    Error {
+        error: String,
+    },
+}
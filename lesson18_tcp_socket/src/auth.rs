@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs, io,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::request::{Headers, Request};
+
+/// Realm of `WWW-Authenticate` challenges
+pub const REALM: &str = "tcp_socket";
+/// Name of cookie holding dashboard session
+pub const SESSION_COOKIE: &str = "session";
+/// Time session stays valid after its last request
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Enum for permissions of users
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Reads states, reports, metrics and events of devices
+    ReadOnly,
+    /// Also switches devices
+    Control,
+}
+
+/// User entry of credentials file
+#[derive(Debug, Deserialize)]
+struct UserEntry {
+    name: String,
+    role: Role,
+    /// Argon2 hash of password in PHC string format, users without it log in with tokens only
+    #[serde(default)]
+    password_hash: Option<String>,
+    /// Hex SHA-256 of bearer tokens
+    #[serde(default)]
+    token_sha256: Vec<String>,
+}
+
+/// Authenticated user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+impl User {
+    /// Returns `true` if user may switch devices
+    pub fn may_control(&self) -> bool {
+        self.role == Role::Control
+    }
+}
+
+/// Enum for requests which are not authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// Request has neither `Authorization` header nor session cookie
+    Missing,
+    /// Credentials are malformed or do not match any user
+    InvalidCredentials,
+    /// Bearer token does not match any user
+    InvalidToken,
+    /// Session is expired or ended
+    InvalidSession,
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Missing => write!(f, "Authentication is required"),
+            AuthError::InvalidCredentials => write!(f, "Wrong user name or password"),
+            AuthError::InvalidToken => write!(f, "Bearer token is not valid"),
+            AuthError::InvalidSession => write!(f, "Session has expired"),
+        }
+    }
+}
+
+struct Session {
+    user: User,
+    expires: Instant,
+}
+
+/// Users loaded from credentials file and sessions of users logged in to dashboard
+///
+/// Credentials file is a JSON array of users, e.g.
+/// `[{"name": "admin", "role": "control", "password_hash": "$argon2id$...",
+/// "token_sha256": ["..."]}]`, hashes are made by
+/// `printf '%s' "$password" | argon2 "$salt" -id -t 2 -k 19456 -p 1 -e`
+/// and `printf '%s' "$token" | sha256sum`
+pub struct Auth {
+    users: Vec<UserEntry>,
+    /// Hash of random password checked for unknown users,
+    /// so response time does not tell which users exist
+    dummy_hash: String,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Auth {
+    /// Loads users from credentials file
+    /// Returns `io::Err` if file cannot be read or has invalid users
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&fs::read(path)?)
+    }
+    fn from_json(json: &[u8]) -> io::Result<Self> {
+        let users: Vec<UserEntry> = serde_json::from_slice(json)?;
+        for (i, user) in users.iter().enumerate() {
+            if users[..i].iter().any(|u| u.name == user.name) {
+                return Err(invalid_data(format!("User {} is duplicated", user.name)));
+            }
+            if let Some(hash) = &user.password_hash {
+                if PasswordHash::new(hash).is_err() {
+                    return Err(invalid_data(format!(
+                        "User {} has password hash {:?} which is not PHC string",
+                        user.name, hash
+                    )));
+                }
+            }
+            if let Some(hash) = user
+                .token_sha256
+                .iter()
+                .find(|h| h.len() != 64 || !h.bytes().all(|b| b.is_ascii_hexdigit()))
+            {
+                return Err(invalid_data(format!(
+                    "User {} has hash {:?} which is not hex SHA-256",
+                    user.name, hash
+                )));
+            }
+        }
+        let mut password = [0; 16];
+        OsRng.fill_bytes(&mut password);
+        let dummy_hash = Argon2::default()
+            .hash_password(&password, &SaltString::generate(&mut OsRng))
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .to_string();
+        Ok(Self {
+            users,
+            dummy_hash,
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+    /// Returns user authenticated by `Authorization` header or session cookie
+    /// Returns `Err(AuthError)` if request has no or wrong credentials
+    pub fn authenticate(&self, request: &Request) -> Result<User, AuthError> {
+        if let Some(authorization) = request.headers.get("authorization") {
+            let (scheme, credentials) =
+                authorization.split_once(' ').unwrap_or((authorization, ""));
+            let credentials = credentials.trim();
+            return match scheme.to_ascii_lowercase().as_str() {
+                "basic" => self.basic(credentials).ok_or(AuthError::InvalidCredentials),
+                "bearer" => self.bearer(credentials).ok_or(AuthError::InvalidToken),
+                _ => Err(AuthError::InvalidCredentials),
+            };
+        }
+        match session_cookie(&request.headers) {
+            Some(id) => self.session(id).ok_or(AuthError::InvalidSession),
+            None => Err(AuthError::Missing),
+        }
+    }
+    /// Returns user with `name` and `password`
+    ///
+    /// Password of unknown user is checked against dummy hash,
+    /// so it takes as long as a wrong password of existing user
+    pub fn login(&self, name: &str, password: &str) -> Option<User> {
+        let user = self
+            .users
+            .iter()
+            .find(|user| user.name == name && user.password_hash.is_some());
+        let hash = user
+            .and_then(|user| user.password_hash.as_deref())
+            .unwrap_or(&self.dummy_hash);
+        let hash = PasswordHash::new(hash).ok()?;
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        match verified {
+            true => user.map(Self::user),
+            false => None,
+        }
+    }
+    /// Starts session of logged in user
+    /// Returns id of the session, `io::Err` if random id cannot be generated
+    pub fn start_session(&self, user: User) -> io::Result<String> {
+        let mut bytes = [0; 16];
+        OsRng.try_fill_bytes(&mut bytes).map_err(io::Error::other)?;
+        let id = hex(&bytes);
+        let mut sessions = self.lock();
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(
+            id.clone(),
+            Session {
+                user,
+                expires: now + SESSION_TTL,
+            },
+        );
+        Ok(id)
+    }
+    /// Ends session given in request cookie
+    pub fn end_session(&self, headers: &Headers) {
+        if let Some(id) = session_cookie(headers) {
+            self.lock().remove(id);
+        }
+    }
+    /// Returns user of `Basic` credentials, see RFC 7617
+    fn basic(&self, credentials: &str) -> Option<User> {
+        let credentials = String::from_utf8(STANDARD.decode(credentials).ok()?).ok()?;
+        let (name, password) = credentials.split_once(':')?;
+        self.login(name, password)
+    }
+    /// Returns user of `Bearer` token, see RFC 6750
+    fn bearer(&self, token: &str) -> Option<User> {
+        let hash = hex(&Sha256::digest(token.as_bytes()));
+        self.users
+            .iter()
+            .find(|user| {
+                user.token_sha256.iter().any(|expected| {
+                    constant_time_eq(hash.as_bytes(), expected.to_ascii_lowercase().as_bytes())
+                })
+            })
+            .map(Self::user)
+    }
+    /// Returns user of session and prolongs it
+    fn session(&self, id: &str) -> Option<User> {
+        let mut sessions = self.lock();
+        let session = sessions.get_mut(id)?;
+        let now = Instant::now();
+        if session.expires <= now {
+            sessions.remove(id);
+            return None;
+        }
+        session.expires = now + SESSION_TTL;
+        Some(session.user.clone())
+    }
+    fn user(entry: &UserEntry) -> User {
+        User {
+            name: entry.name.clone(),
+            role: entry.role,
+        }
+    }
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns `Set-Cookie` value storing session id, empty id removes the cookie
+///
+/// `SameSite=Strict` keeps other sites from switching devices with forms
+pub fn session_cookie_header(id: &str) -> String {
+    let max_age = match id.is_empty() {
+        true => 0,
+        false => SESSION_TTL.as_secs(),
+    };
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Strict",
+        SESSION_COOKIE, id, max_age
+    )
+}
+
+/// Returns session id from `Cookie` header
+fn session_cookie(headers: &Headers) -> Option<&str> {
+    headers
+        .get_all("cookie")
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, id)| id)
+}
+
+/// Returns lowercase hex string of bytes
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compares data in time depending only on its length,
+/// so secrets cannot be guessed byte by byte from response times
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn invalid_data(error_msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error_msg)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::request::RequestLimits;
+
+    use super::*;
+
+    fn request(headers: &str) -> Request {
+        let input = format!("GET / HTTP/1.1\r\nHost: a\r\n{headers}\r\n");
+        Request::read(&mut input.as_bytes(), &RequestLimits::default())
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Returns Argon2 hash of password in PHC string format
+    pub fn password_hash(password: &str) -> String {
+        Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string()
+    }
+    pub fn token_hash(token: &str) -> String {
+        hex(&Sha256::digest(token.as_bytes()))
+    }
+
+    #[test]
+    fn authenticate_requests() {
+        let json = format!(
+            r#"[
+                {{"name": "admin", "role": "control",
+                  "password_hash": "{}", "token_sha256": ["{}"]}},
+                {{"name": "viewer", "role": "read_only", "password_hash": "{}"}},
+                {{"name": "robot", "role": "control", "token_sha256": ["{}"]}}
+            ]"#,
+            password_hash("secret"),
+            token_hash("token1").to_uppercase(),
+            password_hash("view"),
+            token_hash("token2"),
+        );
+        let auth = Auth::from_json(json.as_bytes()).unwrap();
+        let admin = User {
+            name: "admin".to_string(),
+            role: Role::Control,
+        };
+        let authenticate = |headers| auth.authenticate(&request(headers));
+
+        assert_eq!(authenticate(""), Err(AuthError::Missing));
+        // admin:secret
+        assert_eq!(
+            authenticate("Authorization: Basic YWRtaW46c2VjcmV0\r\n"),
+            Ok(admin.clone())
+        );
+        // admin:wrong
+        assert_eq!(
+            authenticate("Authorization: Basic YWRtaW46d3Jvbmc=\r\n"),
+            Err(AuthError::InvalidCredentials)
+        );
+        assert_eq!(
+            authenticate("Authorization: Basic %%%\r\n"),
+            Err(AuthError::InvalidCredentials)
+        );
+        assert_eq!(
+            authenticate("Authorization: bearer token1\r\n"),
+            Ok(admin.clone())
+        );
+        assert_eq!(
+            authenticate("Authorization: Bearer token3\r\n"),
+            Err(AuthError::InvalidToken)
+        );
+        assert_eq!(auth.login("viewer", "view").unwrap().role, Role::ReadOnly);
+        assert_eq!(auth.login("nobody", "view"), None);
+        assert_eq!(auth.login("robot", ""), None);
+
+        let id = auth.start_session(admin.clone()).unwrap();
+        let cookie = format!("Cookie: theme=dark; {SESSION_COOKIE}={id}\r\n");
+        assert_eq!(authenticate(&cookie), Ok(admin));
+        auth.end_session(&request(&cookie).headers);
+        assert_eq!(authenticate(&cookie), Err(AuthError::InvalidSession));
+
+        assert!(Auth::from_json(br#"[{"name": "a", "role": "admin"}]"#).is_err());
+        assert!(
+            Auth::from_json(br#"[{"name": "a", "role": "control", "token_sha256": ["ab"]}]"#)
+                .is_err()
+        );
+        assert!(Auth::from_json(
+            br#"[{"name": "a", "role": "control", "password_hash": "5e884898"}]"#
+        )
+        .is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use crate::request::RequestType;
+
 #[derive(Debug, Default)]
 pub enum Command {
     TurnOn {
@@ -12,6 +14,21 @@ pub enum Command {
     GetDeviceStatus {
         device_name: String,
     },
+    GetMetrics,
+    /// Returns devices matching query given in `q` parameter of request target
+    Query,
+    /// Streams device changes as server-sent events
+    Events,
+    /// Opens WebSocket control channel
+    WebSocket,
+    /// Shows login form and starts dashboard session
+    Login,
+    /// Ends dashboard session
+    Logout,
+    /// Sets device state given in request body
+    SetState {
+        device_name: String,
+    },
     #[default]
     ShowMain,
     Error {
@@ -19,14 +36,60 @@ pub enum Command {
     },
     Ignore,
 }
+impl Command {
+    /// Returns methods the command can be requested with,
+    /// empty if command does not address a resource
+    pub fn allowed_methods(&self) -> &'static [RequestType] {
+        match self {
+            Command::TurnOn { .. } | Command::TurnOff { .. } => &[RequestType::Post],
+            Command::SetState { .. } => &[RequestType::Put],
+            Command::Events | Command::WebSocket => &[RequestType::Get],
+            Command::Login => &[RequestType::Get, RequestType::Head, RequestType::Post],
+            Command::Logout => &[RequestType::Post],
+            Command::GetStatus
+            | Command::GetDeviceStatus { .. }
+            | Command::GetMetrics
+            | Command::Query
+            | Command::ShowMain => &[RequestType::Get, RequestType::Head],
+            Command::Error { .. } | Command::Ignore => &[],
+        }
+    }
+    /// Parses path of JSON API after `/api/` prefix
+    fn from_api_path<'p>(
+        resource: Option<&'p str>,
+        mut collection: impl Iterator<Item = &'p str>,
+    ) -> Self {
+        let device_name = collection.next().filter(|n| !n.is_empty());
+        let action = collection.next();
+        match (resource, device_name, action, collection.next()) {
+            (Some("devices"), None, None, None) => Command::GetStatus,
+            (Some("query"), None, None, None) => Command::Query,
+            (Some("devices"), Some(device_name), None | Some(""), None) => {
+                Command::GetDeviceStatus {
+                    device_name: device_name.to_string(),
+                }
+            }
+            (Some("devices"), Some(device_name), Some("on"), None) => Command::TurnOn {
+                device_name: device_name.to_string(),
+            },
+            (Some("devices"), Some(device_name), Some("off"), None) => Command::TurnOff {
+                device_name: device_name.to_string(),
+            },
+            (Some("devices"), Some(device_name), Some("state"), None) => Command::SetState {
+                device_name: device_name.to_string(),
+            },
+            _ => Command::Error {
+                error_msg: "Unknown request".to_string(),
+            },
+        }
+    }
+}
+
 impl FromStr for Command {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        dbg!("Request: {s}");
-
         let mut collection = s.split('/');
-        dbg!(&collection);
         // should be '/' symb
         collection.next();
         let section = collection.next();
@@ -58,6 +121,12 @@ impl FromStr for Command {
                 })
             }
             "status_all" => Ok(Command::GetStatus),
+            "metrics" => Ok(Command::GetMetrics),
+            "query" => Ok(Command::Query),
+            "events" => Ok(Command::Events),
+            "ws" => Ok(Command::WebSocket),
+            "login" => Ok(Command::Login),
+            "logout" => Ok(Command::Logout),
             "status_device" => {
                 if dev_name.is_none() {
                     return Err(());
@@ -68,6 +137,17 @@ impl FromStr for Command {
                 })
             }
             "flavicon.ico" => Ok(Command::Ignore),
+            "api" => Ok(Self::from_api_path(dev_name, collection)),
+            "devices" => match (dev_name, collection.next(), collection.next()) {
+                (Some(device_name), Some("state"), None) if !device_name.is_empty() => {
+                    Ok(Command::SetState {
+                        device_name: device_name.to_string(),
+                    })
+                }
+                _ => Ok(Command::Error {
+                    error_msg: "Unknown request".to_string(),
+                }),
+            },
             _ => Ok(Command::Error {
                 error_msg: "Unknown request".to_string(),
             }),
@@ -0,0 +1,361 @@
+use build_html::{
+    escape_html, Html, HtmlContainer, HtmlPage, Table, TableCell, TableCellType, TableRow,
+};
+use lesson8_lib::{Device, DeviceInfo, DeviceState, Home, Reading};
+
+use crate::auth::User;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { padding: 0.4em 1em; border-bottom: 1px solid #ddd; text-align: left; }
+form { display: inline; }
+button { padding: 0.2em 1em; cursor: pointer; }
+button:disabled { cursor: default; }
+.On { color: #080; font-weight: bold; }
+.Off { color: #888; }
+footer { margin-top: 2em; font-size: 0.9em; color: #666; }
+";
+
+/// Updates devices from `/events` stream,
+/// browsers without event support reload page every 5 seconds
+const SCRIPT: &str = r#"
+function update(device) {
+    for (const element of document.querySelectorAll("[data-device]")) {
+        if (element.dataset.device !== device.name) {
+            continue;
+        }
+        const state = element.querySelector(".state");
+        // device pages are reloaded to show the new report
+        if (element.dataset.reload !== undefined && state.textContent !== device.state) {
+            location.reload();
+        }
+        state.textContent = device.state;
+        state.className = "state " + device.state;
+        element.querySelector(".reading").textContent = device.reading;
+        for (const button of element.querySelectorAll("button")) {
+            button.disabled = button.textContent === device.state;
+        }
+    }
+}
+if (window.EventSource) {
+    const events = new EventSource("/events");
+    events.addEventListener("state", (event) => update(JSON.parse(event.data)));
+    events.addEventListener("readings", (event) => JSON.parse(event.data).forEach(update));
+} else {
+    setTimeout(() => location.reload(), 5000);
+}
+"#;
+
+/// Returns dashboard page with table of all devices and buttons switching them,
+/// `user` is `None` if authentication is disabled
+pub fn dashboard(home: &Home, user: Option<&User>) -> String {
+    let mut table = Table::new().with_header_row(["Device", "Room", "State", "Reading", ""]);
+    for (room_name, device) in home.devices() {
+        table.add_custom_body_row(
+            TableRow::new()
+                .with_attributes([("data-device", escape_html(device.name()).as_str())])
+                .with_cell(TableCell::default().with_link(
+                    format!("/status_device/{}", percent_encode(device.name())),
+                    escape_html(device.name()),
+                ))
+                .with_cell(TableCell::default().with_raw(escape_html(room_name)))
+                .with_cell(state_cell(device))
+                .with_cell(reading_cell(home, device))
+                .with_cell(TableCell::default().with_raw(switch_forms(device, "/", user))),
+        );
+    }
+    let page = page("Home dashboard").with_header(1, "Home dashboard");
+    let page = match home.devices().next() {
+        Some(_) => page.with_table(table),
+        None => page.with_paragraph("No devices registered"),
+    };
+    page.with_raw(format!(
+        "<footer>\
+         <a href=\"/status_all\">Reports</a> | <a href=\"/api/devices\">JSON API</a> | \
+         <a href=\"/metrics\">Metrics</a>{}\
+         </footer>",
+        match user {
+            Some(user) => format!(
+                " | Signed in as {} \
+                 <form method=\"post\" action=\"/logout\"><button>Log out</button></form>",
+                escape_html(&user.name)
+            ),
+            None => String::new(),
+        }
+    ))
+    .with_header(2, "Urls")
+    .with_paragraph("GET /status_all - get statuses of all devices")
+    .with_paragraph("GET /status_device/{device_name} - get status of device")
+    .with_paragraph("POST /turn_on/{device_name} - turn on device")
+    .with_paragraph("POST /turn_off/{device_name} - turn off device")
+    .with_paragraph("PUT /devices/{device_name}/state - set state given as on or off")
+    .with_paragraph("GET /metrics - get metrics in Prometheus format")
+    .with_paragraph("GET /events - stream device changes and readings as server-sent events")
+    .with_paragraph(escape_html(
+        r#"GET /ws - WebSocket control channel with JSON messages {"type": "list"}, {"type": "subscribe"}, {"type": "unsubscribe"} and {"type": "toggle", "device": "{device_name}"}"#,
+    ))
+    .with_paragraph("GET /login - show login form, POST /login - start dashboard session")
+    .with_paragraph("POST /logout - end dashboard session")
+    .with_header(2, "JSON API")
+    .with_paragraph("GET /api/devices - get all devices")
+    .with_paragraph("GET /api/devices/{device_name} - get device")
+    .with_paragraph("POST /api/devices/{device_name}/on - turn on device")
+    .with_paragraph("POST /api/devices/{device_name}/off - turn off device")
+    .with_paragraph(escape_html(
+        r#"PUT /api/devices/{device_name}/state - set state given as {"state": "on"}"#,
+    ))
+    .to_html_string()
+}
+
+/// Returns local path form asks to be redirected to in `redirect` field,
+/// `None` if form body has no such field or it points to another site
+///
+/// Browsers read backslashes as slashes and drop tabs and line breaks,
+/// so `/\host` and `/\t/host` are rejected like `//host`
+pub fn redirect_target(body: &[u8]) -> Option<String> {
+    let value = form_field(body, "redirect")?;
+    let local = value.starts_with('/')
+        && !value.starts_with("//")
+        && !value.contains('\\')
+        && !value.chars().any(char::is_control);
+    match local {
+        true => Some(value),
+        false => None,
+    }
+}
+
+/// Returns decoded value of form field with `name`,
+/// `None` if form body has no such field or it is not decodable
+pub fn form_field(body: &[u8], name: &str) -> Option<String> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .filter_map(|field| field.split_once('='))
+        .find(|(field_name, _)| *field_name == name)
+        .and_then(|(_, value)| form_decode(value))
+}
+
+/// Decodes `application/x-www-form-urlencoded` value,
+/// `None` if it has broken escapes or is not UTF-8
+fn form_decode(value: &str) -> Option<String> {
+    percent_decode(value, true)
+}
+
+/// Decodes percent escapes of request path, `+` is kept as is.
+/// `None` if it has broken escapes or is not UTF-8
+pub fn path_decode(path: &str) -> Option<String> {
+    percent_decode(path, false)
+}
+
+fn percent_decode(value: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = vec![];
+    let mut input = value.bytes();
+    while let Some(byte) = input.next() {
+        bytes.push(match byte {
+            b'+' if plus_as_space => b' ',
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            byte => byte,
+        });
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Returns `segment` with every byte except unreserved URL characters percent-encoded
+pub fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Returns decoded local `path` with its segments percent-encoded, e.g. for `Location` header
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(percent_encode)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Returns page with state, reading and report of device
+pub fn device_page(
+    home: &Home,
+    device_info: &DeviceInfo,
+    report: &str,
+    user: Option<&User>,
+) -> String {
+    let path = escape_html(&format!("/status_device/{}", device_info.device_name));
+    let mut table = Table::new().with_attributes([
+        (
+            "data-device",
+            escape_html(&device_info.device_name).as_str(),
+        ),
+        ("data-reload", ""),
+    ]);
+    if let Some(device) = home
+        .devices()
+        .find(|(room_name, device)| {
+            *room_name == device_info.room_name && device.name() == device_info.device_name
+        })
+        .map(|(_, device)| device)
+    {
+        table.add_custom_body_row(
+            TableRow::new()
+                .with_cell(TableCell::new(TableCellType::Header).with_raw("Room"))
+                .with_cell(TableCell::default().with_raw(escape_html(&device_info.room_name))),
+        );
+        table.add_custom_body_row(
+            TableRow::new()
+                .with_cell(TableCell::new(TableCellType::Header).with_raw("State"))
+                .with_cell(state_cell(device)),
+        );
+        table.add_custom_body_row(
+            TableRow::new()
+                .with_cell(TableCell::new(TableCellType::Header).with_raw("Reading"))
+                .with_cell(reading_cell(home, device)),
+        );
+        table.add_custom_body_row(
+            TableRow::new()
+                .with_cell(TableCell::default())
+                .with_cell(TableCell::default().with_raw(switch_forms(device, &path, user))),
+        );
+    }
+    page(&device_info.device_name)
+        .with_header(1, escape_html(&device_info.device_name))
+        .with_table(table)
+        .with_header(2, "Report")
+        .with_preformatted(escape_html(report))
+        .with_link("/", "Return home")
+        .to_html_string()
+}
+
+/// Returns login form posting user name and password,
+/// `redirect` is local path shown after login
+pub fn login_page(redirect: &str, error: Option<&str>) -> String {
+    let page = plain_page("Log in").with_header(1, "Log in");
+    let page = match error {
+        Some(error) => page.with_paragraph(escape_html(error)),
+        None => page,
+    };
+    page.with_raw(format!(
+        "<form method=\"post\" action=\"/login\">\
+         <input type=\"hidden\" name=\"redirect\" value=\"{}\">\
+         <p><label>User <input name=\"username\" autocomplete=\"username\" required></label></p>\
+         <p><label>Password <input type=\"password\" name=\"password\" \
+         autocomplete=\"current-password\" required></label></p>\
+         <button>Log in</button>\
+         </form>",
+        escape_html(redirect)
+    ))
+    .to_html_string()
+}
+
+/// Returns empty page updating devices from event stream
+fn page(title: &str) -> HtmlPage {
+    plain_page(title).with_script_literal(SCRIPT)
+}
+
+fn plain_page(title: &str) -> HtmlPage {
+    HtmlPage::new()
+        .with_title(escape_html(title))
+        .with_meta([("charset", "utf-8")])
+        .with_style(STYLE)
+}
+
+fn state_cell(device: &dyn Device) -> TableCell {
+    TableCell::default()
+        .with_attributes([("class", format!("state {}", device.state()).as_str())])
+        .with_raw(device.state())
+}
+
+fn reading_cell(home: &Home, device: &dyn Device) -> TableCell {
+    TableCell::default()
+        .with_attributes([("class", "reading")])
+        .with_raw(escape_html(&reading_text(home, device.reading())))
+}
+
+/// Returns reading in home units or dash if there is none
+pub fn reading_text(home: &Home, reading: Option<Reading>) -> String {
+    match reading {
+        Some(Reading::Power(power)) => format!("{} W", power),
+        Some(Reading::Temperature(temperature)) => home.temperature_display().format(temperature),
+        None => "-".to_string(),
+    }
+}
+
+/// Returns forms turning device on and off and redirecting to HTML-escaped `redirect` afterwards,
+/// nothing for users who may not switch devices
+fn switch_forms(device: &dyn Device, redirect: &str, user: Option<&User>) -> String {
+    if !user.is_none_or(User::may_control) {
+        return String::new();
+    }
+    let name = percent_encode(device.name());
+    [
+        ("turn_on", "On", DeviceState::On),
+        ("turn_off", "Off", DeviceState::Off),
+    ]
+    .into_iter()
+    .map(|(action, label, state)| {
+        format!(
+            "<form method=\"post\" action=\"/{action}/{name}\">\
+                 <input type=\"hidden\" name=\"redirect\" value=\"{redirect}\">\
+                 <button{}>{label}</button>\
+                 </form>",
+            match *device.state() == state {
+                true => " disabled",
+                false => "",
+            }
+        )
+    })
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_redirect_target() {
+        assert_eq!(redirect_target(b"redirect=%2F"), Some("/".to_string()));
+        assert_eq!(
+            redirect_target(b"a=1&redirect=/status_device/my+socket"),
+            Some("/status_device/my socket".to_string())
+        );
+        assert_eq!(redirect_target(b""), None);
+        assert_eq!(redirect_target(b"redirect=%2"), None);
+        assert_eq!(redirect_target(b"redirect=https://example.com/"), None);
+        assert_eq!(redirect_target(b"redirect=//example.com/"), None);
+        assert_eq!(redirect_target(b"redirect=/%5Cexample.com/"), None);
+        assert_eq!(redirect_target(b"redirect=/\\example.com/"), None);
+        assert_eq!(redirect_target(b"redirect=/%09/example.com/"), None);
+        assert_eq!(redirect_target(b"redirect=/%0D%0ASet-Cookie:a"), None);
+    }
+    #[test]
+    fn encode_and_decode_paths() {
+        assert_eq!(percent_encode("my socket/1+1%"), "my%20socket%2F1%2B1%25");
+        assert_eq!(
+            percent_encode("розетка"),
+            "%D1%80%D0%BE%D0%B7%D0%B5%D1%82%D0%BA%D0%B0"
+        );
+        assert_eq!(
+            encode_path("/status_device/my socket?"),
+            "/status_device/my%20socket%3F"
+        );
+        assert_eq!(
+            path_decode("/status_device/my%20socket+1"),
+            Some("/status_device/my socket+1".to_string())
+        );
+        assert_eq!(path_decode("/%D1%80"), Some("/р".to_string()));
+        assert_eq!(path_decode("/%2"), None);
+        assert_eq!(path_decode("/%FF"), None);
+    }
+}
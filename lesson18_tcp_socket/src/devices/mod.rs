@@ -1,2 +0,0 @@
-/// Example devices
-pub mod socket;
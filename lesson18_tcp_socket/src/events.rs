@@ -0,0 +1,246 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::api::DeviceObject;
+
+pub const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+/// Time clients wait before reconnecting to closed event stream
+const RETRY_MILLIS: u64 = 3000;
+
+/// Device object sent in events, with reading formatted in home units
+#[derive(Debug, Serialize)]
+pub struct DeviceEvent {
+    #[serde(flatten)]
+    pub device: DeviceObject,
+    pub reading: String,
+}
+
+/// Server-sent event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub id: u64,
+    pub name: &'static str,
+    /// JSON on a single line
+    pub data: String,
+}
+
+impl Event {
+    /// Returns event in `text/event-stream` format
+    pub fn to_stream_string(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id, self.name, self.data
+        )
+    }
+}
+
+/// Stream receiving events
+struct Subscriber {
+    /// Shared with delivering thread writing outside the lock
+    stream: Arc<TcpStream>,
+    /// Id of the last event written into stream
+    last_id: u64,
+}
+
+struct EventLogInner {
+    events: VecDeque<Event>,
+    last_id: u64,
+    subscribers: Vec<Subscriber>,
+    closed: bool,
+}
+
+/// Log of recent events delivered to subscribed streams
+///
+/// Ids grow from 1, so clients reconnecting with `Last-Event-ID`
+/// receive events they missed if those are still in the log.
+/// Streams are written after the log is unlocked, so a slow subscriber
+/// does not block publishing
+pub struct EventLog {
+    inner: Mutex<EventLogInner>,
+    published: Condvar,
+    capacity: usize,
+}
+
+impl EventLog {
+    /// Returns log keeping at most `capacity` recent events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(EventLogInner {
+                events: VecDeque::new(),
+                last_id: 0,
+                subscribers: vec![],
+                closed: false,
+            }),
+            published: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+    /// Adds event with `value` serialized to JSON and wakes delivering thread
+    /// Returns id of the event
+    pub fn publish(&self, name: &'static str, value: &impl Serialize) -> serde_json::Result<u64> {
+        let data = serde_json::to_string(value)?;
+        let mut inner = self.lock();
+        inner.last_id += 1;
+        let id = inner.last_id;
+        inner.events.push_back(Event { id, name, data });
+        if inner.events.len() > self.capacity {
+            inner.events.pop_front();
+        }
+        self.published.notify_all();
+        Ok(id)
+    }
+    /// Returns id of the last published event, 0 if there is none
+    pub fn last_id(&self) -> u64 {
+        self.lock().last_id
+    }
+    /// Returns events published after `last_id`,
+    /// all events in the log if `last_id` is unknown, e.g. server was restarted
+    pub fn since(&self, last_id: u64) -> Vec<Event> {
+        Self::events_since(&self.lock(), last_id)
+    }
+    fn events_since(inner: &EventLogInner, last_id: u64) -> Vec<Event> {
+        let last_id = match last_id > inner.last_id {
+            true => 0,
+            false => last_id,
+        };
+        inner
+            .events
+            .iter()
+            .filter(|event| event.id > last_id)
+            .cloned()
+            .collect()
+    }
+    /// Writes events missed since `last_event_id` into stream
+    /// and subscribes it to new events, closes stream if log is closed
+    /// Returns `io::Err` if it failes to write to stream
+    pub fn subscribe(&self, mut stream: TcpStream, last_event_id: Option<u64>) -> io::Result<()> {
+        let (output, last_id) = {
+            let inner = self.lock();
+            if inner.closed {
+                return stream.shutdown(Shutdown::Both);
+            }
+            let mut output = format!("retry: {}\n\n", RETRY_MILLIS);
+            if let Some(last_event_id) = last_event_id {
+                for event in Self::events_since(&inner, last_event_id) {
+                    output.push_str(&event.to_stream_string());
+                }
+            }
+            (output, inner.last_id)
+        };
+        stream.write_all(output.as_bytes())?;
+        let mut inner = self.lock();
+        if inner.closed {
+            return stream.shutdown(Shutdown::Both);
+        }
+        // events published meanwhile are written by the next delivery
+        inner.subscribers.push(Subscriber {
+            stream: Arc::new(stream),
+            last_id,
+        });
+        Ok(())
+    }
+    /// Returns number of subscribed streams
+    pub fn subscribers(&self) -> usize {
+        self.lock().subscribers.len()
+    }
+    /// Waits at most `timeout` for events not delivered to subscribers yet
+    /// Returns `false` if log is closed
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let mut inner = self.lock();
+        if !inner.closed && inner.subscribers.iter().all(|s| s.last_id == inner.last_id) {
+            inner = self
+                .published
+                .wait_timeout(inner, timeout)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        !inner.closed
+    }
+    /// Writes new events into subscribed streams,
+    /// streams which cannot be written to are closed
+    pub fn deliver(&self) {
+        let pending: Vec<(Arc<TcpStream>, String)> = {
+            let mut inner = self.lock();
+            let EventLogInner {
+                events,
+                last_id,
+                subscribers,
+                ..
+            } = &mut *inner;
+            subscribers
+                .iter_mut()
+                .filter(|subscriber| subscriber.last_id != *last_id)
+                .map(|subscriber| {
+                    let output = events
+                        .iter()
+                        .filter(|event| event.id > subscriber.last_id)
+                        .map(Event::to_stream_string)
+                        .collect();
+                    subscriber.last_id = *last_id;
+                    (subscriber.stream.clone(), output)
+                })
+                .collect()
+        };
+        let failed: Vec<Arc<TcpStream>> = pending
+            .into_iter()
+            .filter_map(
+                |(stream, output)| match (&*stream).write_all(output.as_bytes()) {
+                    Ok(()) => None,
+                    Err(e) => {
+                        eprintln!("Event stream error: {}", e);
+                        let _ = stream.shutdown(Shutdown::Both);
+                        Some(stream)
+                    }
+                },
+            )
+            .collect();
+        if !failed.is_empty() {
+            self.lock()
+                .subscribers
+                .retain(|subscriber| !failed.iter().any(|f| Arc::ptr_eq(f, &subscriber.stream)));
+        }
+    }
+    /// Closes all subscribed streams and wakes delivering thread
+    pub fn close(&self) {
+        let mut inner = self.lock();
+        inner.closed = true;
+        for subscriber in inner.subscribers.drain(..) {
+            let _ = subscriber.stream.shutdown(Shutdown::Both);
+        }
+        self.published.notify_all();
+    }
+    fn lock(&self) -> MutexGuard<'_, EventLogInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_after_last_event_id() {
+        let log = EventLog::new(2);
+        for value in 1..=3 {
+            assert_eq!(log.publish("number", &value).unwrap(), value);
+        }
+        // the first event is dropped from the full log
+        let since = |last_id| log.since(last_id);
+        let ids = |events: Vec<Event>| events.iter().map(|e| e.id).collect::<Vec<u64>>();
+        assert_eq!(ids(since(0)), [2, 3]);
+        assert_eq!(ids(since(2)), [3]);
+        assert!(since(3).is_empty());
+        assert_eq!(ids(since(7)), [2, 3]);
+        assert_eq!(
+            since(2)[0].to_stream_string(),
+            "id: 3\nevent: number\ndata: 3\n\n"
+        );
+    }
+}
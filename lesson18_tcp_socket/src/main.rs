@@ -1,29 +1,50 @@
-use std::{io, thread, time::Duration};
+use std::{env, io, time::Duration};
 
-mod client;
+use lesson8_lib::{Socket, Thermometer};
+
+mod api;
+mod auth;
 mod command;
-mod device;
-mod devices;
+mod dashboard;
+mod events;
+mod pool;
 mod request;
+mod response;
 mod server;
-use devices::socket::Socket;
-
-use crate::client::Client;
+mod websocket;
 
 fn main() -> io::Result<()> {
-    let mut server = server::Server::new("127.0.0.1", 9871)?;
+    let mut server = server::Server::new("127.0.0.1", 9871)?
+        .with_workers(4)
+        .with_max_connections(32)
+        .with_drain_timeout(Duration::from_secs(3))
+        .with_reading_interval(Duration::from_secs(2))
+        .with_ping_interval(Duration::from_secs(30));
+    // optional path of file home state is saved to on shutdown
+    if let Some(path) = env::args().nth(1) {
+        server = server.with_state_file(path);
+    }
+    // optional credentials file, server is open to everyone without it
+    if let Ok(path) = env::var("TCP_SOCKET_CREDENTIALS") {
+        server = server.with_auth(auth::Auth::load(path)?);
+    }
+    lesson18_shutdown::shutdown_on_signals(server.shutdown_handle())?;
     println!("Connected to 127.0.0.1:9871");
 
     let mut socket1 = Socket::new("socket1");
-    server.add_device(&mut socket1).expect("should be unique");
+    server
+        .add_device("kitchen", &mut socket1)
+        .expect("should be unique");
     let mut socket2 = Socket::new("socket2");
-    server.add_device(&mut socket2).expect("should be unique");
-    thread::spawn(|| {
-        thread::sleep(Duration::from_secs(5));
-        let mut client = Client::new("127.0.0.1:9871");
-        let _ = client.get_device_names();
-        
-    });
+    server
+        .add_device("kitchen", &mut socket2)
+        .expect("should be unique");
+    let mut thermo1 = Thermometer::new("thermo1");
+    server
+        .add_device("bedroom", &mut thermo1)
+        .expect("should be unique");
+    // devices are switched by `cargo run --example client` while server runs
     server.run()?;
+    println!("Server stopped");
     Ok(())
 }
@@ -0,0 +1,46 @@
+use std::{
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::Scope,
+};
+
+type Job<'scope> = Box<dyn FnOnce() + Send + 'scope>;
+
+/// Fixed number of scoped worker threads running jobs in order of arrival
+///
+/// Jobs may borrow from outside the scope. Workers stop after the pool is dropped
+/// and queued jobs are done, the scope waits for them
+pub struct ThreadPool<'scope> {
+    sender: Sender<Job<'scope>>,
+}
+
+impl<'scope> ThreadPool<'scope> {
+    /// Spawns `workers` threads in `scope`
+    pub fn new<'env>(scope: &'scope Scope<'scope, 'env>, workers: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job<'scope>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers.max(1) {
+            let receiver = receiver.clone();
+            scope.spawn(move || loop {
+                // lock is released before the job runs
+                let job = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+        }
+        Self { sender }
+    }
+    /// Queues job for the first free worker
+    pub fn execute(&self, job: impl FnOnce() + Send + 'scope) {
+        self.sender
+            .send(Box::new(job))
+            .expect("workers should run while pool exists");
+    }
+}
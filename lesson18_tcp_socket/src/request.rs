@@ -1,44 +1,635 @@
-use std::str::FromStr;
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, Read},
+    str::FromStr,
+};
 
-use crate::command::Command;
+use crate::{command::Command, dashboard};
 
-#[derive(Debug, Default)]
+/// Enum for request methods
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum RequestType {
     #[default]
     Get,
+    Head,
     Post,
-    Del,
     Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl RequestType {
+    /// Returns `true` if method only reads resources, see RFC 9110 section 9.2.1
+    pub fn is_safe(&self) -> bool {
+        matches!(self, Self::Get | Self::Head | Self::Options)
+    }
 }
 
 impl FromStr for RequestType {
     type Err = ();
 
+    /// Parses method name, method names are case-sensitive
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().trim() {
-            "get" => Ok(Self::Get),
-            "post" => Ok(Self::Post),
-            "del" => Ok(Self::Del),
-            "put" => Ok(Self::Put),
+        match s {
+            "GET" => Ok(Self::Get),
+            "HEAD" => Ok(Self::Head),
+            "POST" => Ok(Self::Post),
+            "PUT" => Ok(Self::Put),
+            "PATCH" => Ok(Self::Patch),
+            "DELETE" => Ok(Self::Delete),
+            "OPTIONS" => Ok(Self::Options),
             _ => Err(()),
         }
     }
 }
+
+impl Display for RequestType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestType::Get => write!(f, "GET"),
+            RequestType::Head => write!(f, "HEAD"),
+            RequestType::Post => write!(f, "POST"),
+            RequestType::Put => write!(f, "PUT"),
+            RequestType::Patch => write!(f, "PATCH"),
+            RequestType::Delete => write!(f, "DELETE"),
+            RequestType::Options => write!(f, "OPTIONS"),
+        }
+    }
+}
+
+/// Enum for supported protocol versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpVersion::Http10 => write!(f, "HTTP/1.0"),
+            HttpVersion::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
+
+/// Limits protecting server from oversized requests
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// Longest request line or header line in bytes
+    pub max_line: usize,
+    pub max_headers: usize,
+    /// Largest body in bytes
+    pub max_body: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_line: 8 * 1024,
+            max_headers: 100,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+/// Request headers in received order
+#[derive(Debug, Default)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    /// Returns value of the first header with `name` ignoring case
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    /// Returns values of all headers with `name` ignoring case
+    pub fn get_all<'h>(&'h self, name: &'h str) -> impl Iterator<Item = &'h str> {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    /// Returns `true` if comma separated header value contains `token` ignoring case
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name)
+            .flat_map(|v| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+}
+
+/// Enum for request reading errors
+#[derive(Debug)]
+pub enum RequestError {
+    Io(io::Error),
+    /// Request violates HTTP syntax
+    Malformed(String),
+    UnknownMethod(String),
+    UnsupportedVersion(String),
+    LineTooLong,
+    TooManyHeaders,
+    BodyTooLarge(usize),
+}
+
+impl From<io::Error> for RequestError {
+    fn from(value: io::Error) -> Self {
+        RequestError::Io(value)
+    }
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RequestError::Io(e) => e.to_string(),
+                RequestError::Malformed(reason) => format!("Malformed request: {}", reason),
+                RequestError::UnknownMethod(method) => format!("Unknown method {}", method),
+                RequestError::UnsupportedVersion(version) =>
+                    format!("Unsupported version {}", version),
+                RequestError::LineTooLong => "Request line or header is too long".to_string(),
+                RequestError::TooManyHeaders => "Too many headers".to_string(),
+                RequestError::BodyTooLarge(length) =>
+                    format!("Body of {} bytes is too large", length),
+            }
+        )
+    }
+}
+
+impl Error for RequestError {}
+
+/// Parsed HTTP request
 #[derive(Debug)]
 pub struct Request {
     pub req_type: RequestType,
     pub command: Command,
+    /// Request target as sent by client
+    pub target: String,
+    /// Path of request target without query
+    pub path: String,
+    pub version: HttpVersion,
+    pub headers: Headers,
+    pub body: Vec<u8>,
 }
+
 impl Request {
-    pub fn new(req_type: RequestType, command: Command) -> Self {
-        Self { req_type, command }
+    /// Reads one request
+    ///
+    /// Returns `Ok(None)` if connection is closed before a request starts,
+    /// `Err` if request is malformed, exceeds `limits` or cannot be read
+    pub fn read(
+        reader: &mut impl BufRead,
+        limits: &RequestLimits,
+    ) -> Result<Option<Self>, RequestError> {
+        // empty lines before request line are ignored
+        let request_line = loop {
+            match read_line(reader, limits.max_line)? {
+                None => return Ok(None),
+                Some(line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+        let parts: Vec<&str> = request_line.split(' ').collect();
+        let [method, target, version] = parts[..] else {
+            return Err(RequestError::Malformed(format!(
+                "invalid request line {:?}",
+                request_line
+            )));
+        };
+        if method.is_empty() || !method.bytes().all(is_token_byte) {
+            return Err(RequestError::Malformed(format!(
+                "invalid method {:?}",
+                method
+            )));
+        }
+        let req_type = RequestType::from_str(method)
+            .map_err(|_| RequestError::UnknownMethod(method.to_string()))?;
+        let version = match version {
+            "HTTP/1.1" => HttpVersion::Http11,
+            "HTTP/1.0" => HttpVersion::Http10,
+            v if v.starts_with("HTTP/") => return Err(RequestError::UnsupportedVersion(v.into())),
+            v => return Err(RequestError::Malformed(format!("invalid version {:?}", v))),
+        };
+        let path = request_path(target)?;
+
+        let mut headers = Headers::default();
+        loop {
+            let line = read_line(reader, limits.max_line)?.ok_or_else(|| {
+                RequestError::Malformed("connection closed inside headers".to_string())
+            })?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                return Err(RequestError::Malformed(
+                    "obsolete header line folding".to_string(),
+                ));
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(RequestError::Malformed(format!(
+                    "invalid header {:?}",
+                    line
+                )));
+            };
+            if name.is_empty() || !name.bytes().all(is_token_byte) {
+                return Err(RequestError::Malformed(format!(
+                    "invalid header name {:?}",
+                    name
+                )));
+            }
+            if headers.entries.len() == limits.max_headers {
+                return Err(RequestError::TooManyHeaders);
+            }
+            headers.entries.push((
+                name.to_string(),
+                value.trim_matches([' ', '\t']).to_string(),
+            ));
+        }
+        if version == HttpVersion::Http11 && headers.get_all("host").count() != 1 {
+            return Err(RequestError::Malformed(
+                "exactly one Host header is required".to_string(),
+            ));
+        }
+        if headers.get("transfer-encoding").is_some() {
+            return Err(RequestError::Malformed(
+                "transfer encodings are not supported".to_string(),
+            ));
+        }
+
+        let length = {
+            let mut lengths = headers.get_all("content-length").flat_map(|v| v.split(','));
+            match lengths.next() {
+                None => 0,
+                Some(first) => {
+                    let length = parse_content_length(first)?;
+                    for other in lengths {
+                        if parse_content_length(other)? != length {
+                            return Err(RequestError::Malformed(
+                                "conflicting Content-Length headers".to_string(),
+                            ));
+                        }
+                    }
+                    length
+                }
+            }
+        };
+        if length > limits.max_body {
+            return Err(RequestError::BodyTooLarge(length));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                RequestError::Malformed("body is shorter than Content-Length".to_string())
+            }
+            _ => RequestError::Io(e),
+        })?;
+
+        let command = Command::from_str(&path).map_err(|_| {
+            RequestError::Malformed(format!("cannot parse request target {:?}", target))
+        })?;
+        Ok(Some(Self {
+            req_type,
+            command,
+            target: target.to_string(),
+            path,
+            version,
+            headers,
+            body,
+        }))
+    }
+    /// Returns `true` if connection stays open after response
+    pub fn keep_alive(&self) -> bool {
+        match self.version {
+            HttpVersion::Http11 => !self.headers.has_token("connection", "close"),
+            HttpVersion::Http10 => self.headers.has_token("connection", "keep-alive"),
+        }
+    }
+    /// Returns decoded value of parameter `name` in query of request target
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let (_, query) = self.target.split_once('?')?;
+        let query = query.split('#').next().unwrap_or_default();
+        dashboard::form_field(query.as_bytes(), name)
+    }
+    /// Returns `true` if request addresses JSON API
+    pub fn is_api(&self) -> bool {
+        self.path == "/api" || self.path.starts_with("/api/")
+    }
+    /// Returns response format negotiated with `Accept` header
+    ///
+    /// API requests default to JSON, other requests to HTML
+    pub fn format(&self) -> ResponseFormat {
+        let default = match self.is_api() {
+            true => ResponseFormat::Json,
+            false => ResponseFormat::Html,
+        };
+        let Some(accept) = self.headers.get("accept") else {
+            return default;
+        };
+        let json = accept_quality(accept, "application", "json");
+        let html = accept_quality(accept, "text", "html");
+        match json.partial_cmp(&html) {
+            Some(Ordering::Greater) => ResponseFormat::Json,
+            Some(Ordering::Less) => ResponseFormat::Html,
+            _ => default,
+        }
     }
 }
-impl Default for Request {
-    fn default() -> Self {
-        Self {
-            req_type: RequestType::Get,
-            command: Command::ShowMain,
+
+/// Enum for formats of response bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Html,
+    Json,
+}
+
+impl Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}, body {} bytes",
+            self.req_type,
+            self.target,
+            self.version,
+            self.body.len()
+        )
+    }
+}
+
+/// Reads line ending with `\n` and strips line ending
+///
+/// Returns `Ok(None)` if stream ends before the line starts
+fn read_line(reader: &mut impl BufRead, max_line: usize) -> Result<Option<String>, RequestError> {
+    let mut line = vec![];
+    // line ending is not counted in the limit
+    reader
+        .take(max_line as u64 + 2)
+        .read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n") {
+        return Err(match line.len() > max_line {
+            true => RequestError::LineTooLong,
+            false => RequestError::Malformed("connection closed inside a line".to_string()),
+        });
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    if line.len() > max_line {
+        return Err(RequestError::LineTooLong);
+    }
+    String::from_utf8(line).map(Some).map_err(|_| {
+        RequestError::Malformed("request line and headers should be valid UTF-8".to_string())
+    })
+}
+
+/// Returns decoded path of origin-form or absolute-form request target
+///
+/// Encoded slashes are rejected, so path segments stay unambiguous
+fn request_path(target: &str) -> Result<String, RequestError> {
+    let path = match target.split_once("://") {
+        Some((scheme, rest))
+            if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") =>
+        {
+            rest.find('/').map(|i| &rest[i..]).unwrap_or("/")
+        }
+        _ => target,
+    };
+    let invalid = || RequestError::Malformed(format!("invalid request target {:?}", target));
+    if !path.starts_with('/') || path.bytes().any(|b| b.is_ascii_control() || b == b' ') {
+        return Err(invalid());
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    if path.to_ascii_lowercase().contains("%2f") {
+        return Err(invalid());
+    }
+    match dashboard::path_decode(path) {
+        Some(path) if !path.chars().any(char::is_control) => Ok(path),
+        _ => Err(invalid()),
+    }
+}
+
+/// Returns quality of the most specific media range in `accept` matching `kind/subtype`
+fn accept_quality(accept: &str, kind: &str, subtype: &str) -> f32 {
+    let mut best = (0, 0.0);
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let Some((range_kind, range_subtype)) =
+            params.next().and_then(|m| m.trim().split_once('/'))
+        else {
+            continue;
+        };
+        let specificity = match (range_kind, range_subtype) {
+            (k, s) if k.eq_ignore_ascii_case(kind) && s.eq_ignore_ascii_case(subtype) => 3,
+            (k, "*") if k.eq_ignore_ascii_case(kind) => 2,
+            ("*", "*") => 1,
+            _ => continue,
+        };
+        let quality = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if specificity > best.0 {
+            best = (specificity, quality);
+        }
+    }
+    best.1
+}
+
+fn parse_content_length(value: &str) -> Result<usize, RequestError> {
+    let value = value.trim();
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RequestError::Malformed(format!(
+            "invalid Content-Length {:?}",
+            value
+        )));
+    }
+    value
+        .parse()
+        .map_err(|_| RequestError::BodyTooLarge(usize::MAX))
+}
+
+/// Returns `true` for bytes allowed in method and header names
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(request: &str) -> Result<Option<Request>, RequestError> {
+        Request::read(&mut request.as_bytes(), &RequestLimits::default())
+    }
+
+    #[test]
+    fn parse_request_with_headers_and_body() {
+        let request = read(
+            "\r\nPOST /turn_on/socket1?from=form HTTP/1.1\r\nHOST: localhost\r\n\
+             content-length: 5\r\nX-Empty:\r\nAccept:  text/html \r\n\r\nhello",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.req_type, RequestType::Post);
+        assert!(matches!(
+            request.command,
+            Command::TurnOn { ref device_name } if device_name == "socket1"
+        ));
+        assert_eq!(request.target, "/turn_on/socket1?from=form");
+        assert_eq!(request.path, "/turn_on/socket1");
+        assert_eq!(request.version, HttpVersion::Http11);
+        assert_eq!(request.headers.get("Host"), Some("localhost"));
+        assert_eq!(request.headers.get("accept"), Some("text/html"));
+        assert_eq!(request.headers.get("x-empty"), Some(""));
+        assert_eq!(request.body, b"hello");
+        assert!(request.keep_alive());
+        assert_eq!(
+            request.to_string(),
+            "POST /turn_on/socket1?from=form HTTP/1.1, body 5 bytes"
+        );
+    }
+    #[test]
+    fn parse_methods() {
+        for method in ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"] {
+            let request = read(&format!("{method} / HTTP/1.1\r\nHost: a\r\n\r\n"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(request.req_type.to_string(), method);
+        }
+    }
+    #[test]
+    fn read_pipelined_requests() {
+        let mut stream = "GET / HTTP/1.1\nHost: a\n\nGET http://a/metrics HTTP/1.0\n\n".as_bytes();
+        let limits = RequestLimits::default();
+        let first = Request::read(&mut stream, &limits).unwrap().unwrap();
+        assert!(matches!(first.command, Command::ShowMain));
+        let second = Request::read(&mut stream, &limits).unwrap().unwrap();
+        assert!(matches!(second.command, Command::GetMetrics));
+        assert!(!second.keep_alive());
+        assert!(Request::read(&mut stream, &limits).unwrap().is_none());
+    }
+    #[test]
+    fn connection_header() {
+        let close = read("GET / HTTP/1.1\r\nHost: a\r\nConnection: TE, close\r\n\r\n");
+        assert!(!close.unwrap().unwrap().keep_alive());
+        let keep = read("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n");
+        assert!(keep.unwrap().unwrap().keep_alive());
+    }
+    #[test]
+    fn negotiate_format() {
+        let format = |target: &str, accept: &str| {
+            let accept = match accept {
+                "" => String::new(),
+                accept => format!("Accept: {accept}\r\n"),
+            };
+            read(&format!("GET {target} HTTP/1.1\r\nHost: a\r\n{accept}\r\n"))
+                .unwrap()
+                .unwrap()
+                .format()
+        };
+        assert_eq!(format("/status_all", ""), ResponseFormat::Html);
+        assert_eq!(format("/api/devices", ""), ResponseFormat::Json);
+        assert_eq!(format("/api/devices", "*/*"), ResponseFormat::Json);
+        assert_eq!(format("/api/devices", "text/html"), ResponseFormat::Html);
+        assert_eq!(
+            format("/status_all", "application/json"),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            format(
+                "/status_all",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+            ),
+            ResponseFormat::Html
+        );
+        assert_eq!(
+            format("/status_all", "text/html;q=0.5, application/*"),
+            ResponseFormat::Json
+        );
+    }
+    #[test]
+    fn malformed_requests() {
+        let malformed = [
+            "GET /\r\n\r\n",
+            "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "G(T / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET status HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /status_device/a%2Fb HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /status_device/%0A HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /status_device/%E2%28 HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTX/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nBad Name: x\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nNo-Colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nX-Long: a\r\n  folded\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nContent-Length: -1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            "GET / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nshort",
+            "GET / HTTP/1.1\r\nHost: a\r\n",
+            "GET / HTTP/1.1",
+        ];
+        for request in malformed {
+            assert!(
+                matches!(read(request), Err(RequestError::Malformed(_))),
+                "{request:?}"
+            );
+        }
+        for method in ["BREW", "get", "DEL"] {
+            assert!(matches!(
+                read(&format!("{method} / HTTP/1.1\r\nHost: a\r\n\r\n")),
+                Err(RequestError::UnknownMethod(_))
+            ));
         }
+        assert!(matches!(
+            read("GET / HTTP/2.0\r\nHost: a\r\n\r\n"),
+            Err(RequestError::UnsupportedVersion(_))
+        ));
+        assert!(read("").unwrap().is_none());
+    }
+    #[test]
+    fn limits() {
+        let limits = RequestLimits {
+            max_line: 32,
+            max_headers: 2,
+            max_body: 4,
+        };
+        let read = |request: String| Request::read(&mut request.as_bytes(), &limits);
+        let target = "/".repeat(20);
+        assert!(matches!(
+            read(format!("GET {target} HTTP/1.1\r\nHost: a\r\n\r\n")),
+            Err(RequestError::LineTooLong)
+        ));
+        assert!(matches!(
+            read(format!(
+                "GET / HTTP/1.1\r\nHost: {}\r\n\r\n",
+                "a".repeat(40)
+            )),
+            Err(RequestError::LineTooLong)
+        ));
+        assert!(read(format!(
+            "GET / HTTP/1.1\r\nHost: {}\r\n\r\n",
+            "a".repeat(26)
+        ))
+        .is_ok());
+        assert!(matches!(
+            read("GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\n\r\n".to_string()),
+            Err(RequestError::TooManyHeaders)
+        ));
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello".to_string()),
+            Err(RequestError::BodyTooLarge(5))
+        ));
     }
 }
@@ -0,0 +1,292 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::request::RequestError;
+
+pub const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Enum for response status codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Connection is upgraded to protocol given in `Upgrade` header
+    SwitchingProtocols,
+    Ok,
+    NoContent,
+    /// Result of form submission is shown at `Location`
+    SeeOther,
+    BadRequest,
+    /// Request lacks valid credentials, see `WWW-Authenticate` header
+    Unauthorized,
+    /// User is not permitted to make request
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    /// Request conflicts with device state, e.g. tripped device is turned on
+    Conflict,
+    PayloadTooLarge,
+    /// Resource is served only with protocol given in `Upgrade` header
+    UpgradeRequired,
+    HeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    ServiceUnavailable,
+    VersionNotSupported,
+}
+
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Status::SwitchingProtocols => 101,
+            Status::Ok => 200,
+            Status::NoContent => 204,
+            Status::SeeOther => 303,
+            Status::BadRequest => 400,
+            Status::Unauthorized => 401,
+            Status::Forbidden => 403,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed => 405,
+            Status::Conflict => 409,
+            Status::PayloadTooLarge => 413,
+            Status::UpgradeRequired => 426,
+            Status::HeaderFieldsTooLarge => 431,
+            Status::InternalServerError => 500,
+            Status::NotImplemented => 501,
+            Status::ServiceUnavailable => 503,
+            Status::VersionNotSupported => 505,
+        }
+    }
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Status::SwitchingProtocols => "Switching Protocols",
+            Status::Ok => "OK",
+            Status::NoContent => "No Content",
+            Status::SeeOther => "See Other",
+            Status::BadRequest => "Bad Request",
+            Status::Unauthorized => "Unauthorized",
+            Status::Forbidden => "Forbidden",
+            Status::NotFound => "Not Found",
+            Status::MethodNotAllowed => "Method Not Allowed",
+            Status::Conflict => "Conflict",
+            Status::PayloadTooLarge => "Payload Too Large",
+            Status::UpgradeRequired => "Upgrade Required",
+            Status::HeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Status::InternalServerError => "Internal Server Error",
+            Status::NotImplemented => "Not Implemented",
+            Status::ServiceUnavailable => "Service Unavailable",
+            Status::VersionNotSupported => "HTTP Version Not Supported",
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+impl From<&RequestError> for Status {
+    fn from(value: &RequestError) -> Self {
+        match value {
+            RequestError::Io(_) => Status::InternalServerError,
+            RequestError::Malformed(_) => Status::BadRequest,
+            RequestError::UnknownMethod(_) => Status::NotImplemented,
+            RequestError::UnsupportedVersion(_) => Status::VersionNotSupported,
+            RequestError::LineTooLong | RequestError::TooManyHeaders => {
+                Status::HeaderFieldsTooLarge
+            }
+            RequestError::BodyTooLarge(_) => Status::PayloadTooLarge,
+        }
+    }
+}
+
+/// HTTP response builder
+///
+/// `Date`, `Connection` and `Content-Length` headers are added on writing
+#[derive(Debug)]
+pub struct Response {
+    status: Status,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Returns response without body
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+    /// Returns response with html page
+    pub fn html(status: Status, content: String) -> Self {
+        Self::new(status).with_body(HTML_CONTENT_TYPE, content)
+    }
+    /// Returns response with `value` serialized to JSON,
+    /// `500 Internal Server Error` if it cannot be serialized
+    pub fn json(status: Status, value: &impl Serialize) -> Self {
+        match serde_json::to_string(value) {
+            Ok(content) => Self::new(status).with_body(JSON_CONTENT_TYPE, content),
+            Err(e) => Self::new(Status::InternalServerError).with_body(
+                JSON_CONTENT_TYPE,
+                format!(r#"{{"error":{:?}}}"#, e.to_string()),
+            ),
+        }
+    }
+    /// Adds header
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+    /// Sets body and its `Content-Type`
+    pub fn with_body(self, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        let mut response = self.with_header("Content-Type", content_type);
+        response.body = body.into();
+        response
+    }
+    /// Writes response into stream
+    /// Returns `io::Err` if it failes to write to stream
+    pub fn write_to<T>(&self, stream: T, keep_alive: bool) -> io::Result<()>
+    where
+        T: Write,
+    {
+        self.write(stream, keep_alive, Part::Whole)
+    }
+    /// Writes response without body into stream, e.g. to answer `HEAD` request
+    /// Returns `io::Err` if it failes to write to stream
+    pub fn write_head_to<T>(&self, stream: T, keep_alive: bool) -> io::Result<()>
+    where
+        T: Write,
+    {
+        self.write(stream, keep_alive, Part::Head)
+    }
+    /// Writes head of response streaming body until connection is closed
+    /// or switching connection to another protocol
+    /// Returns `io::Err` if it failes to write to stream
+    pub fn write_stream_head_to<T>(&self, stream: T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        self.write(stream, false, Part::StreamHead)
+    }
+    fn write<T>(&self, mut stream: T, keep_alive: bool, part: Part) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nDate: {}\r\nConnection: {}\r\n",
+            self.status,
+            http_date(SystemTime::now()),
+            match (self.status, keep_alive) {
+                (Status::SwitchingProtocols, _) => "Upgrade",
+                (_, true) => "keep-alive",
+                (_, false) => "close",
+            }
+        );
+        for (name, value) in self.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        // responses without content or with streamed body do not have Content-Length
+        if !matches!(self.status, Status::NoContent | Status::SwitchingProtocols)
+            && part != Part::StreamHead
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        // single write keeps small responses in one packet
+        let mut response = head.into_bytes();
+        if part == Part::Whole {
+            response.extend_from_slice(&self.body);
+        }
+        stream.write_all(&response)?;
+        stream.flush()
+    }
+}
+
+/// Parts of response written into stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Whole,
+    Head,
+    StreamHead,
+}
+
+/// Returns time formatted as IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+    // civil date from days since epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn format_http_date() {
+        let date = |secs| http_date(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(date(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(date(1798761599), "Thu, 31 Dec 2026 23:59:59 GMT");
+    }
+    #[test]
+    fn write_response() {
+        let mut output = vec![];
+        Response::json(Status::NotFound, &["a"])
+            .with_header("Allow", "GET")
+            .write_to(&mut output, false)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        let lines: Vec<&str> = head.split("\r\n").collect();
+        assert_eq!(lines[0], "HTTP/1.1 404 Not Found");
+        assert!(lines[1].starts_with("Date: ") && lines[1].ends_with(" GMT"));
+        assert_eq!(
+            lines[2..],
+            [
+                "Connection: close",
+                "Content-Type: application/json",
+                "Allow: GET",
+                "Content-Length: 5"
+            ]
+        );
+        assert_eq!(body, r#"["a"]"#);
+    }
+}
@@ -1,264 +1,2058 @@
 use std::{
+    collections::HashMap,
+    fs,
     io::{self, BufRead, BufReader, Write},
-    net::TcpListener,
+    net::{Shutdown, TcpListener, TcpStream},
+    ops::{Deref, DerefMut},
+    path::PathBuf,
     str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-use build_html::{Html, HtmlContainer, HtmlPage};
-use device::Device;
+use build_html::{escape_html, Html, HtmlContainer, HtmlPage};
+use lesson18_shutdown::{accept_until_shutdown, ShutdownHandle, POLL_INTERVAL};
+use lesson8_lib::{
+    render_metrics, Device, DeviceInfo, DeviceState, Home, Query, UserContext,
+    METRICS_CONTENT_TYPE, OWNER,
+};
+use serde::Serialize;
 
 use crate::{
+    api::{parse_state, ClientMessage, DeviceObject, ErrorObject, ServerMessage},
+    auth::{self, Auth, AuthError, User},
     command::Command,
-    device,
-    request::{Request, RequestType},
+    dashboard,
+    events::{DeviceEvent, EventLog, EVENT_STREAM_CONTENT_TYPE},
+    pool::ThreadPool,
+    request::{Headers, Request, RequestError, RequestLimits, RequestType, ResponseFormat},
+    response::{Response, Status},
+    websocket::{
+        self, Frame, HandshakeError, Message, MessageReader, Opcode, WebSocketError,
+        CLOSE_GOING_AWAY, CLOSE_NORMAL, CLOSE_UNSUPPORTED_DATA,
+    },
 };
+
+/// Time client is given to send the first request and each part of a request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time idle keep-alive connection holds a worker waiting for the next request
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Time accepting thread waits for rejected client to receive response
+const REJECT_TIMEOUT: Duration = Duration::from_millis(100);
+/// Time event stream is given to receive events before it is closed
+const EVENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Number of recent events sent to reconnecting event streams
+const EVENT_LOG_CAPACITY: usize = 256;
+/// Time WebSocket client is given to answer close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Protocol connection is handed over to after HTTP response
+#[derive(Debug)]
+enum Upgrade {
+    /// Server-sent events resumed after `Last-Event-ID`
+    Events {
+        last_event_id: Option<u64>,
+    },
+    WebSocket {
+        user: Option<User>,
+    },
+}
+
+/// Home shared by worker threads
+///
+/// Devices are not `Send` in general, so home is shared only because
+/// every device of server home is added by `Server::add_device` requiring `Send`
+struct SharedHome<'a>(Home<'a>);
+
+// SAFETY: devices are added only by `Server::add_device`, which takes `dyn Device + Send`,
+// the rest of home is `Send`
+unsafe impl Send for SharedHome<'_> {}
+
+impl<'a> Deref for SharedHome<'a> {
+    type Target = Home<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SharedHome<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 /// Server struct
 ///
-/// Runs TCP server and controlls device's states
+/// Runs TCP server and controlls states of home devices.
+/// Connections are served by pool of worker threads sharing home behind a lock.
+/// Devices are addressed by name, so names are unique in the whole home
 pub struct Server<'a> {
     address: String,
-    devices: Vec<&'a mut dyn Device>,
+    home: Mutex<SharedHome<'a>>,
+    limits: RequestLimits,
+    workers: usize,
+    max_connections: usize,
+    shutdown: ShutdownHandle,
+    /// Time served connections are given to finish after shutdown request
+    drain_timeout: Duration,
+    /// File home snapshot is written to after shutdown
+    state_file: Option<PathBuf>,
+    events: EventLog,
+    /// Time between events with readings of all devices
+    reading_interval: Duration,
+    /// Time idle WebSocket is pinged after and closed if ping is not answered
+    ping_interval: Duration,
+    /// Users allowed to make requests, `None` if server is open to everyone
+    auth: Option<Auth>,
 }
 
 impl<'a> Server<'a> {
     pub fn new(address: &str, port: u32) -> io::Result<Self> {
         Ok(Self {
             address: format!("{}:{}", address, port),
-            devices: vec![],
+            home: Mutex::new(SharedHome(Home::new("tcp_socket"))),
+            limits: RequestLimits::default(),
+            workers: 4,
+            max_connections: 64,
+            shutdown: ShutdownHandle::default(),
+            drain_timeout: Duration::from_secs(5),
+            state_file: None,
+            events: EventLog::new(EVENT_LOG_CAPACITY),
+            reading_interval: Duration::from_secs(5),
+            ping_interval: Duration::from_secs(30),
+            auth: None,
         })
     }
-    /// Adds device to device vector
+    /// Sets number of threads serving connections
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+    /// Sets number of connections served or waiting for a worker,
+    /// connections over the limit are rejected with `503 Service Unavailable`
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+    /// Sets time served connections are given to finish after shutdown request,
+    /// connections still open after it are closed
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+    /// Sets file home snapshot is written to in JSON after shutdown
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+    /// Sets time between events with readings of all devices
+    pub fn with_reading_interval(mut self, reading_interval: Duration) -> Self {
+        self.reading_interval = reading_interval;
+        self
+    }
+    /// Sets time idle WebSocket is pinged after,
+    /// WebSocket is closed if ping is not answered in the same time
+    pub fn with_ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+    /// Requires requests to authenticate as users of `auth`
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+    /// Returns handle stopping `run`
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+    /// Adds device to room, creates the room if needed
     /// Returns `Err(String)` if device name is not unique
-    pub fn add_device(&mut self, device: &'a mut dyn Device) -> Result<(), String> {
-        if self.devices.iter().any(|d| d.name() == device.name()) {
+    pub fn add_device(
+        &mut self,
+        room_name: &str,
+        device: &'a mut (dyn Device + Send),
+    ) -> Result<(), String> {
+        let home = self.home.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if Self::find_device(home, device.name()).is_some() {
             return Err(format!("Device with name {} exists!", device.name()));
         }
-        self.devices.push(device);
-        Ok(())
+        if !home.rooms().any(|r| r.name() == room_name) {
+            Self::owner(home)
+                .add_room(room_name)
+                .map_err(|e| e.to_string())?;
+        }
+        Self::owner(home)
+            .add_device(room_name, device)
+            .map_err(|e| e.to_string())
+    }
+    /// Returns home context of its owner, server changes home on behalf of the owner
+    fn owner<'h>(home: &'h mut Home<'a>) -> UserContext<'h, 'a> {
+        home.acting_as(OWNER).expect("home should have owner")
+    }
+    /// Returns position of device with `device_name` in home
+    fn find_device(home: &Home, device_name: &str) -> Option<DeviceInfo> {
+        home.devices()
+            .find(|(_, device)| device.name() == device_name)
+            .map(|(room_name, _)| DeviceInfo::new(device_name, room_name))
+    }
+    /// Returns all devices in home
+    fn device_infos(home: &Home) -> Vec<DeviceInfo> {
+        home.devices()
+            .map(|(room_name, device)| DeviceInfo::new(device.name(), room_name))
+            .collect()
     }
     /// Main worker
-    /// Returns `io::Err` if it cannot connect to given address
-    /// or serve stream
+    ///
+    /// Serves connections until shutdown is requested, then waits for served connections
+    /// and writes home snapshot if state file is set.
+    /// Returns `io::Err` if it cannot connect to given address or write state file
     pub fn run(&mut self) -> io::Result<()> {
         let listener = TcpListener::bind(self.address.as_str())?;
-        for stream in listener.incoming() {
-            let mut stream = stream?;
-            dbg!(stream.peer_addr()?);
-            let buf_reader = BufReader::new(&mut stream);
-// let http_request: Vec<_> = buf_reader
-//         .lines()
-//         .map(|result| result.unwrap())
-//         .take_while(|line| !line.is_empty())
-//         .collect();
-//                         dbg!(http_request);
-        
-            
-            let request_line = buf_reader
-                .lines()
-                .next()
-                .unwrap_or(Ok("GET / HTTP/1.1".to_string()))?;
-            dbg!(&request_line);
-            let request = Self::parse_request(request_line.as_str()).unwrap_or_default();
-            dbg!(&request);
-            match request {
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::TurnOn { device_name },
-                } => self.turn_on_device(stream, &device_name)?,
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::TurnOff { device_name },
-                } => self.turn_off_device(stream, &device_name)?,
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::GetStatus,
-                } => self.write_state_all(&mut stream)?,
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::ShowMain,
-                } => self.write_hello(&mut stream)?,
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::Error { error_msg },
-                } => self.write_error(&mut stream, &error_msg)?,
-                Request {
-                    req_type: RequestType::Get,
-                    command: Command::GetDeviceStatus { device_name },
-                } => self.write_state_device(&mut stream, &device_name)?,
-                Request {
-                    req_type: _,
-                    command: Command::Ignore,
-                } => (),
-                _ => self.write_error(&mut stream, "Page not found")?,
+        listener.set_nonblocking(true)?;
+        self.serve_connections(accept_until_shutdown(&listener, &self.shutdown));
+        drop(listener);
+        self.persist_state()
+    }
+    /// Serves connections on worker threads until `connections` end
+    /// and waits for served connections at most `drain_timeout`
+    ///
+    /// Connections over `max_connections`, counting event streams, are rejected
+    /// by accepting thread. Event streams are written by separate thread and WebSockets are served
+    /// by their own threads, so long-lived connections do not occupy workers
+    fn serve_connections<I>(&self, connections: I)
+    where
+        I: IntoIterator<Item = io::Result<TcpStream>>,
+    {
+        // connections served or waiting for a worker
+        let active = AtomicUsize::new(0);
+        // clones of connections closed when drain timeout expires
+        let open = Mutex::new(HashMap::new());
+        thread::scope(|scope| {
+            scope.spawn(|| self.deliver_events());
+            let pool = ThreadPool::new(scope, self.workers);
+            for (id, stream) in connections.into_iter().enumerate() {
+                let stream = match stream.and_then(|s| s.try_clone().map(|c| (s, c))) {
+                    Ok((stream, clone)) => {
+                        lock(&open).insert(id, clone);
+                        stream
+                    }
+                    Err(e) => {
+                        eprintln!("Accept error: {}", e);
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                };
+                // event streams leave `active` once subscribed, the log counts them
+                let open_streams =
+                    active.fetch_add(1, Ordering::SeqCst) + self.events.subscribers();
+                if open_streams >= self.max_connections {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    lock(&open).remove(&id);
+                    if let Err(e) = Self::reject(&stream) {
+                        eprintln!("Connection error: {}", e);
+                    }
+                    continue;
+                }
+                let (active, open) = (&active, &open);
+                pool.execute(move || {
+                    match self.serve_stream(&stream) {
+                        Ok(Some((reader, user))) => {
+                            scope.spawn(move || {
+                                if let Err(e) = self.serve_websocket(reader, user) {
+                                    eprintln!("WebSocket error: {}", e);
+                                }
+                                lock(open).remove(&id);
+                                active.fetch_sub(1, Ordering::SeqCst);
+                            });
+                            return;
+                        }
+                        Ok(None) => (),
+                        Err(e) => eprintln!("Connection error: {}", e),
+                    }
+                    lock(open).remove(&id);
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+
+            let deadline = Instant::now() + self.drain_timeout;
+            while active.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+                thread::sleep(POLL_INTERVAL);
+            }
+            for stream in lock(&open).values() {
+                // worker fails reading or writing and drops connection
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            self.events.close();
+        })
+    }
+    /// Delivers events to subscribed streams and publishes readings
+    /// every `reading_interval` until event log is closed
+    fn deliver_events(&self) {
+        let mut next_readings = Instant::now() + self.reading_interval;
+        while self.events.wait(POLL_INTERVAL) {
+            if Instant::now() >= next_readings {
+                let devices = Self::device_events(&lock(&self.home));
+                self.publish("readings", &devices);
+                next_readings = Instant::now() + self.reading_interval;
+            }
+            self.events.deliver();
+        }
+    }
+    /// Publishes event, logs error if value cannot be serialized
+    fn publish(&self, name: &'static str, value: &impl Serialize) {
+        if let Err(e) = self.events.publish(name, value) {
+            eprintln!("Event error: {}", e);
+        }
+    }
+    /// Serves requests of one connection
+    ///
+    /// Returns reader and user of connection upgraded to WebSocket,
+    /// `io::Err` if it failes to read from or write to connection
+    fn serve_stream(
+        &self,
+        stream: &TcpStream,
+    ) -> io::Result<Option<(BufReader<TcpStream>, Option<User>)>> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let set_read_timeout = |timeout| stream.set_read_timeout(Some(timeout));
+        match self.serve(&mut reader, stream, set_read_timeout)? {
+            None => Ok(None),
+            Some(Upgrade::Events { last_event_id }) => {
+                stream.set_write_timeout(Some(EVENT_WRITE_TIMEOUT))?;
+                self.events.subscribe(stream.try_clone()?, last_event_id)?;
+                Ok(None)
+            }
+            Some(Upgrade::WebSocket { user }) => Ok(Some((reader, user))),
+        }
+    }
+    /// Serves WebSocket messages until close handshake, shutdown request
+    /// or ping is not answered
+    ///
+    /// Subscribed events and pings are sent while waiting for client messages.
+    /// Returns `io::Err` if it failes to read from or write to connection
+    fn serve_websocket(
+        &self,
+        mut reader: BufReader<TcpStream>,
+        user: Option<User>,
+    ) -> io::Result<()> {
+        reader.get_ref().set_write_timeout(Some(READ_TIMEOUT))?;
+        let mut messages = MessageReader::new(true, self.limits.max_body);
+        // id of the last event sent to subscribed client
+        let mut subscription = None;
+        let mut last_activity = Instant::now();
+        let mut ping_sent = false;
+        loop {
+            if self.shutdown.is_requested() {
+                return Self::close_websocket(
+                    &mut reader,
+                    &mut messages,
+                    CLOSE_GOING_AWAY,
+                    "Server is shutting down",
+                );
+            }
+            if let Some(last_id) = &mut subscription {
+                for event in self.events.since(*last_id) {
+                    *last_id = event.id;
+                    let message = ServerMessage::Event {
+                        id: event.id,
+                        event: event.name,
+                        data: serde_json::from_str(&event.data).unwrap_or_default(),
+                    };
+                    Self::send(reader.get_ref(), &message)?;
+                }
+            }
+            if last_activity.elapsed() >= self.ping_interval {
+                if ping_sent {
+                    return Ok(());
+                }
+                Frame::new(Opcode::Ping, []).write_to(reader.get_ref(), None)?;
+                ping_sent = true;
+                last_activity = Instant::now();
+            }
+            // waits for the next frame without consuming partially received one
+            reader.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+            match reader.fill_buf() {
+                Ok([]) => return Ok(()),
+                Ok(_) => (),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+            reader.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+            let message = match messages.read(&mut reader) {
+                Ok(message) => message,
+                Err(WebSocketError::Io(e)) => return Err(e),
+                Err(e) => {
+                    return Frame::close(e.close_code(), &e.to_string())
+                        .write_to(reader.get_ref(), None)
+                }
+            };
+            last_activity = Instant::now();
+            ping_sent = false;
+            match message {
+                Message::Text(text) => {
+                    let reply = self.websocket_reply(&text, &mut subscription, user.as_ref());
+                    Self::send(reader.get_ref(), &reply)?;
+                }
+                Message::Binary(_) => {
+                    return Frame::close(CLOSE_UNSUPPORTED_DATA, "Messages should be JSON text")
+                        .write_to(reader.get_ref(), None)
+                }
+                Message::Ping(payload) => {
+                    Frame::new(Opcode::Pong, payload).write_to(reader.get_ref(), None)?
+                }
+                Message::Pong(_) => (),
+                Message::Close(code, _) => {
+                    return Frame::close(code.unwrap_or(CLOSE_NORMAL), "")
+                        .write_to(reader.get_ref(), None)
+                }
             }
         }
+    }
+    /// Sends close frame and waits for client close frame at most `CLOSE_TIMEOUT`,
+    /// messages received meanwhile are dropped
+    /// Returns `io::Err` if it failes to write to connection
+    fn close_websocket(
+        reader: &mut BufReader<TcpStream>,
+        messages: &mut MessageReader,
+        code: u16,
+        reason: &str,
+    ) -> io::Result<()> {
+        Frame::close(code, reason).write_to(reader.get_ref(), None)?;
+        reader.get_ref().set_read_timeout(Some(CLOSE_TIMEOUT))?;
+        while !matches!(messages.read(reader), Ok(Message::Close(..)) | Err(_)) {}
         Ok(())
     }
-    /// Parses request line and returns Request
-    /// Returns `Err(())` if request string is in wrong format
-    fn parse_request(request_line: &str) -> Result<Request, ()> {
-        let mut collection = request_line.split_whitespace();
-        let req_type = collection.next().unwrap_or("GET");
-        let req_type = RequestType::from_str(req_type)?;
-        let path = collection.next().unwrap_or("/");
-        let command = Command::from_str(path)?;
-        Ok(Request::new(req_type, command))
-    }
-    /// Writes http main page into stream
-    /// Returns `io::Err` if it failes to write to stream
-    fn write_hello<T>(&self, mut stream: T) -> io::Result<()>
+    /// Writes message of WebSocket control channel
+    /// Returns `io::Err` if it failes to write to connection
+    fn send(stream: &TcpStream, message: &ServerMessage) -> io::Result<()> {
+        Frame::new(Opcode::Text, serde_json::to_string(message)?).write_to(stream, None)
+    }
+    /// Returns reply to client message of WebSocket control channel,
+    /// sets id of the last event sent to subscribed client
+    fn websocket_reply(
+        &self,
+        text: &str,
+        subscription: &mut Option<u64>,
+        user: Option<&User>,
+    ) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return ServerMessage::Error {
+                    error: e.to_string(),
+                }
+            }
+        };
+        match message {
+            ClientMessage::List => ServerMessage::Devices {
+                devices: Self::device_events(&lock(&self.home)),
+            },
+            ClientMessage::Subscribe { last_event_id } => {
+                let last_id = self.events.last_id();
+                *subscription = Some(last_event_id.unwrap_or(last_id));
+                ServerMessage::Subscribed {
+                    last_event_id: last_id,
+                }
+            }
+            ClientMessage::Unsubscribe => {
+                *subscription = None;
+                ServerMessage::Unsubscribed
+            }
+            ClientMessage::Toggle { device } => match user {
+                Some(user) if !user.may_control() => ServerMessage::Error {
+                    error: Self::control_denied(user),
+                },
+                _ => match self.toggle_device(&device) {
+                    Ok(device) => ServerMessage::Toggled { device },
+                    Err(error) => ServerMessage::Error { error },
+                },
+            },
+        }
+    }
+    /// Switches device to the opposite state and publishes the change
+    /// Returns `Err(String)` if device does not exist or cannot be switched
+    fn toggle_device(&self, device_name: &str) -> Result<DeviceEvent, String> {
+        let mut home = lock(&self.home);
+        let device_info = Self::find_device(&home, device_name)
+            .ok_or_else(|| format!("Device with name {} does not exist", device_name))?;
+        let states = Self::device_states(&home);
+        let result = match Self::device_object(&home, &device_info).state {
+            DeviceState::On => Self::owner(&mut home).turn_off(&device_info),
+            DeviceState::Off => Self::owner(&mut home).turn_on(&device_info),
+        };
+        result.map_err(|e| e.to_string())?;
+        let changes = Self::changes(&home, states);
+        let (room_name, device) = home
+            .devices()
+            .find(|(_, device)| device.name() == device_name)
+            .expect("device should exist");
+        let event = Self::device_event(&home, room_name, device);
+        drop(home);
+        self.publish_changes(changes);
+        Ok(event)
+    }
+    /// Writes `503 Service Unavailable` into connection which is not served
+    /// Returns `io::Err` if it failes to write to connection
+    fn reject(stream: &TcpStream) -> io::Result<()> {
+        stream.set_write_timeout(Some(REJECT_TIMEOUT))?;
+        Self::error(
+            Status::ServiceUnavailable,
+            ResponseFormat::Html,
+            "Too many connections",
+        )
+        .with_header("Retry-After", "1")
+        .write_to(stream, false)
+    }
+    /// Writes home snapshot in JSON into state file if it is set
+    /// Returns `io::Err` if it failes to write the file
+    fn persist_state(&self) -> io::Result<()> {
+        match &self.state_file {
+            Some(path) => {
+                let snapshot = lock(&self.home).snapshot();
+                fs::write(path, serde_json::to_string_pretty(&snapshot)?)
+            }
+            None => Ok(()),
+        }
+    }
+    /// Serves requests read from connection until client closes it,
+    /// asks not to keep it alive, shutdown is requested or connection is upgraded
+    ///
+    /// Connection is closed if the next request does not start within `IDLE_TIMEOUT`,
+    /// so idle keep-alive connections do not hold workers.
+    /// Writes error response and closes connection on malformed request
+    /// Returns `io::Err` if it failes to read from or write to connection
+    fn serve<R, W>(
+        &self,
+        reader: &mut R,
+        mut writer: W,
+        set_read_timeout: impl Fn(Duration) -> io::Result<()>,
+    ) -> io::Result<Option<Upgrade>>
     where
-        T: Write,
+        R: BufRead,
+        W: Write,
     {
+        let mut kept_alive = false;
+        loop {
+            if std::mem::replace(&mut kept_alive, true) {
+                set_read_timeout(IDLE_TIMEOUT)?;
+                match reader.fill_buf() {
+                    Ok([]) => return Ok(None),
+                    Ok(_) => (),
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        return Ok(None)
+                    }
+                    Err(e) => return Err(e),
+                }
+                set_read_timeout(READ_TIMEOUT)?;
+            }
+            let request = match Request::read(reader, &self.limits) {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(None),
+                Err(RequestError::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(RequestError::Io(e)) => return Err(e),
+                Err(e) => {
+                    return Self::error((&e).into(), ResponseFormat::Html, &e.to_string())
+                        .write_to(&mut writer, false)
+                        .map(|_| None)
+                }
+            };
+            let keep_alive = request.keep_alive() && !self.shutdown.is_requested();
+            let head = request.req_type == RequestType::Head;
+            let user = match self.authorize(&request) {
+                Ok(user) => user,
+                Err(response) => {
+                    match head {
+                        true => response.write_head_to(&mut writer, keep_alive)?,
+                        false => response.write_to(&mut writer, keep_alive)?,
+                    }
+                    match keep_alive {
+                        true => continue,
+                        false => return Ok(None),
+                    }
+                }
+            };
+            let response = match (&request.command, request.req_type) {
+                (Command::Events, RequestType::Get) => {
+                    let last_event_id = request
+                        .headers
+                        .get("Last-Event-ID")
+                        .and_then(|id| id.trim().parse().ok());
+                    Response::new(Status::Ok)
+                        .with_header("Content-Type", EVENT_STREAM_CONTENT_TYPE)
+                        .with_header("Cache-Control", "no-cache")
+                        .write_stream_head_to(&mut writer)?;
+                    return Ok(Some(Upgrade::Events { last_event_id }));
+                }
+                (Command::WebSocket, RequestType::Get) => {
+                    match websocket::handshake_key(&request) {
+                        Ok(key) => {
+                            Response::new(Status::SwitchingProtocols)
+                                .with_header("Upgrade", "websocket")
+                                .with_header("Sec-WebSocket-Accept", &websocket::accept_key(key))
+                                .write_stream_head_to(&mut writer)?;
+                            return Ok(Some(Upgrade::WebSocket { user }));
+                        }
+                        Err(e) => Self::handshake_error(&e, request.format()),
+                    }
+                }
+                _ => self.handle(request, user.as_ref()),
+            };
+            match head {
+                true => response.write_head_to(&mut writer, keep_alive)?,
+                false => response.write_to(&mut writer, keep_alive)?,
+            }
+            if !keep_alive {
+                return Ok(None);
+            }
+        }
+    }
+    /// Returns user of request, `None` if authentication is disabled
+    /// or request logs in or out
+    ///
+    /// Returns `Err(Response)` with `401 Unauthorized` if credentials are missing or wrong,
+    /// `403 Forbidden` if read-only user makes unsafe request
+    fn authorize(&self, request: &Request) -> Result<Option<User>, Response> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+        if matches!(request.command, Command::Login | Command::Logout) {
+            return Ok(None);
+        }
+        let redirect = match request.req_type {
+            RequestType::Get => request.path.as_str(),
+            _ => "/",
+        };
+        let user = auth
+            .authenticate(request)
+            .map_err(|e| Self::unauthorized(request.format(), e, redirect))?;
+        if !request.req_type.is_safe() && !user.may_control() {
+            return Err(Self::error(
+                Status::Forbidden,
+                request.format(),
+                &Self::control_denied(&user),
+            ));
+        }
+        Ok(Some(user))
+    }
+    /// Returns `401 Unauthorized` asking to authenticate
+    ///
+    /// HTML clients get login form redirecting to `redirect`, they are not challenged
+    /// with `Basic` scheme, so browsers do not prompt for credentials over the form
+    fn unauthorized(format: ResponseFormat, error: AuthError, redirect: &str) -> Response {
+        let bearer = match error {
+            AuthError::InvalidToken => {
+                format!(r#"Bearer realm="{}", error="invalid_token""#, auth::REALM)
+            }
+            _ => format!(r#"Bearer realm="{}""#, auth::REALM),
+        };
+        match format {
+            ResponseFormat::Html => Response::html(
+                Status::Unauthorized,
+                dashboard::login_page(redirect, Some(&error.to_string())),
+            )
+            .with_header("WWW-Authenticate", &bearer),
+            ResponseFormat::Json => Self::error(Status::Unauthorized, format, &error.to_string())
+                .with_header(
+                    "WWW-Authenticate",
+                    &format!(r#"Basic realm="{}", charset="UTF-8""#, auth::REALM),
+                )
+                .with_header("WWW-Authenticate", &bearer),
+        }
+    }
+    fn control_denied(user: &User) -> String {
+        format!("User {} may not switch devices", user.name)
+    }
+    /// Shows login form, on `POST` starts session of user given in form
+    /// and redirects to page given in `redirect` field
+    ///
+    /// Returns `401 Unauthorized` if user name or password is wrong,
+    /// `404 Not Found` if authentication is disabled
+    fn login(&self, req_type: RequestType, format: ResponseFormat, body: &[u8]) -> Response {
+        let Some(auth) = &self.auth else {
+            return Self::error(Status::NotFound, format, "Authentication is disabled");
+        };
+        let redirect = dashboard::redirect_target(body).unwrap_or_else(|| "/".into());
+        if req_type != RequestType::Post {
+            return Response::html(Status::Ok, dashboard::login_page(&redirect, None));
+        }
+        let field = |name| dashboard::form_field(body, name).unwrap_or_default();
+        let Some(user) = auth.login(&field("username"), &field("password")) else {
+            return Self::unauthorized(
+                ResponseFormat::Html,
+                AuthError::InvalidCredentials,
+                &redirect,
+            );
+        };
+        match auth.start_session(user) {
+            Ok(id) => Response::new(Status::SeeOther)
+                .with_header("Location", &dashboard::encode_path(&redirect))
+                .with_header("Set-Cookie", &auth::session_cookie_header(&id)),
+            Err(e) => Self::error(Status::InternalServerError, format, &e.to_string()),
+        }
+    }
+    /// Ends session of request cookie and redirects to dashboard
+    fn logout(&self, headers: &Headers) -> Response {
+        if let Some(auth) = &self.auth {
+            auth.end_session(headers);
+        }
+        Response::new(Status::SeeOther)
+            .with_header("Location", "/")
+            .with_header("Set-Cookie", &auth::session_cookie_header(""))
+    }
+    /// Returns response to request of `user`, publishes events of changed devices
+    fn handle(&self, request: Request, user: Option<&User>) -> Response {
+        let format = request.format();
+        let allowed = request.command.allowed_methods();
+        if !allowed.is_empty() {
+            let allow = allowed
+                .iter()
+                .chain([&RequestType::Options])
+                .map(|m| m.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            if request.req_type == RequestType::Options {
+                return Response::new(Status::NoContent).with_header("Allow", &allow);
+            }
+            if !allowed.contains(&request.req_type) {
+                return Self::error(Status::MethodNotAllowed, format, "Method not allowed")
+                    .with_header("Allow", &allow);
+            }
+        }
+        let mut home = lock(&self.home);
+        let states = Self::device_states(&home);
+        let response = match request.command {
+            Command::TurnOn { device_name } => {
+                Self::turn_on_device(&mut home, format, &device_name, &request.body)
+            }
+            Command::TurnOff { device_name } => {
+                Self::turn_off_device(&mut home, format, &device_name, &request.body)
+            }
+            Command::SetState { device_name } => {
+                Self::set_device_state(&mut home, format, &device_name, &request.body)
+            }
+            Command::GetStatus => Self::state_all(&mut home, format),
+            Command::ShowMain => Self::main_page(&home, user),
+            Command::GetMetrics => Self::metrics(&home),
+            Command::Query => Self::query(&home, format, request.query_param("q")),
+            Command::GetDeviceStatus { device_name } => {
+                Self::state_device(&mut home, format, &device_name, user)
+            }
+            Command::Login => self.login(request.req_type, format, &request.body),
+            Command::Logout => self.logout(&request.headers),
+            Command::Events | Command::WebSocket => Self::error(
+                Status::BadRequest,
+                format,
+                "Streams are opened by GET requests",
+            ),
+            Command::Ignore => Response::new(Status::NotFound),
+            Command::Error { error_msg } => Self::error(Status::NotFound, format, &error_msg),
+        };
+        let changes = Self::changes(&home, states);
+        drop(home);
+        self.publish_changes(changes);
+        response
+    }
+    /// Returns states of all devices in home
+    fn device_states(home: &Home) -> Vec<DeviceState> {
+        home.devices().map(|(_, device)| *device.state()).collect()
+    }
+    /// Returns events of devices whose state differs from `states` taken before
+    fn changes(home: &Home, states: Vec<DeviceState>) -> Vec<DeviceEvent> {
+        home.devices()
+            .zip(states)
+            .filter(|((_, device), state)| device.state() != state)
+            .map(|((room_name, device), _)| Self::device_event(home, room_name, device))
+            .collect()
+    }
+    /// Publishes state events, home should be unlocked so requests are not blocked
+    fn publish_changes(&self, changes: Vec<DeviceEvent>) {
+        for change in changes {
+            self.publish("state", &change);
+        }
+    }
+    /// Returns response to refused WebSocket opening handshake
+    fn handshake_error(error: &HandshakeError, format: ResponseFormat) -> Response {
+        let response = match error {
+            HandshakeError::MissingKey => Status::BadRequest,
+            HandshakeError::NotUpgrade | HandshakeError::UnsupportedVersion => {
+                Status::UpgradeRequired
+            }
+        };
+        let response = Self::error(response, format, &error.to_string());
+        match error {
+            HandshakeError::NotUpgrade => response.with_header("Upgrade", "websocket"),
+            HandshakeError::UnsupportedVersion => {
+                response.with_header("Sec-WebSocket-Version", websocket::VERSION)
+            }
+            HandshakeError::MissingKey => response,
+        }
+    }
+    /// Returns dashboard page
+    fn main_page(home: &Home, user: Option<&User>) -> Response {
+        Response::html(Status::Ok, dashboard::dashboard(home, user))
+    }
+    /// Returns state of all devices
+    fn state_all(home: &mut Home<'a>, format: ResponseFormat) -> Response {
+        if format == ResponseFormat::Json {
+            let devices: Vec<DeviceObject> = home
+                .devices()
+                .map(|(room_name, device)| DeviceObject::new(room_name, device))
+                .collect();
+            return Response::json(Status::Ok, &devices);
+        }
         let content = HtmlPage::new()
-            .with_header(1, "Main Page")
-            .with_header(2, "Devices")
-            .with_paragraph({
-                let devs: Vec<String> = self.devices.iter().map(|d| d.name().to_string()).collect();
-                match devs.is_empty() {
+            .with_header(1, "All devices status")
+            .with_preformatted({
+                let device_infos = Self::device_infos(home);
+                match device_infos.is_empty() {
+                    false => Self::owner(home)
+                        .get_devices_report(device_infos.iter().collect())
+                        .into_iter()
+                        .filter_map(|r| r.ok())
+                        .collect::<Vec<String>>()
+                        .join("\n"),
                     true => "No devices registered".to_string(),
-                    false => devs.join(", "),
                 }
             })
-            .with_header(2, "Urls")
-            .with_paragraph("/status_all - get statuses of all devices")
-            .with_paragraph("/status_device/{device_name} - get status of device")
-            .with_paragraph("/turn_on/{device_name} - turn on device")
-            .with_paragraph("/turn_off/{device_name} - turn off device")
+            .with_link("/", "Return home")
             .to_html_string();
-        let status_line = "HTTP/1.1 200 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        Response::html(Status::Ok, content)
     }
-    /// Writes state of all devices into stream
-    /// Returns `io::Err` if it failes to write to stream
-    fn write_state_all<T>(&self, mut stream: T) -> io::Result<()>
-    where
-        T: Write,
-    {
+    /// Returns devices matching query, `400 Bad Request` if query is missing or invalid
+    fn query(home: &Home, format: ResponseFormat, query: Option<String>) -> Response {
+        let Some(query) = query else {
+            return Self::error(
+                Status::BadRequest,
+                format,
+                "Query should be given in q parameter",
+            );
+        };
+        let matches = match Query::from_str(&query) {
+            Ok(parsed) => home.query(&parsed),
+            Err(e) => return Self::error(Status::BadRequest, format, &e.to_string()),
+        };
+        if format == ResponseFormat::Json {
+            let devices: Vec<DeviceObject> = matches
+                .iter()
+                .map(|m| Self::device_object(home, &m.device_info()))
+                .collect();
+            return Response::json(Status::Ok, &devices);
+        }
         let content = HtmlPage::new()
-            .with_header(1, "All devices status")
-            .with_preformatted(match self.devices.is_empty() {
-                false => self
-                    .devices
+            .with_header(1, "Query results")
+            .with_paragraph(format!("Query: {}", escape_html(&query)))
+            .with_preformatted(match matches.is_empty() {
+                false => matches
                     .iter()
-                    .map(|el| el.to_string())
+                    .map(|m| escape_html(&format!("{}/{}", m.room_name, m.device.name)))
                     .collect::<Vec<String>>()
                     .join("\n"),
-                true => "No devices registered".to_string(),
+                true => "No devices match".to_string(),
             })
             .with_link("/", "Return home")
             .to_html_string();
-        let status_line = "HTTP/1.1 200 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        Response::html(Status::Ok, content)
     }
-    /// Writes state of a device into stream.
-    /// Writes error page if device does not exist
-    /// Returns `io::Err` if it failes to write to stream
-    fn write_state_device<T>(&self, mut stream: T, device_name: &str) -> io::Result<()>
-    where
-        T: Write,
-    {
-        let dev = self.devices.iter().find(|d| d.name() == device_name);
-        if dev.is_none() {
-            self.write_error(
-                stream,
-                &format!("Device with name {} does not exist", device_name),
-            )?;
-            return Ok(());
+    /// Returns state of a device, `404 Not Found` if device does not exist
+    fn state_device(
+        home: &mut Home<'a>,
+        format: ResponseFormat,
+        device_name: &str,
+        user: Option<&User>,
+    ) -> Response {
+        let device_info = match Self::find_device(home, device_name) {
+            Some(device_info) => device_info,
+            None => return Self::device_not_found(format, device_name),
+        };
+        if format == ResponseFormat::Json {
+            return Response::json(Status::Ok, &Self::device_object(home, &device_info));
         }
-        let dev = dev.unwrap();
-        let content = HtmlPage::new()
-            .with_header(1, "Device state")
-            .with_preformatted(dev.to_string())
-            .with_link("/", "Return home")
-            .to_html_string();
-        let status_line = "HTTP/1.1 200 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        let report = match Self::owner(home).get_device_report(&device_info) {
+            Ok(report) => report,
+            Err(e) => return Self::error(Status::InternalServerError, format, &e.to_string()),
+        };
+        Response::html(
+            Status::Ok,
+            dashboard::device_page(home, &device_info, &report, user),
+        )
     }
-    /// Turns off device and write a page into stream
-    /// Returns `io::Err` if it failes to write to stream
-    fn turn_on_device<T>(&mut self, mut stream: T, device_name: &str) -> io::Result<()>
-    where
-        T: Write,
-    {
-        let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
-        if dev.is_none() {
-            self.write_error(
-                stream,
-                &format!("Device with name {} does not exist", device_name),
-            )?;
-            return Ok(());
+    /// Turns on device and returns its state,
+    /// redirects to page given in `redirect` field of dashboard form body
+    ///
+    /// Returns `404 Not Found` if device does not exist,
+    /// `409 Conflict` if device cannot be turned on
+    fn turn_on_device(
+        home: &mut Home<'a>,
+        format: ResponseFormat,
+        device_name: &str,
+        body: &[u8],
+    ) -> Response {
+        let device_info = match Self::find_device(home, device_name) {
+            Some(device_info) => device_info,
+            None => return Self::device_not_found(format, device_name),
+        };
+        if let Err(e) = Self::owner(home).turn_on(&device_info) {
+            return Self::error(Status::Conflict, format, &e.to_string());
+        }
+        if format == ResponseFormat::Json {
+            return Response::json(Status::Ok, &Self::device_object(home, &device_info));
+        }
+        if let Some(location) = dashboard::redirect_target(body) {
+            return Response::new(Status::SeeOther)
+                .with_header("Location", &dashboard::encode_path(&location));
         }
-        let dev = dev.unwrap();
-        dev.turn_on();
         let content = HtmlPage::new()
             .with_header(1, "Turn on device")
             .with_paragraph(format!("Device {} is on", device_name))
             .with_link("/", "Return home")
             .to_html_string();
-        let status_line = "HTTP/1.1 200 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        Response::html(Status::Ok, content)
     }
-    /// Turns off device and write a page into stream
-    /// Returns `io::Err` if it failes to write to stream
-    fn turn_off_device<T>(&mut self, mut stream: T, device_name: &str) -> io::Result<()>
-    where
-        T: Write,
-    {
-        let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
-        if dev.is_none() {
-            self.write_error(
-                stream,
-                &format!("Device with name {} does not exist", device_name),
-            )?;
-            return Ok(());
+    /// Turns off device and returns its state,
+    /// redirects to page given in `redirect` field of dashboard form body
+    ///
+    /// Returns `404 Not Found` if device does not exist,
+    /// `409 Conflict` if device cannot be turned off
+    fn turn_off_device(
+        home: &mut Home<'a>,
+        format: ResponseFormat,
+        device_name: &str,
+        body: &[u8],
+    ) -> Response {
+        let device_info = match Self::find_device(home, device_name) {
+            Some(device_info) => device_info,
+            None => return Self::device_not_found(format, device_name),
+        };
+        if let Err(e) = Self::owner(home).turn_off(&device_info) {
+            return Self::error(Status::Conflict, format, &e.to_string());
+        }
+        if format == ResponseFormat::Json {
+            return Response::json(Status::Ok, &Self::device_object(home, &device_info));
+        }
+        if let Some(location) = dashboard::redirect_target(body) {
+            return Response::new(Status::SeeOther)
+                .with_header("Location", &dashboard::encode_path(&location));
         }
-        let dev = dev.unwrap();
-        dev.turn_on();
         let content = HtmlPage::new()
             .with_header(1, "Turn off device")
             .with_paragraph(format!("Device {} is off", device_name))
             .with_link("/", "Return home")
             .to_html_string();
-        let status_line = "HTTP/1.1 200 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        Response::html(Status::Ok, content)
     }
-    /// Writes error page into stream
-    /// Returns `io::Err` if it failes to write to stream
-    fn write_error<T>(&self, mut stream: T, error_msg: &str) -> io::Result<()>
-    where
-        T: Write,
-    {
+    /// Sets device state to `on` or `off` given in body, does nothing if device is in it
+    ///
+    /// Returns `404 Not Found` if device does not exist, `400 Bad Request` if body
+    /// is not a state, `409 Conflict` if device cannot change state
+    fn set_device_state(
+        home: &mut Home<'a>,
+        format: ResponseFormat,
+        device_name: &str,
+        body: &[u8],
+    ) -> Response {
+        let device_info = match Self::find_device(home, device_name) {
+            Some(device_info) => device_info,
+            None => return Self::device_not_found(format, device_name),
+        };
+        let Some(state) = parse_state(body) else {
+            return Self::error(
+                Status::BadRequest,
+                format,
+                r#"Body should be on, off or {"state": "on"}"#,
+            );
+        };
+        if Self::device_object(home, &device_info).state != state {
+            let result = match state {
+                DeviceState::On => Self::owner(home).turn_on(&device_info),
+                DeviceState::Off => Self::owner(home).turn_off(&device_info),
+            };
+            if let Err(e) = result {
+                return Self::error(Status::Conflict, format, &e.to_string());
+            }
+        }
+        if format == ResponseFormat::Json {
+            return Response::json(Status::Ok, &Self::device_object(home, &device_info));
+        }
         let content = HtmlPage::new()
-            .with_header(1, "Error")
+            .with_header(1, "Set device state")
+            .with_paragraph(format!("Device {} is {}", device_name, state))
+            .with_link("/", "Return home")
+            .to_html_string();
+        Response::html(Status::Ok, content)
+    }
+    /// Returns JSON API object of device
+    fn device_object(home: &Home, device_info: &DeviceInfo) -> DeviceObject {
+        home.devices()
+            .find(|(room_name, device)| {
+                *room_name == device_info.room_name && device.name() == device_info.device_name
+            })
+            .map(|(room_name, device)| DeviceObject::new(room_name, device))
+            .expect("device should exist")
+    }
+    /// Returns device object sent in events
+    fn device_event(home: &Home, room_name: &str, device: &dyn Device) -> DeviceEvent {
+        let reading = device.reading();
+        DeviceEvent {
+            device: DeviceObject::with_reading(room_name, device, reading),
+            reading: dashboard::reading_text(home, reading),
+        }
+    }
+    /// Returns objects of all devices sent in events
+    fn device_events(home: &Home) -> Vec<DeviceEvent> {
+        home.devices()
+            .map(|(room_name, device)| Self::device_event(home, room_name, device))
+            .collect()
+    }
+    /// Returns home metrics in Prometheus text format
+    fn metrics(home: &Home) -> Response {
+        Response::new(Status::Ok).with_body(METRICS_CONTENT_TYPE, render_metrics(home))
+    }
+    fn device_not_found(format: ResponseFormat, device_name: &str) -> Response {
+        Self::error(
+            Status::NotFound,
+            format,
+            &format!("Device with name {} does not exist", device_name),
+        )
+    }
+    /// Returns error page or JSON error object
+    fn error(status: Status, format: ResponseFormat, error_msg: &str) -> Response {
+        if format == ResponseFormat::Json {
+            return Response::json(status, &ErrorObject::new(error_msg));
+        }
+        let content = HtmlPage::new()
+            .with_header(1, status.reason())
             .with_paragraph(format!("Error message: {}", error_msg))
             .with_link("/", "Return home")
             .to_html_string();
-        let status_line = "HTTP/1.1 503 OK";
-        let content_length = content.len();
-        let response =
-            format!("{status_line}\r\nContent-Length: {content_length}\r\n\r\n{content}");
-        stream.write_all(response.as_bytes())?;
-        Ok(())
+        Response::html(status, content)
+    }
+}
+
+/// Locks mutex, data of poisoned mutex is used as is
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use lesson8_lib::{HomeSnapshot, Socket, Thermometer};
+
+    use super::*;
+
+    /// Time client waits before sending the next request
+    const CLIENT_DELAY: Duration = Duration::from_millis(20);
+
+    fn serve(server: &mut Server, input: &str) -> String {
+        let mut output = vec![];
+        server
+            .serve(&mut input.as_bytes(), &mut output, |_| Ok(()))
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn serve_keep_alive_requests() {
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+        let output = serve(
+            &mut server,
+            "POST /turn_on/socket1 HTTP/1.1\r\nHost: a\r\n\r\n\
+             GET /status_device/socket1 HTTP/1.1\r\nhost: a\r\nConnection: close\r\n\r\n\
+             GET /metrics HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(output.contains("Device socket1 is on"));
+        assert!(output.contains("state: On"));
+    }
+    #[test]
+    fn serve_malformed_request() {
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        let output = serve(
+            &mut server,
+            "GET / HTTP/1.1\r\nBroken header\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(output.contains("Connection: close\r\n"));
+        assert_eq!(output.matches("HTTP/1.1").count(), 1);
+
+        let output = serve(&mut server, "GET / HTTP/1.1\r\nHost: a\r\n");
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(serve(&mut server, "").is_empty());
+    }
+    /// Returns JSON body of the only response in output
+    fn json_body(output: &str) -> serde_json::Value {
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Content-Type: application/json"), "{head}");
+        serde_json::from_str(body).unwrap()
+    }
+    #[test]
+    fn serve_json_api() {
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+
+        let output = serve(
+            &mut server,
+            "POST /api/devices/socket1/on HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        let device = json_body(&output);
+        assert_eq!(device["name"], "socket1");
+        assert_eq!(device["room"], "kitchen");
+        assert_eq!(device["state"], "On");
+        assert!(device["power"].is_u64());
+
+        let output = serve(
+            &mut server,
+            "GET /api/devices HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        let devices = json_body(&output);
+        assert_eq!(devices.as_array().unwrap().len(), 1);
+        assert_eq!(devices[0]["state"], "On");
+
+        let output = serve(
+            &mut server,
+            "GET /api/devices/socket2 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(
+            json_body(&output)["error"],
+            "Device with name socket2 does not exist"
+        );
+
+        // state is not changed by GET requests to API
+        let output = serve(
+            &mut server,
+            "GET /api/devices/socket1/off HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(json_body(&output)["error"], "Method not allowed");
+        assert_eq!(*socket.state(), lesson8_lib::DeviceState::On);
+    }
+    #[test]
+    fn serve_queries() {
+        let mut socket1 = Socket::new("socket1");
+        let mut socket2 = Socket::new("socket2");
+        let mut thermo = Thermometer::new("thermo1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket1).unwrap();
+        server.add_device("garage", &mut socket2).unwrap();
+        server.add_device("bedroom", &mut thermo).unwrap();
+        serve(
+            &mut server,
+            "POST /turn_on/socket2 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+
+        let output = serve(
+            &mut server,
+            "GET /api/query?q=state+%3D+On+sort+by+device HTTP/1.1\r\nHost: a\r\n\
+             Connection: close\r\n\r\n",
+        );
+        let devices = json_body(&output);
+        assert_eq!(devices.as_array().unwrap().len(), 1);
+        assert_eq!(devices[0]["name"], "socket2");
+        assert_eq!(devices[0]["room"], "garage");
+
+        let output = serve(
+            &mut server,
+            "GET /query?q=room%20%3D%20kitchen HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("kitchen/socket1"));
+        assert!(!output.contains("garage/socket2"));
+
+        let output = serve(
+            &mut server,
+            "GET /api/query?q=colour+%3D+red HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        let output = serve(
+            &mut server,
+            "GET /api/query HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(
+            json_body(&output)["error"],
+            "Query should be given in q parameter"
+        );
+    }
+    #[test]
+    fn negotiate_html_and_json() {
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+
+        let output = serve(
+            &mut server,
+            "GET /status_device/socket1 HTTP/1.1\r\nHost: a\r\nAccept: application/json\r\n\
+             Connection: close\r\n\r\n",
+        );
+        assert_eq!(json_body(&output)["state"], "Off");
+
+        let output = serve(
+            &mut server,
+            "GET /api/devices HTTP/1.1\r\nHost: a\r\nAccept: text/html\r\n\
+             Connection: close\r\n\r\n",
+        );
+        assert!(output.contains("<h1>All devices status</h1>"));
+    }
+    /// Returns status line of the only response to request
+    fn status_line(server: &mut Server, request: &str) -> String {
+        let output = serve(server, request);
+        assert_eq!(output.matches("HTTP/1.1 ").count(), 1, "{output}");
+        output.lines().next().unwrap().to_string()
+    }
+    #[test]
+    fn status_codes_and_headers() {
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+
+        let output = serve(&mut server, "GET /status_all HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\nDate: "));
+        assert!(output.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(output.contains("\r\nContent-Type: text/html; charset=utf-8\r\n"));
+        let output = serve(&mut server, "GET /metrics HTTP/1.0\r\n\r\n");
+        assert!(output.contains("\r\nConnection: close\r\n"));
+        assert!(output.contains("\r\nContent-Type: text/plain; version=0.0.4"));
+
+        for (request, status) in [
+            ("GET /status_device/socket2 HTTP/1.1", "404 Not Found"),
+            ("GET /api/devices/socket2 HTTP/1.1", "404 Not Found"),
+            ("POST /api/devices/socket2/on HTTP/1.1", "404 Not Found"),
+            ("GET /unknown HTTP/1.1", "404 Not Found"),
+            ("GET /api/unknown HTTP/1.1", "404 Not Found"),
+            ("GET /turn_on HTTP/1.1", "400 Bad Request"),
+            ("POST /status_all HTTP/1.1", "405 Method Not Allowed"),
+            ("BREW / HTTP/1.1", "501 Not Implemented"),
+            ("GET / HTTP/2.0", "505 HTTP Version Not Supported"),
+        ] {
+            let request = format!("{request}\r\nHost: a\r\nConnection: close\r\n\r\n");
+            assert_eq!(
+                status_line(&mut server, &request),
+                format!("HTTP/1.1 {status}"),
+                "{request}"
+            );
+        }
+        let output = serve(
+            &mut server,
+            "GET /api/devices/socket1/on HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(output.contains("\r\nAllow: POST, OPTIONS\r\n"));
+
+        server.limits.max_body = 1;
+        let output = serve(
+            &mut server,
+            "POST /api/devices/socket1/on HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\n\r\nab",
+        );
+        assert!(output.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+    /// Reads one response with body from keep-alive connection and returns its status line
+    fn read_response(reader: &mut impl BufRead) -> String {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).unwrap();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(": ") {
+                Some(("Content-Length", length)) => content_length = length.parse().unwrap(),
+                None => break,
+                _ => (),
+            }
+        }
+        reader.read_exact(&mut vec![0; content_length]).unwrap();
+        status_line.trim_end().to_string()
+    }
+    /// Returns number of keep-alive connections answered at once by `workers`
+    /// while `clients` hold their connections open, and `true` if more were answered
+    fn concurrent_connections(workers: usize, clients: usize) -> (usize, bool) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_workers(workers)
+            .with_max_connections(clients);
+        server.add_device("kitchen", &mut socket).unwrap();
+        let server = &server;
+        let (answered, responses) = std::sync::mpsc::channel();
+        // clients hold their connections until write lock is released
+        let release = std::sync::RwLock::new(());
+        let held = release.write().unwrap();
+        thread::scope(|scope| {
+            scope.spawn(|| server.serve_connections(listener.incoming().take(clients)));
+            for _ in 0..clients {
+                let answered = answered.clone();
+                let release = &release;
+                scope.spawn(move || {
+                    let mut stream = TcpStream::connect(address).unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    for connection in ["keep-alive", "close"] {
+                        let request = format!(
+                            "GET /api/devices HTTP/1.1\r\nHost: a\r\nConnection: {connection}\r\n\r\n"
+                        );
+                        stream.write_all(request.as_bytes()).unwrap();
+                        assert_eq!(read_response(&mut reader), "HTTP/1.1 200 OK");
+                        let _ = answered.send(());
+                        drop(release.read().unwrap());
+                    }
+                });
+            }
+            let expected = workers.min(clients);
+            let concurrent = (0..expected)
+                .filter(|_| responses.recv_timeout(READ_TIMEOUT).is_ok())
+                .count();
+            // short wait may miss extra answers but never fails correct server
+            let extra = responses.recv_timeout(CLIENT_DELAY * 5).is_ok();
+            drop(held);
+            (concurrent, extra)
+        })
+    }
+    #[test]
+    fn workers_serve_connections_concurrently() {
+        for workers in [1, 4, 8] {
+            let (concurrent, extra) = concurrent_connections(workers, 6);
+            assert_eq!(concurrent, workers.min(6), "{workers} workers");
+            assert!(!extra, "{workers} workers answered more connections");
+        }
+    }
+    /// Returns time `workers` take to serve `clients` which wait `think` after
+    /// the first answered request before sending the last one
+    fn serve_thinking_clients(workers: usize, clients: usize, think: Duration) -> Duration {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_workers(workers)
+            .with_max_connections(clients);
+        let started = std::time::Instant::now();
+        thread::scope(|scope| {
+            scope.spawn(|| server.serve_connections(listener.incoming().take(clients)));
+            for _ in 0..clients {
+                scope.spawn(move || {
+                    let mut stream = TcpStream::connect(address).unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    for connection in ["keep-alive", "close"] {
+                        let request = format!(
+                            "GET /metrics HTTP/1.1\r\nHost: a\r\nConnection: {connection}\r\n\r\n"
+                        );
+                        stream.write_all(request.as_bytes()).unwrap();
+                        assert_eq!(read_response(&mut reader), "HTTP/1.1 200 OK");
+                        // worker waits for the next request meanwhile
+                        thread::sleep(think);
+                    }
+                });
+            }
+        });
+        started.elapsed()
+    }
+    #[test]
+    fn throughput_scales_with_workers() {
+        let think = CLIENT_DELAY * 2;
+        let single = serve_thinking_clients(1, 12, think);
+        assert!(single >= think * 12, "{single:?}");
+        // four workers need a quarter of the time, tolerance covers slow machines
+        let four = serve_thinking_clients(4, 12, think);
+        assert!(
+            four * 2 < single,
+            "{four:?} with 4 workers, {single:?} with 1"
+        );
+    }
+    #[test]
+    fn idle_connection_releases_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0).unwrap().with_workers(1);
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(2)));
+            let mut idle = TcpStream::connect(address).unwrap();
+            idle.write_all(b"GET /metrics HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(idle.try_clone().unwrap());
+            assert_eq!(read_response(&mut reader), "HTTP/1.1 200 OK");
+
+            let started = std::time::Instant::now();
+            let mut other = TcpStream::connect(address).unwrap();
+            other
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            other.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(started.elapsed() < READ_TIMEOUT);
+            // idle connection was closed by server
+            let mut rest = String::new();
+            reader.read_to_string(&mut rest).unwrap();
+            assert_eq!(rest, "");
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn reject_connections_over_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_workers(1)
+            .with_max_connections(1);
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(2)));
+            let mut first = TcpStream::connect(address).unwrap();
+            thread::sleep(CLIENT_DELAY);
+            let mut second = TcpStream::connect(address).unwrap();
+            let mut response = String::new();
+            second.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+            assert!(response.contains("\r\nRetry-After: 1\r\n"));
+
+            first
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            first.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn event_streams_count_against_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0).unwrap().with_max_connections(1);
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(2)));
+            let mut events = TcpStream::connect(address).unwrap();
+            events
+                .write_all(b"GET /events HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(events.try_clone().unwrap());
+            assert!(read_stream_head(&mut reader).starts_with("HTTP/1.1 200 OK\r\n"));
+            let mut retry = String::new();
+            reader.read_line(&mut retry).unwrap();
+            assert!(retry.starts_with("retry: "));
+            thread::sleep(CLIENT_DELAY);
+
+            let mut second = TcpStream::connect(address).unwrap();
+            let mut response = String::new();
+            second.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn shutdown_drains_served_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0).unwrap();
+        let handle = server.shutdown_handle();
+        thread::scope(|scope| {
+            let served =
+                scope.spawn(|| server.serve_connections(accept_until_shutdown(&listener, &handle)));
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: a\r\n")
+                .unwrap();
+            thread::sleep(POLL_INTERVAL * 2);
+            handle.shutdown();
+            thread::sleep(POLL_INTERVAL * 2);
+            // request started before shutdown is served, connection is not kept alive
+            stream.write_all(b"\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.contains("\r\nConnection: close\r\n"));
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn shutdown_closes_idle_connections_after_drain_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_drain_timeout(Duration::from_millis(100));
+        let handle = server.shutdown_handle();
+        thread::scope(|scope| {
+            let served =
+                scope.spawn(|| server.serve_connections(accept_until_shutdown(&listener, &handle)));
+            let mut stream = TcpStream::connect(address).unwrap();
+            thread::sleep(POLL_INTERVAL * 2);
+            let start = Instant::now();
+            handle.shutdown();
+            served.join().unwrap();
+            assert!(start.elapsed() < READ_TIMEOUT);
+            let mut response = vec![];
+            let _ = stream.read_to_end(&mut response);
+            assert!(response.is_empty());
+        });
+    }
+    #[test]
+    fn persist_state_after_shutdown() {
+        let path =
+            std::env::temp_dir().join(format!("tcp_socket_state_{}.json", std::process::id()));
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap().with_state_file(&path);
+        server.add_device("kitchen", &mut socket).unwrap();
+        serve(
+            &mut server,
+            "POST /api/devices/socket1/on HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        server.shutdown_handle().shutdown();
+        server.run().unwrap();
+
+        let snapshot: HomeSnapshot =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let device = snapshot
+            .device(&DeviceInfo::new("socket1", "kitchen"))
+            .unwrap();
+        assert_eq!(device.state, lesson8_lib::DeviceState::On);
+    }
+    #[test]
+    fn require_authentication() {
+        use crate::auth::tests::{password_hash, token_hash};
+
+        let path = std::env::temp_dir().join(format!(
+            "tcp_socket_credentials_{}.json",
+            std::process::id()
+        ));
+        let credentials = format!(
+            r#"[{{"name": "admin", "role": "control", "password_hash": "{}"}},
+                {{"name": "viewer", "role": "read_only", "token_sha256": ["{}"]}}]"#,
+            password_hash("secret"),
+            token_hash("token1")
+        );
+        fs::write(&path, credentials).unwrap();
+        let auth = Auth::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap().with_auth(auth);
+        server.add_device("kitchen", &mut socket).unwrap();
+        let mut request = |request_line: &str, headers: &str, body: &str| {
+            serve(
+                &mut server,
+                &format!(
+                    "{request_line} HTTP/1.1\r\nHost: a\r\nConnection: close\r\n{headers}\
+                     Content-Length: {}\r\n\r\n{body}",
+                    body.len()
+                ),
+            )
+        };
+
+        // API clients are challenged with both schemes
+        let output = request("GET /api/devices", "", "");
+        assert!(
+            output.starts_with("HTTP/1.1 401 Unauthorized\r\n"),
+            "{output}"
+        );
+        assert!(output
+            .contains("\r\nWWW-Authenticate: Basic realm=\"tcp_socket\", charset=\"UTF-8\"\r\n"));
+        assert!(output.contains("\r\nWWW-Authenticate: Bearer realm=\"tcp_socket\"\r\n"));
+        let output = request("GET /api/devices", "Authorization: Bearer token2\r\n", "");
+        assert!(output.contains(r#"Bearer realm="tcp_socket", error="invalid_token""#));
+
+        // read-only user reads devices but may not switch them
+        let viewer = "Authorization: Bearer token1\r\n";
+        assert!(request("GET /api/devices", viewer, "").starts_with("HTTP/1.1 200 OK\r\n"));
+        let output = request("POST /api/devices/socket1/on", viewer, "");
+        assert!(output.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{output}");
+        let admin = "Authorization: Basic YWRtaW46c2VjcmV0\r\n";
+        let output = request("POST /api/devices/socket1/on", admin, "");
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+
+        // browsers get login form instead of Basic prompt
+        let output = request("GET /status_device/socket1", "", "");
+        assert!(output.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(output.contains(r#"name="redirect" value="/status_device/socket1""#));
+        assert!(!output.contains("Basic"));
+        let output = request(
+            "POST /login",
+            "",
+            "username=admin&password=wrong&redirect=%2Fstatus_all",
+        );
+        assert!(output.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        let output = request(
+            "POST /login",
+            "",
+            "username=admin&password=secret&redirect=%2Fstatus_all",
+        );
+        assert!(output.starts_with("HTTP/1.1 303 See Other\r\n"), "{output}");
+        assert!(output.contains("\r\nLocation: /status_all\r\n"));
+        let id = output
+            .split("\r\nSet-Cookie: session=")
+            .nth(1)
+            .and_then(|cookie| cookie.split_once(';'))
+            .unwrap()
+            .0;
+        let cookie = format!("Cookie: session={id}\r\n");
+        let output = request("GET /", &cookie, "");
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("Signed in as admin"));
+        let output = request("POST /logout", &cookie, "");
+        assert!(output.starts_with("HTTP/1.1 303 See Other\r\n"));
+        assert!(output.contains("\r\nSet-Cookie: session=; Path=/; Max-Age=0;"));
+        assert!(request("GET /", &cookie, "").contains("Session has expired"));
+
+        let viewer = User {
+            name: "viewer".to_string(),
+            role: auth::Role::ReadOnly,
+        };
+        let reply = server.websocket_reply(
+            r#"{"type": "toggle", "device": "socket1"}"#,
+            &mut None,
+            Some(&viewer),
+        );
+        assert!(matches!(reply, ServerMessage::Error { .. }));
+    }
+    #[test]
+    fn method_semantics() {
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+
+        // links followed by crawlers do not change state
+        let output = serve(
+            &mut server,
+            "GET /turn_on/socket1 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        let output = serve(
+            &mut server,
+            "DELETE /api/devices/socket1 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(output.contains("\r\nAllow: GET, HEAD, OPTIONS\r\n"));
+
+        let output = serve(
+            &mut server,
+            "OPTIONS /devices/socket1/state HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(output.contains("\r\nAllow: PUT, OPTIONS\r\n"));
+        assert!(!output.contains("Content-Length"));
+
+        let get = serve(
+            &mut server,
+            "GET /status_all HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        let head = serve(
+            &mut server,
+            "HEAD /status_all HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        let (get_head, get_body) = get.split_once("\r\n\r\n").unwrap();
+        assert!(!get_body.is_empty());
+        assert!(head.ends_with("\r\n\r\n"));
+        let content_length = |head: &str| {
+            head.lines()
+                .find(|l| l.starts_with("Content-Length"))
+                .map(|l| l.to_string())
+        };
+        assert_eq!(content_length(&head), content_length(get_head));
+
+        // the same state can be put many times
+        for body in ["on", r#"{"state": "ON"}"#] {
+            let request = format!(
+                "PUT /devices/socket1/state HTTP/1.1\r\nHost: a\r\nAccept: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            assert_eq!(json_body(&serve(&mut server, &request))["state"], "On");
+        }
+        let output = serve(
+            &mut server,
+            "PUT /api/devices/socket1/state HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\
+             Connection: close\r\n\r\nmaybe",
+        );
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        let output = serve(
+            &mut server,
+            "PUT /api/devices/socket1/state HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\
+             Connection: close\r\n\r\noff",
+        );
+        assert_eq!(json_body(&output)["state"], "Off");
+    }
+    #[test]
+    fn dashboard_forms() {
+        let mut socket = Socket::new("socket1");
+        let mut thermo = lesson8_lib::Thermometer::new("<thermo>");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+        server.add_device("bedroom", &mut thermo).unwrap();
+
+        let output = serve(
+            &mut server,
+            "GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.contains(r#"new EventSource("/events")"#));
+        assert!(output.contains(r#"<a href="/status_device/socket1">socket1</a>"#));
+        assert!(output.contains(r#"<form method="post" action="/turn_on/socket1">"#));
+        assert!(output.contains("&lt;thermo&gt;"));
+        assert!(!output.contains("<thermo>"));
+        assert!(output.contains(r#"<td class="reading">-</td>"#));
+
+        let body = "redirect=%2Fstatus_device%2Fsocket1";
+        let request = format!(
+            "POST /turn_on/socket1 HTTP/1.1\r\nHost: a\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let output = serve(&mut server, &request);
+        assert!(output.starts_with("HTTP/1.1 303 See Other\r\n"));
+        assert!(output.contains("\r\nLocation: /status_device/socket1\r\n"));
+
+        let output = serve(
+            &mut server,
+            "GET /status_device/socket1 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.contains(r#"<td class="state On">On</td>"#));
+        assert!(output.contains(" W</td>"));
+        assert!(output
+            .contains(r#"<input type="hidden" name="redirect" value="/status_device/socket1">"#));
+        assert!(output.contains("<button disabled>On</button>"));
+    }
+    #[test]
+    fn dashboard_encodes_device_names_in_urls() {
+        let mut socket = Socket::new("my socket?");
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        server.add_device("kitchen", &mut socket).unwrap();
+
+        let output = serve(
+            &mut server,
+            "GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.contains(r#"<a href="/status_device/my%20socket%3F">my socket?</a>"#));
+        assert!(output.contains(r#"<form method="post" action="/turn_on/my%20socket%3F">"#));
+
+        let body = "redirect=%2Fstatus_device%2Fmy+socket%3F";
+        let request = format!(
+            "POST /turn_on/my%20socket%3F HTTP/1.1\r\nHost: a\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let output = serve(&mut server, &request);
+        assert!(output.starts_with("HTTP/1.1 303 See Other\r\n"));
+        assert!(output.contains("\r\nLocation: /status_device/my%20socket%3F\r\n"));
+
+        let output = serve(
+            &mut server,
+            "GET /status_device/my%20socket%3F HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains(r#"<td class="state On">On</td>"#));
+        assert!(output.contains(
+            r#"<input type="hidden" name="redirect" value="/status_device/my socket?">"#
+        ));
+    }
+    /// Reads head of event stream response and returns it
+    fn read_stream_head(reader: &mut impl BufRead) -> String {
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            assert_ne!(reader.read_line(&mut head).unwrap(), 0);
+        }
+        head
+    }
+    /// Reads the next event of kind `name` from event stream
+    /// and returns its id and data
+    fn read_event(reader: &mut impl BufRead, name: &str) -> (u64, String) {
+        loop {
+            let (mut id, mut event, mut data) = (0, String::new(), String::new());
+            loop {
+                let mut line = String::new();
+                assert_ne!(reader.read_line(&mut line).unwrap(), 0);
+                match line.trim_end().split_once(": ") {
+                    Some(("id", value)) => id = value.parse().unwrap(),
+                    Some(("event", value)) => event = value.to_string(),
+                    Some(("data", value)) => data = value.to_string(),
+                    _ if line == "\n" => break,
+                    _ => (),
+                }
+            }
+            if event == name {
+                return (id, data);
+            }
+        }
+    }
+    #[test]
+    fn stream_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_workers(1)
+            .with_reading_interval(Duration::from_millis(100));
+        server.add_device("kitchen", &mut socket).unwrap();
+        let server = &server;
+        let subscribe = |last_event_id: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "GET /events HTTP/1.1\r\nHost: a\r\n{last_event_id}\r\n"
+            )
+            .unwrap();
+            let mut reader = BufReader::new(stream);
+            let head = read_stream_head(&mut reader);
+            assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(head.contains("\r\nContent-Type: text/event-stream\r\n"));
+            assert!(!head.contains("Content-Length"));
+            reader
+        };
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(3)));
+            let mut first = subscribe("");
+
+            // the only worker is not occupied by event stream
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .write_all(
+                    b"POST /turn_on/socket1 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+            stream.read_to_end(&mut vec![]).unwrap();
+            let (id, data) = read_event(&mut first, "state");
+            let device: serde_json::Value = serde_json::from_str(&data).unwrap();
+            assert_eq!(device["name"], "socket1");
+            assert_eq!(device["state"], "On");
+            assert!(device["reading"].as_str().unwrap().ends_with(" W"));
+            let (_, data) = read_event(&mut first, "readings");
+            let devices: serde_json::Value = serde_json::from_str(&data).unwrap();
+            assert_eq!(devices[0]["state"], "On");
+
+            // reconnecting client receives missed events
+            let mut second = subscribe(&format!("Last-Event-ID: {}\r\n", id - 1));
+            assert_eq!(read_event(&mut second, "state").0, id);
+
+            served.join().unwrap();
+            // streams are closed when server stops
+            first
+                .get_ref()
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .unwrap();
+            let start = Instant::now();
+            let _ = first.read_to_end(&mut vec![]);
+            assert!(start.elapsed() < READ_TIMEOUT);
+        });
+    }
+    /// Opens WebSocket with key from RFC 6455 example
+    fn open_websocket(address: std::net::SocketAddr) -> BufReader<TcpStream> {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(
+                b"GET /ws HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        stream.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        let mut reader = BufReader::new(stream);
+        let head = read_stream_head(&mut reader);
+        assert!(
+            head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+            "{head}"
+        );
+        assert!(head.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(head.contains("\r\nConnection: Upgrade\r\n"));
+        reader
+    }
+    /// Sends frame masked like client frames
+    fn send_frame(reader: &BufReader<TcpStream>, frame: Frame) {
+        frame
+            .write_to(reader.get_ref(), Some([7, 1, 9, 4]))
+            .unwrap();
+    }
+    fn receive(reader: &mut BufReader<TcpStream>) -> Message {
+        MessageReader::new(false, usize::MAX).read(reader).unwrap()
+    }
+    /// Sends JSON message and returns reply
+    fn request(reader: &mut BufReader<TcpStream>, message: &str) -> serde_json::Value {
+        send_frame(reader, Frame::new(Opcode::Text, message));
+        match receive(reader) {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("{message:?} is not text"),
+        }
+    }
+    #[test]
+    fn websocket_control_channel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut socket = Socket::new("socket1");
+        let mut server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_workers(1)
+            .with_reading_interval(Duration::from_secs(60));
+        server.add_device("kitchen", &mut socket).unwrap();
+        let server = &server;
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(2)));
+            let mut ws = open_websocket(address);
+
+            let reply = request(&mut ws, r#"{"type": "list"}"#);
+            assert_eq!(reply["type"], "devices");
+            assert_eq!(reply["devices"][0]["name"], "socket1");
+            assert_eq!(reply["devices"][0]["state"], "Off");
+            let reply = request(&mut ws, r#"{"type": "toggle", "device": "socket1"}"#);
+            assert_eq!(reply["type"], "toggled");
+            assert_eq!(reply["device"]["state"], "On");
+            assert!(reply["device"]["reading"].as_str().unwrap().ends_with(" W"));
+            let reply = request(&mut ws, r#"{"type": "toggle", "device": "lamp"}"#);
+            assert_eq!(reply["type"], "error");
+            assert_eq!(request(&mut ws, "list")["type"], "error");
+
+            let reply = request(&mut ws, r#"{"type": "subscribe"}"#);
+            assert_eq!(reply["type"], "subscribed");
+            let last_event_id = reply["last_event_id"].as_u64().unwrap();
+            // the only worker is not occupied by WebSocket
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .write_all(
+                    b"POST /turn_off/socket1 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+            stream.read_to_end(&mut vec![]).unwrap();
+            let Message::Text(text) = receive(&mut ws) else {
+                panic!("event is not text");
+            };
+            let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(event["type"], "event");
+            assert_eq!(event["id"], last_event_id + 1);
+            assert_eq!(event["event"], "state");
+            assert_eq!(event["data"]["state"], "Off");
+            assert_eq!(
+                request(&mut ws, r#"{"type": "unsubscribe"}"#)["type"],
+                "unsubscribed"
+            );
+
+            // ping is answered between fragments of another message
+            send_frame(
+                &ws,
+                Frame {
+                    fin: false,
+                    ..Frame::new(Opcode::Text, r#"{"type":"#)
+                },
+            );
+            send_frame(&ws, Frame::new(Opcode::Ping, "keepalive"));
+            send_frame(&ws, Frame::new(Opcode::Continuation, r#" "list"}"#));
+            assert_eq!(receive(&mut ws), Message::Pong(b"keepalive".to_vec()));
+            let Message::Text(text) = receive(&mut ws) else {
+                panic!("reply is not text");
+            };
+            assert!(text.contains(r#""type":"devices""#));
+
+            send_frame(&ws, Frame::close(CLOSE_NORMAL, "bye"));
+            assert_eq!(
+                receive(&mut ws),
+                Message::Close(Some(CLOSE_NORMAL), String::new())
+            );
+            assert_eq!(ws.read_to_end(&mut vec![]).unwrap(), 0);
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn websocket_protocol_errors() {
+        let mut server = Server::new("127.0.0.1", 0).unwrap();
+        let output = serve(&mut server, "GET /ws HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(
+            output.starts_with("HTTP/1.1 426 Upgrade Required\r\n"),
+            "{output}"
+        );
+        assert!(output.contains("\r\nUpgrade: websocket\r\n"));
+        let output = serve(
+            &mut server,
+            "GET /ws HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: a2V5\r\nSec-WebSocket-Version: 8\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+        assert!(output.contains("\r\nSec-WebSocket-Version: 13\r\n"));
+        let output = serve(
+            &mut server,
+            "GET /ws HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = &server;
+        thread::scope(|scope| {
+            let served = scope.spawn(|| server.serve_connections(listener.incoming().take(2)));
+            let mut ws = open_websocket(address);
+            send_frame(&ws, Frame::new(Opcode::Binary, [1, 2]));
+            assert!(matches!(
+                receive(&mut ws),
+                Message::Close(Some(CLOSE_UNSUPPORTED_DATA), _)
+            ));
+            // frames of clients should be masked
+            let mut ws = open_websocket(address);
+            Frame::new(Opcode::Text, "{}")
+                .write_to(ws.get_ref(), None)
+                .unwrap();
+            assert!(matches!(
+                receive(&mut ws),
+                Message::Close(Some(websocket::CLOSE_PROTOCOL_ERROR), _)
+            ));
+            served.join().unwrap();
+        });
+    }
+    #[test]
+    fn websocket_ping_and_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1", 0)
+            .unwrap()
+            .with_ping_interval(Duration::from_millis(100));
+        let handle = server.shutdown_handle();
+        let server = &server;
+        thread::scope(|scope| {
+            let served =
+                scope.spawn(|| server.serve_connections(accept_until_shutdown(&listener, &handle)));
+            // connection is closed if ping is not answered
+            let mut silent = open_websocket(address);
+            assert_eq!(receive(&mut silent), Message::Ping(vec![]));
+            assert_eq!(silent.read_to_end(&mut vec![]).unwrap(), 0);
+
+            let mut ws = open_websocket(address);
+            for _ in 0..2 {
+                assert_eq!(receive(&mut ws), Message::Ping(vec![]));
+                send_frame(&ws, Frame::new(Opcode::Pong, []));
+            }
+            handle.shutdown();
+            assert!(matches!(
+                receive(&mut ws),
+                Message::Close(Some(CLOSE_GOING_AWAY), _)
+            ));
+            let start = Instant::now();
+            send_frame(&ws, Frame::close(CLOSE_GOING_AWAY, ""));
+            served.join().unwrap();
+            assert!(start.elapsed() < CLOSE_TIMEOUT);
+        });
     }
 }
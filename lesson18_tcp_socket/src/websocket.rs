@@ -0,0 +1,485 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+
+use crate::request::{HttpVersion, Request};
+
+/// GUID appended to client key, see RFC 6455 section 1.3
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The only protocol version defined by RFC 6455
+pub const VERSION: &str = "13";
+
+/// Close status codes, see RFC 6455 section 7.4.1
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+pub const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Returns `Sec-WebSocket-Accept` value for client `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    STANDARD.encode(Sha1::digest(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// Enum for refused opening handshakes
+#[derive(Debug, PartialEq, Eq)]
+pub enum HandshakeError {
+    /// Request does not ask to upgrade connection to WebSocket
+    NotUpgrade,
+    UnsupportedVersion,
+    MissingKey,
+}
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::NotUpgrade => write!(f, "Request should upgrade to websocket"),
+            HandshakeError::UnsupportedVersion => {
+                write!(f, "WebSocket version should be {}", VERSION)
+            }
+            HandshakeError::MissingKey => write!(f, "Sec-WebSocket-Key header is missing"),
+        }
+    }
+}
+
+/// Returns `Sec-WebSocket-Key` of opening handshake
+/// Returns `Err(HandshakeError)` if request is not a valid handshake
+pub fn handshake_key(request: &Request) -> Result<&str, HandshakeError> {
+    if request.version != HttpVersion::Http11
+        || !request.headers.has_token("upgrade", "websocket")
+        || !request.headers.has_token("connection", "upgrade")
+    {
+        return Err(HandshakeError::NotUpgrade);
+    }
+    if request.headers.get("sec-websocket-version").map(str::trim) != Some(VERSION) {
+        return Err(HandshakeError::UnsupportedVersion);
+    }
+    request
+        .headers
+        .get("sec-websocket-key")
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .ok_or(HandshakeError::MissingKey)
+}
+
+/// Enum for frame types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+    fn code(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+    /// Returns `true` for close, ping and pong frames
+    pub fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+/// Enum for WebSocket reading errors
+#[derive(Debug)]
+pub enum WebSocketError {
+    Io(io::Error),
+    /// Peer violates RFC 6455
+    Protocol(&'static str),
+    InvalidUtf8,
+    TooLarge(usize),
+}
+
+impl WebSocketError {
+    /// Returns status code of close frame sent to peer
+    pub fn close_code(&self) -> u16 {
+        match self {
+            WebSocketError::Io(_) | WebSocketError::Protocol(_) => CLOSE_PROTOCOL_ERROR,
+            WebSocketError::InvalidUtf8 => CLOSE_INVALID_DATA,
+            WebSocketError::TooLarge(_) => CLOSE_TOO_BIG,
+        }
+    }
+}
+
+impl From<io::Error> for WebSocketError {
+    fn from(value: io::Error) -> Self {
+        WebSocketError::Io(value)
+    }
+}
+
+impl Display for WebSocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebSocketError::Io(e) => write!(f, "WebSocket io error: {}", e),
+            WebSocketError::Protocol(msg) => write!(f, "Protocol error: {}", msg),
+            WebSocketError::InvalidUtf8 => write!(f, "Text is not UTF-8"),
+            WebSocketError::TooLarge(limit) => {
+                write!(f, "Message is larger than {} bytes", limit)
+            }
+        }
+    }
+}
+
+/// WebSocket frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// `false` if message continues in the next frame
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Returns frame holding whole message
+    pub fn new(opcode: Opcode, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            fin: true,
+            opcode,
+            payload: payload.into(),
+        }
+    }
+    /// Returns close frame with status code and reason
+    pub fn close(code: u16, reason: &str) -> Self {
+        let mut payload = code.to_be_bytes().to_vec();
+        // control frames are limited to 125 bytes, reason stays valid UTF-8
+        let mut length = reason.len().min(123);
+        while !reason.is_char_boundary(length) {
+            length -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[..length]);
+        Self::new(Opcode::Close, payload)
+    }
+    /// Reads frame and unmasks its payload
+    ///
+    /// Frames from clients are `masked`, frames from servers are not.
+    /// Returns `Err(WebSocketError)` if frame violates protocol
+    /// or its payload is longer than `max_payload`
+    pub fn read<R>(reader: &mut R, masked: bool, max_payload: usize) -> Result<Self, WebSocketError>
+    where
+        R: Read,
+    {
+        let mut head = [0; 2];
+        reader.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        if head[0] & 0x70 != 0 {
+            return Err(WebSocketError::Protocol("reserved bits are set"));
+        }
+        let opcode =
+            Opcode::from_code(head[0] & 0x0F).ok_or(WebSocketError::Protocol("unknown opcode"))?;
+        if (head[1] & 0x80 != 0) != masked {
+            return Err(WebSocketError::Protocol(match masked {
+                true => "client frames should be masked",
+                false => "server frames should not be masked",
+            }));
+        }
+        let length = match head[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                reader.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as u64
+            }
+            127 => {
+                let mut length = [0; 8];
+                reader.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => length as u64,
+        };
+        if opcode.is_control() && (!fin || length > 125) {
+            return Err(WebSocketError::Protocol(
+                "control frames should not be fragmented or longer than 125 bytes",
+            ));
+        }
+        if length > max_payload as u64 {
+            return Err(WebSocketError::TooLarge(max_payload));
+        }
+        let mut mask = [0; 4];
+        if masked {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length as usize];
+        reader.read_exact(&mut payload)?;
+        apply_mask(&mut payload, mask);
+        Ok(Self {
+            fin,
+            opcode,
+            payload,
+        })
+    }
+    /// Writes frame, clients give `mask` of their frames
+    /// Returns `io::Err` if it failes to write to stream
+    pub fn write_to<W>(&self, mut writer: W, mask: Option<[u8; 4]>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut frame = vec![(u8::from(self.fin) << 7) | self.opcode.code()];
+        let mask_bit = match mask {
+            Some(_) => 0x80,
+            None => 0,
+        };
+        match self.payload.len() {
+            length @ 0..=125 => frame.push(mask_bit | length as u8),
+            length @ 126..=0xFFFF => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(length as u16).to_be_bytes());
+            }
+            length => {
+                frame.push(mask_bit | 127);
+                frame.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+        let mut payload = self.payload.clone();
+        if let Some(mask) = mask {
+            frame.extend_from_slice(&mask);
+            apply_mask(&mut payload, mask);
+        }
+        frame.extend(payload);
+        writer.write_all(&frame)?;
+        writer.flush()
+    }
+}
+
+/// Masks or unmasks payload
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// Enum for WebSocket messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// Status code and reason, code is `None` if peer gave none
+    Close(Option<u16>, String),
+}
+
+/// Reader of messages reassembled from fragmented frames
+#[derive(Debug)]
+pub struct MessageReader {
+    masked: bool,
+    max_message: usize,
+    /// Type and payload of fragmented message received so far
+    fragments: Option<(Opcode, Vec<u8>)>,
+}
+
+impl MessageReader {
+    /// Returns reader of `masked` frames of messages at most `max_message` bytes long
+    pub fn new(masked: bool, max_message: usize) -> Self {
+        Self {
+            masked,
+            max_message,
+            fragments: None,
+        }
+    }
+    /// Reads the next message, control messages may arrive between fragments of another
+    /// Returns `Err(WebSocketError)` if frames violate protocol or message is too large
+    pub fn read<R>(&mut self, reader: &mut R) -> Result<Message, WebSocketError>
+    where
+        R: Read,
+    {
+        loop {
+            let received = self.fragments.as_ref().map_or(0, |(_, data)| data.len());
+            let frame = Frame::read(reader, self.masked, self.max_message - received)?;
+            if frame.opcode.is_control() {
+                return Self::message(frame.opcode, frame.payload);
+            }
+            let (opcode, payload) = match (frame.opcode, self.fragments.take()) {
+                (Opcode::Continuation, None) => {
+                    return Err(WebSocketError::Protocol("continuation of no message"))
+                }
+                (Opcode::Continuation, Some((opcode, mut payload))) => {
+                    payload.extend(frame.payload);
+                    (opcode, payload)
+                }
+                (_, Some(_)) => {
+                    return Err(WebSocketError::Protocol(
+                        "fragmented message is not finished",
+                    ))
+                }
+                (opcode, None) => (opcode, frame.payload),
+            };
+            match frame.fin {
+                true => return Self::message(opcode, payload),
+                false => self.fragments = Some((opcode, payload)),
+            }
+        }
+    }
+    fn message(opcode: Opcode, payload: Vec<u8>) -> Result<Message, WebSocketError> {
+        match opcode {
+            Opcode::Text => String::from_utf8(payload)
+                .map(Message::Text)
+                .map_err(|_| WebSocketError::InvalidUtf8),
+            Opcode::Binary => Ok(Message::Binary(payload)),
+            Opcode::Ping => Ok(Message::Ping(payload)),
+            Opcode::Pong => Ok(Message::Pong(payload)),
+            Opcode::Close => match payload.len() {
+                0 => Ok(Message::Close(None, String::new())),
+                1 => Err(WebSocketError::Protocol("close code is truncated")),
+                _ => String::from_utf8(payload[2..].to_vec())
+                    .map(|reason| {
+                        Message::Close(Some(u16::from_be_bytes([payload[0], payload[1]])), reason)
+                    })
+                    .map_err(|_| WebSocketError::InvalidUtf8),
+            },
+            Opcode::Continuation => Err(WebSocketError::Protocol("continuation of no message")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_of_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+    #[test]
+    fn write_and_read_frames() {
+        for length in [0, 125, 126, 0xFFFF, 0x10000] {
+            let frame = Frame::new(Opcode::Binary, vec![7; length]);
+            for mask in [None, Some([1, 2, 3, 4])] {
+                let mut output = vec![];
+                frame.write_to(&mut output, mask).unwrap();
+                let read = Frame::read(&mut output.as_slice(), mask.is_some(), 0x10000).unwrap();
+                assert_eq!(read, frame);
+            }
+        }
+        // RFC 6455 section 5.7 examples
+        let mut output = vec![];
+        Frame::new(Opcode::Text, "Hello")
+            .write_to(&mut output, Some([0x37, 0xfa, 0x21, 0x3d]))
+            .unwrap();
+        assert_eq!(
+            output,
+            [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+        );
+        let frame = Frame::read(&mut [0x01, 0x03, 0x48, 0x65, 0x6c].as_slice(), false, 10).unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.payload, b"Hel");
+        // reason is cut before a character crossing the control frame limit
+        let close = Frame::close(CLOSE_NORMAL, &format!("{}ж", "a".repeat(122)));
+        assert_eq!(close.payload.len(), 124);
+        assert!(std::str::from_utf8(&close.payload[2..]).is_ok());
+        assert_eq!(
+            Frame::close(CLOSE_NORMAL, &"a".repeat(200)).payload.len(),
+            125
+        );
+    }
+    #[test]
+    fn reassemble_fragmented_messages() {
+        let mut input = vec![];
+        for frame in [
+            Frame {
+                fin: false,
+                opcode: Opcode::Text,
+                payload: b"Hel".to_vec(),
+            },
+            Frame::new(Opcode::Ping, "p"),
+            Frame::new(Opcode::Continuation, "lo"),
+            Frame::close(CLOSE_NORMAL, "bye"),
+        ] {
+            frame.write_to(&mut input, Some([9, 8, 7, 6])).unwrap();
+        }
+        let mut reader = MessageReader::new(true, 16);
+        let mut input = input.as_slice();
+        assert_eq!(
+            reader.read(&mut input).unwrap(),
+            Message::Ping(b"p".to_vec())
+        );
+        assert_eq!(
+            reader.read(&mut input).unwrap(),
+            Message::Text("Hello".to_string())
+        );
+        assert_eq!(
+            reader.read(&mut input).unwrap(),
+            Message::Close(Some(CLOSE_NORMAL), "bye".to_string())
+        );
+    }
+    #[test]
+    fn reject_protocol_violations() {
+        let read = |frames: &[Frame], mask: Option<[u8; 4]>| {
+            let mut input = vec![];
+            for frame in frames {
+                frame.write_to(&mut input, mask).unwrap();
+            }
+            MessageReader::new(true, 8)
+                .read(&mut input.as_slice())
+                .unwrap_err()
+                .close_code()
+        };
+        let mask = Some([1, 1, 1, 1]);
+        let unfinished = Frame {
+            fin: false,
+            opcode: Opcode::Text,
+            payload: b"1234".to_vec(),
+        };
+        assert_eq!(
+            read(&[Frame::new(Opcode::Text, "a")], None),
+            CLOSE_PROTOCOL_ERROR
+        );
+        assert_eq!(
+            read(&[Frame::new(Opcode::Continuation, "a")], mask),
+            CLOSE_PROTOCOL_ERROR
+        );
+        assert_eq!(
+            read(&[unfinished.clone(), Frame::new(Opcode::Text, "a")], mask),
+            CLOSE_PROTOCOL_ERROR
+        );
+        assert_eq!(
+            read(
+                &[Frame {
+                    fin: false,
+                    opcode: Opcode::Ping,
+                    payload: vec![],
+                }],
+                mask
+            ),
+            CLOSE_PROTOCOL_ERROR
+        );
+        assert_eq!(
+            read(
+                &[unfinished, Frame::new(Opcode::Continuation, "56789")],
+                mask
+            ),
+            CLOSE_TOO_BIG
+        );
+        assert_eq!(
+            read(&[Frame::new(Opcode::Text, [0xFF])], mask),
+            CLOSE_INVALID_DATA
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use lesson8_lib::{DeviceInfo, Home, Socket, Thermometer};
+use lesson8_lib::{home, DeviceInfo, Socket, Thermometer, OWNER};
 
 fn main() {
     let home_name = "home";
@@ -10,25 +10,27 @@ fn main() {
     // create devices with names unique in room
     let mut socket1 = Socket::new(socket1_name);
     let mut thermo1 = Thermometer::new(thermo1_name);
-    // create home
-    let mut home = Home::new(home_name);
-    // add room with unique name
-    home.add_room(room1_name).unwrap();
-    // add devices to existing room
-    home.add_device(room1_name, &mut socket1).unwrap();
-    home.add_device(room1_name, &mut thermo1).unwrap();
+    // create home with room of unique name and its devices
+    let mut home = home! {
+        home_name => {
+            room1_name => [&mut socket1, &mut thermo1],
+        }
+    }
+    .unwrap();
 
-    println!("Home report: {}", home.get_home_report());
+    // changes and reports are made on behalf of a user, new home has its owner only
+    let mut owner = home.acting_as(OWNER).unwrap();
+    println!("Home report: {}", owner.get_home_report().unwrap());
     println!(
         "Vec of devices in room '{}': {:?}",
         room1_name,
-        home.get_devices_in_room(room1_name).unwrap()
+        owner.get_devices_in_room(room1_name).unwrap()
     );
-    println!("Rooms in house: {:?}", home.get_room_names());
+    println!("Rooms in house: {:?}", owner.get_room_names());
 
     // get report from existing device in existing room
     let dev_info = DeviceInfo::new(socket1_name, room1_name);
-    let report = home.get_device_report(&dev_info);
+    let report = owner.get_device_report(&dev_info);
 
     println!(
         "Report from room '{}', device '{}': {}",
@@ -38,8 +40,8 @@ fn main() {
     );
 
     // turn on existing device and get report
-    home.turn_on(&dev_info).unwrap();
-    let report = home.get_device_report(&dev_info);
+    owner.turn_on(&dev_info).unwrap();
+    let report = owner.get_device_report(&dev_info);
     println!(
         "Report from room '{}', turned on device '{}': {}",
         room1_name,
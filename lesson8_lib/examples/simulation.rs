@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use lesson8_lib::{home, DeviceInfo, Reading, Simulator, OWNER};
+
+fn main() {
+    let mut sim = Simulator::new(42)
+        .with_outdoor_temperature(-2.0)
+        .with_initial_temperature(17.0)
+        .with_occupant("alice", "living")
+        .with_step(Duration::from_secs(10 * 60));
+    let mut thermo = sim.thermometer("thermo");
+    let mut heater = sim.heater("heater", 4000);
+    let mut tv = sim.appliance("tv", 2, 80);
+    let mut home = home! {
+        "home" => {
+            "living" => [&mut thermo, &mut heater, &mut tv],
+            "kitchen" => [],
+        }
+    }
+    .unwrap();
+    home.acting_as(OWNER)
+        .unwrap()
+        .turn_on(&DeviceInfo::new("tv", "living"))
+        .unwrap();
+
+    // thermostat keeping living room between 20 and 22 degrees
+    let thermo_info = DeviceInfo::new("thermo", "living");
+    let heater_info = DeviceInfo::new("heater", "living");
+    let trace = sim.run_with(&mut home, Duration::from_secs(6 * 3600), |home, _| {
+        if let Some(Reading::Temperature(t)) = home.snapshot().device(&thermo_info).unwrap().reading
+        {
+            if t.celsius() < 20.0 {
+                let _ = home.acting_as(OWNER).unwrap().turn_on(&heater_info);
+            } else if t.celsius() > 22.0 {
+                let _ = home.acting_as(OWNER).unwrap().turn_off(&heater_info);
+            }
+        }
+    });
+    print!("{}", trace);
+    let report = home.acting_as(OWNER).unwrap().get_home_report().unwrap();
+    println!("Home report: {}", report);
+}
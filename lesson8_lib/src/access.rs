@@ -0,0 +1,539 @@
+use std::{fmt::Display, time::SystemTime};
+
+use crate::{
+    device::{Device, DeviceInfo, DeviceMetadata},
+    errors::{access_errors::AccessErrors, home_errors::HomeErrors},
+    home::Home,
+    temperature::{Calibration, TemperatureDisplay},
+    transaction::{Operation, OperationResult, Transaction},
+};
+
+/// Name of user and role every access control starts with, owner administers the whole home
+pub const OWNER: &str = "owner";
+
+/// Enum for user permissions
+///
+/// Each permission includes the previous ones:
+/// `Administer` allows to control and `Control` allows to view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Get reports, room and device names
+    View,
+    /// Turn devices on and off
+    Control,
+    /// Add and remove rooms and devices, edit device metadata and calibration
+    Administer,
+}
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Permission::View => write!(f, "View"),
+            Permission::Control => write!(f, "Control"),
+            Permission::Administer => write!(f, "Administer"),
+        }
+    }
+}
+
+/// Part of home a permission is granted for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Whole home with all rooms and devices
+    Home,
+    /// Room with all its devices
+    Room(String),
+    /// Single device
+    Device(DeviceInfo),
+}
+
+impl Scope {
+    /// Returns `true` if `self` includes `other`
+    pub fn covers(&self, other: &Scope) -> bool {
+        match (self, other) {
+            (Scope::Home, _) => true,
+            (Scope::Room(room), Scope::Room(other_room)) => room == other_room,
+            (Scope::Room(room), Scope::Device(device_info)) => *room == device_info.room_name,
+            (Scope::Device(device_info), Scope::Device(other_info)) => device_info == other_info,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Home => write!(f, "home"),
+            Scope::Room(room) => write!(f, "room {}", room),
+            Scope::Device(device_info) => write!(
+                f,
+                "device {}/{}",
+                device_info.room_name, device_info.device_name
+            ),
+        }
+    }
+}
+
+/// Named set of permissions
+#[derive(Debug, Clone)]
+pub struct Role {
+    /// Role name
+    name: String,
+    /// Granted permissions
+    grants: Vec<(Scope, Permission)>,
+}
+
+impl Role {
+    /// Returns new role without permissions
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            grants: vec![],
+        }
+    }
+    /// Grants `permission` for `scope`
+    pub fn grant(mut self, scope: Scope, permission: Permission) -> Self {
+        self.grants.push((scope, permission));
+        self
+    }
+    /// Returns role name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns `true` if role allows `permission` for `scope`
+    pub fn allows(&self, scope: &Scope, permission: Permission) -> bool {
+        self.grants
+            .iter()
+            .any(|(s, p)| *p >= permission && s.covers(scope))
+    }
+}
+
+/// Home user
+#[derive(Debug, Clone)]
+pub struct User {
+    /// User name
+    name: String,
+    /// Names of user roles
+    roles: Vec<String>,
+}
+
+impl User {
+    /// Returns new user without roles
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            roles: vec![],
+        }
+    }
+    /// Adds role to user
+    pub fn with_role(mut self, role_name: &str) -> Self {
+        self.roles.push(role_name.to_string());
+        self
+    }
+    /// Returns user name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Record about denied user action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeniedAttempt {
+    /// User name
+    pub user_name: String,
+    /// Name of called method
+    pub action: String,
+    /// Target of the action
+    pub scope: Scope,
+    /// Missing permission
+    pub permission: Permission,
+    /// Time of the attempt
+    pub time: SystemTime,
+}
+
+impl Display for DeniedAttempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "User {} has no {} permission for {} to {}",
+            self.user_name, self.permission, self.scope, self.action
+        )
+    }
+}
+
+/// Access control struct
+///
+/// Stores users, roles and denied attempts audit.
+/// User names and role names should be unique
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+    /// Vector of roles
+    roles: Vec<Role>,
+    /// Vector of users
+    users: Vec<User>,
+    /// Audit of denied attempts
+    denied_attempts: Vec<DeniedAttempt>,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccessControl {
+    /// Returns access control with `OWNER` user only
+    pub fn new() -> Self {
+        Self {
+            roles: vec![Role::new(OWNER).grant(Scope::Home, Permission::Administer)],
+            users: vec![User::new(OWNER).with_role(OWNER)],
+            denied_attempts: vec![],
+        }
+    }
+    /// Adds new role
+    ///
+    /// Returns `Ok(())` if role name is unique, `Err` otherwise
+    pub fn add_role(&mut self, role: Role) -> Result<(), AccessErrors> {
+        if self.roles.iter().any(|r| r.name == role.name) {
+            return Err(AccessErrors::RoleNameExists(role.name));
+        }
+        self.roles.push(role);
+        Ok(())
+    }
+    /// Adds new user
+    ///
+    /// Returns `Ok(())` if user name is unique and all user roles exist, `Err` otherwise
+    pub fn add_user(&mut self, user: User) -> Result<(), AccessErrors> {
+        if self.users.iter().any(|u| u.name == user.name) {
+            return Err(AccessErrors::UserNameExists(user.name));
+        }
+        if let Some(role) = user
+            .roles
+            .iter()
+            .find(|role| !self.roles.iter().any(|r| r.name == **role))
+        {
+            return Err(AccessErrors::RoleNameDoesNotExist(role.to_string()));
+        }
+        self.users.push(user);
+        Ok(())
+    }
+    /// Removes user
+    ///
+    /// Returns `Ok(())` if `user_name` is found, `Err` otherwise
+    pub fn remove_user(&mut self, user_name: &str) -> Result<(), AccessErrors> {
+        if self.users.iter().any(|u| u.name == user_name) {
+            self.users.retain(|u| u.name != user_name);
+            return Ok(());
+        }
+        Err(AccessErrors::UserNameDoesNotExist(user_name.to_string()))
+    }
+    /// Returns `true` if user exists
+    pub fn has_user(&self, user_name: &str) -> bool {
+        self.users.iter().any(|u| u.name == user_name)
+    }
+    /// Returns `true` if user has `permission` for `scope`
+    pub fn is_allowed(&self, user_name: &str, scope: &Scope, permission: Permission) -> bool {
+        let user = self.users.iter().find(|u| u.name == user_name);
+        if user.is_none() {
+            return false;
+        }
+        user.unwrap().roles.iter().any(|role_name| {
+            self.roles
+                .iter()
+                .any(|r| r.name == *role_name && r.allows(scope, permission))
+        })
+    }
+    /// Returns audit of denied attempts
+    pub fn denied_attempts(&self) -> &[DeniedAttempt] {
+        &self.denied_attempts
+    }
+    /// Checks permission and records the attempt if it is denied
+    ///
+    /// Returns `Err(HomeErrors::PermissionDenied)` if user has no `permission` for `scope`
+    fn check(
+        &mut self,
+        user_name: &str,
+        action: &str,
+        scope: Scope,
+        permission: Permission,
+    ) -> Result<(), HomeErrors> {
+        if self.is_allowed(user_name, &scope, permission) {
+            return Ok(());
+        }
+        let attempt = DeniedAttempt {
+            user_name: user_name.to_string(),
+            action: action.to_string(),
+            scope,
+            permission,
+            time: SystemTime::now(),
+        };
+        self.denied_attempts.push(attempt.clone());
+        Err(HomeErrors::PermissionDenied(attempt))
+    }
+}
+
+/// Home accessed on behalf of a user
+///
+/// Every call is checked against user permissions.
+/// Created by `Home::acting_as`
+#[derive(Debug)]
+pub struct UserContext<'h, 'a> {
+    /// Accessed home
+    home: &'h mut Home<'a>,
+    /// Acting user name
+    user_name: String,
+}
+
+impl<'h, 'a> UserContext<'h, 'a> {
+    pub(crate) fn new(home: &'h mut Home<'a>, user_name: &str) -> Self {
+        Self {
+            home,
+            user_name: user_name.to_string(),
+        }
+    }
+    /// Returns acting user name
+    pub fn user_name(&self) -> &str {
+        &self.user_name
+    }
+    /// Returns accessed home, e.g. to take snapshot or iterate over devices
+    pub fn home(&self) -> &Home<'a> {
+        self.home
+    }
+    /// Checks acting user permission
+    fn check(&mut self, action: &str, scope: Scope, permission: Permission) -> OperationResult {
+        self.home
+            .access_control_mut()
+            .check(&self.user_name, action, scope, permission)
+    }
+    /// Adds new role. Requires `Administer` for home
+    pub fn add_role(&mut self, role: Role) -> OperationResult {
+        self.check("add_role", Scope::Home, Permission::Administer)?;
+        Ok(self.home.access_control_mut().add_role(role)?)
+    }
+    /// Adds new user. Requires `Administer` for home
+    pub fn add_user(&mut self, user: User) -> OperationResult {
+        self.check("add_user", Scope::Home, Permission::Administer)?;
+        Ok(self.home.access_control_mut().add_user(user)?)
+    }
+    /// Removes user. Requires `Administer` for home
+    pub fn remove_user(&mut self, user_name: &str) -> OperationResult {
+        self.check("remove_user", Scope::Home, Permission::Administer)?;
+        Ok(self.home.access_control_mut().remove_user(user_name)?)
+    }
+    /// Adds new room. Requires `Administer` for home
+    pub fn add_room(&mut self, room_name: &str) -> OperationResult {
+        self.check("add_room", Scope::Home, Permission::Administer)?;
+        self.home.add_room(room_name)
+    }
+    /// Removes a room. Requires `Administer` for the room
+    pub fn remove_room(&mut self, room_name: &str) -> OperationResult {
+        self.check(
+            "remove_room",
+            Scope::Room(room_name.to_string()),
+            Permission::Administer,
+        )?;
+        self.home.remove_room(room_name)
+    }
+    /// Adds device. Requires `Administer` for the room
+    pub fn add_device(&mut self, room_name: &str, device: &'a mut dyn Device) -> OperationResult {
+        self.check(
+            "add_device",
+            Scope::Room(room_name.to_string()),
+            Permission::Administer,
+        )?;
+        self.home.add_device(room_name, device)
+    }
+    /// Removes device. Requires `Administer` for the device
+    pub fn remove_device(&mut self, device_info: &DeviceInfo) -> OperationResult {
+        self.check(
+            "remove_device",
+            Scope::Device(device_info.clone()),
+            Permission::Administer,
+        )?;
+        self.home.remove_device(device_info)
+    }
+    /// Turns on a device. Requires `Control` for the device
+    pub fn turn_on(&mut self, device_info: &DeviceInfo) -> OperationResult {
+        self.check(
+            "turn_on",
+            Scope::Device(device_info.clone()),
+            Permission::Control,
+        )?;
+        self.home.turn_on(device_info)
+    }
+    /// Allows tripped device to be turned on again. Requires `Control` for the device
+    pub fn reset_device(&mut self, device_info: &DeviceInfo) -> Result<bool, HomeErrors> {
+        self.check(
+            "reset_device",
+            Scope::Device(device_info.clone()),
+            Permission::Control,
+        )?;
+        self.home.reset_device(device_info)
+    }
+    /// Turns off a device. Requires `Control` for the device
+    pub fn turn_off(&mut self, device_info: &DeviceInfo) -> OperationResult {
+        self.check(
+            "turn_off",
+            Scope::Device(device_info.clone()),
+            Permission::Control,
+        )?;
+        self.home.turn_off(device_info)
+    }
+    /// Commits transaction. Requires permissions for every operation
+    pub fn commit(
+        &mut self,
+        transaction: Transaction<'a>,
+    ) -> Result<Vec<OperationResult>, HomeErrors> {
+        for operation in transaction.operations() {
+            let (action, scope, permission) = match operation {
+                Operation::AddRoom(_) => ("add_room", Scope::Home, Permission::Administer),
+                Operation::RemoveRoom(room_name) => (
+                    "remove_room",
+                    Scope::Room(room_name.clone()),
+                    Permission::Administer,
+                ),
+                Operation::AddDevice { room_name, .. } => (
+                    "add_device",
+                    Scope::Room(room_name.clone()),
+                    Permission::Administer,
+                ),
+                Operation::RemoveDevice(device_info) => (
+                    "remove_device",
+                    Scope::Device(device_info.clone()),
+                    Permission::Administer,
+                ),
+                Operation::TurnOn(device_info) => (
+                    "turn_on",
+                    Scope::Device(device_info.clone()),
+                    Permission::Control,
+                ),
+                Operation::TurnOff(device_info) => (
+                    "turn_off",
+                    Scope::Device(device_info.clone()),
+                    Permission::Control,
+                ),
+            };
+            self.check(action, scope, permission)?;
+        }
+        self.home.commit(transaction)
+    }
+    /// Reverts the last committed transaction. Requires `Administer` for home
+    pub fn undo(&mut self) -> OperationResult {
+        self.check("undo", Scope::Home, Permission::Administer)?;
+        self.home.undo()
+    }
+    /// Returns names of rooms visible to user
+    pub fn get_room_names(&self) -> Vec<&str> {
+        let access_control = self.home.access_control();
+        self.home
+            .get_room_names()
+            .into_iter()
+            .filter(|r| {
+                access_control.is_allowed(
+                    &self.user_name,
+                    &Scope::Room(r.to_string()),
+                    Permission::View,
+                )
+            })
+            .collect()
+    }
+    /// Returns device names. Requires `View` for the room
+    pub fn get_devices_in_room(&mut self, room_name: &str) -> Result<Vec<&str>, HomeErrors> {
+        self.check(
+            "get_devices_in_room",
+            Scope::Room(room_name.to_string()),
+            Permission::View,
+        )?;
+        self.home.get_devices_in_room(room_name)
+    }
+    /// Returns device report. Requires `View` for the device
+    pub fn get_device_report(&mut self, device_info: &DeviceInfo) -> Result<String, HomeErrors> {
+        self.check(
+            "get_device_report",
+            Scope::Device(device_info.clone()),
+            Permission::View,
+        )?;
+        self.home.get_device_report(device_info)
+    }
+    /// Returns reports of devices. Requires `View` for every device
+    pub fn get_devices_report(
+        &mut self,
+        device_infos: Vec<&DeviceInfo>,
+    ) -> Vec<Result<String, HomeErrors>> {
+        device_infos
+            .into_iter()
+            .map(|device_info| self.get_device_report(device_info))
+            .collect()
+    }
+    /// Returns device metadata. Requires `View` for the device
+    pub fn get_device_metadata(
+        &mut self,
+        device_info: &DeviceInfo,
+    ) -> Result<DeviceMetadata, HomeErrors> {
+        self.check(
+            "get_device_metadata",
+            Scope::Device(device_info.clone()),
+            Permission::View,
+        )?;
+        self.home.get_device_metadata(device_info)
+    }
+    /// Replaces device metadata. Requires `Administer` for the device
+    pub fn set_device_metadata(
+        &mut self,
+        device_info: &DeviceInfo,
+        metadata: DeviceMetadata,
+    ) -> OperationResult {
+        self.check(
+            "set_device_metadata",
+            Scope::Device(device_info.clone()),
+            Permission::Administer,
+        )?;
+        self.home.set_device_metadata(device_info, metadata)
+    }
+    /// Replaces device calibration. Requires `Administer` for the device
+    pub fn set_device_calibration(
+        &mut self,
+        device_info: &DeviceInfo,
+        calibration: Calibration,
+    ) -> OperationResult {
+        self.check(
+            "set_device_calibration",
+            Scope::Device(device_info.clone()),
+            Permission::Administer,
+        )?;
+        self.home.set_device_calibration(device_info, calibration)
+    }
+    /// Returns home report. Requires `View` for home
+    pub fn get_home_report(&mut self) -> Result<String, HomeErrors> {
+        self.check("get_home_report", Scope::Home, Permission::View)?;
+        Ok(self.home.get_home_report())
+    }
+    /// Returns iterator over room name and mutable device pairs of all rooms.
+    /// Requires `Administer` for home
+    ///
+    /// Changes made directly to devices are not counted as state changes and not journaled
+    pub fn devices_mut(
+        &mut self,
+    ) -> Result<impl Iterator<Item = (&str, &mut dyn Device)> + use<'_, 'a>, HomeErrors> {
+        self.check("devices_mut", Scope::Home, Permission::Administer)?;
+        Ok(self.home.devices_mut())
+    }
+    /// Sets limit of total power in watts. Requires `Administer` for home
+    pub fn set_power_budget(&mut self, power_budget: Option<u32>) -> OperationResult {
+        self.check("set_power_budget", Scope::Home, Permission::Administer)?;
+        self.home.set_power_budget(power_budget);
+        Ok(())
+    }
+    /// Sets unit and precision of temperatures in reports. Requires `Administer` for home
+    pub fn set_temperature_display(
+        &mut self,
+        temperature_display: TemperatureDisplay,
+    ) -> OperationResult {
+        self.check(
+            "set_temperature_display",
+            Scope::Home,
+            Permission::Administer,
+        )?;
+        self.home.set_temperature_display(temperature_display);
+        Ok(())
+    }
+}
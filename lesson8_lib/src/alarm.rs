@@ -0,0 +1,542 @@
+use std::{
+    fmt::{Debug, Display},
+    fs::OpenOptions,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    device::{DeviceInfo, Reading},
+    errors::alarm_errors::AlarmErrors,
+    home::Home,
+};
+
+/// Enum for alarm threshold condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Reading value is greater than threshold
+    Above(i64),
+    /// Reading value is less than threshold
+    Below(i64),
+}
+
+impl Condition {
+    /// Returns `true` if reading value in thousandths of unit breaks the threshold
+    fn is_met(&self, milli_value: i64) -> bool {
+        match self {
+            Condition::Above(threshold) => milli_value > threshold.saturating_mul(1000),
+            Condition::Below(threshold) => milli_value < threshold.saturating_mul(1000),
+        }
+    }
+    /// Returns `true` if reading value in thousandths of unit
+    /// is back inside threshold by `hysteresis`
+    fn is_cleared(&self, milli_value: i64, hysteresis: i64) -> bool {
+        match self {
+            Condition::Above(threshold) => {
+                milli_value <= threshold.saturating_sub(hysteresis).saturating_mul(1000)
+            }
+            Condition::Below(threshold) => {
+                milli_value >= threshold.saturating_add(hysteresis).saturating_mul(1000)
+            }
+        }
+    }
+}
+
+/// Enum for alarm lifecycle state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmState {
+    /// Condition is not met
+    Normal,
+    /// Condition is met, but not long enough
+    Pending { since: SystemTime },
+    /// Condition is met long enough
+    Raised { at: SystemTime },
+    /// Raised alarm is seen by user
+    Acknowledged { at: SystemTime },
+}
+
+/// Threshold alarm over a device reading
+#[derive(Debug, Clone)]
+pub struct Alarm {
+    /// Unique alarm name
+    name: String,
+    /// Watched device
+    device: DeviceInfo,
+    /// Threshold condition
+    condition: Condition,
+    /// Time the condition should hold before raising
+    debounce: Duration,
+    /// Distance from threshold the reading should return to clear
+    hysteresis: i64,
+    /// Current lifecycle state
+    state: AlarmState,
+}
+
+impl Alarm {
+    /// Returns alarm raised immediately when condition is met
+    pub fn new(name: &str, device: DeviceInfo, condition: Condition) -> Self {
+        Self {
+            name: name.to_string(),
+            device,
+            condition,
+            debounce: Duration::ZERO,
+            hysteresis: 0,
+            state: AlarmState::Normal,
+        }
+    }
+    /// Returns alarm raised when reading is above `threshold`
+    pub fn above(name: &str, device: DeviceInfo, threshold: i64) -> Self {
+        Self::new(name, device, Condition::Above(threshold))
+    }
+    /// Returns alarm raised when reading is below `threshold`
+    pub fn below(name: &str, device: DeviceInfo, threshold: i64) -> Self {
+        Self::new(name, device, Condition::Below(threshold))
+    }
+    /// Sets time the condition should hold before raising
+    pub fn lasting(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+    /// Sets distance from threshold the reading should return to clear
+    pub fn with_hysteresis(mut self, hysteresis: i64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+    /// Returns alarm name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns watched device
+    pub fn device(&self) -> &DeviceInfo {
+        &self.device
+    }
+    /// Returns current lifecycle state
+    pub fn state(&self) -> AlarmState {
+        self.state
+    }
+    /// Returns `true` if alarm is raised or acknowledged
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.state,
+            AlarmState::Raised { .. } | AlarmState::Acknowledged { .. }
+        )
+    }
+    /// Updates state with new reading
+    ///
+    /// Returns event kind if the alarm is raised or cleared
+    fn update(&mut self, reading: Option<Reading>, now: SystemTime) -> Option<AlarmEventKind> {
+        let value = reading.map(|r| r.milli_value());
+        match (self.state, value) {
+            (AlarmState::Normal | AlarmState::Pending { .. }, None) => {
+                self.state = AlarmState::Normal;
+                None
+            }
+            (AlarmState::Normal, Some(value)) if self.condition.is_met(value) => {
+                self.state = AlarmState::Pending { since: now };
+                self.raise_if_lasted(now)
+            }
+            (AlarmState::Pending { .. }, Some(value)) if self.condition.is_met(value) => {
+                self.raise_if_lasted(now)
+            }
+            (AlarmState::Pending { .. }, Some(_)) => {
+                self.state = AlarmState::Normal;
+                None
+            }
+            (AlarmState::Raised { .. } | AlarmState::Acknowledged { .. }, Some(value))
+                if self.condition.is_cleared(value, self.hysteresis) =>
+            {
+                self.state = AlarmState::Normal;
+                Some(AlarmEventKind::Cleared)
+            }
+            _ => None,
+        }
+    }
+    /// Raises pending alarm if condition holds long enough
+    fn raise_if_lasted(&mut self, now: SystemTime) -> Option<AlarmEventKind> {
+        if let AlarmState::Pending { since } = self.state {
+            if now.duration_since(since).unwrap_or_default() >= self.debounce {
+                self.state = AlarmState::Raised { at: now };
+                return Some(AlarmEventKind::Raised);
+            }
+        }
+        None
+    }
+}
+
+/// Enum for alarm lifecycle events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlarmEventKind {
+    Raised,
+    Acknowledged,
+    Cleared,
+}
+
+impl Display for AlarmEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlarmEventKind::Raised => write!(f, "Raised"),
+            AlarmEventKind::Acknowledged => write!(f, "Acknowledged"),
+            AlarmEventKind::Cleared => write!(f, "Cleared"),
+        }
+    }
+}
+
+/// Alarm lifecycle event sent to notification sinks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlarmEvent {
+    pub alarm_name: String,
+    pub kind: AlarmEventKind,
+    pub device: DeviceInfo,
+    /// Reading which caused the event
+    pub reading: Option<Reading>,
+    pub time: SystemTime,
+}
+
+impl Display for AlarmEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}/{}",
+            self.kind, self.alarm_name, self.device.room_name, self.device.device_name
+        )?;
+        if let Some(reading) = self.reading {
+            write!(f, " {}", reading)?;
+        }
+        Ok(())
+    }
+}
+
+/// Trait for alarm event receivers
+pub trait NotificationSink: Debug {
+    /// Delivers alarm event
+    fn notify(&mut self, event: &AlarmEvent) -> io::Result<()>;
+}
+
+/// Sink printing events to stdout
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl NotificationSink for StdoutSink {
+    fn notify(&mut self, event: &AlarmEvent) -> io::Result<()> {
+        println!("{}", event);
+        Ok(())
+    }
+}
+
+/// Sink appending events to a log file
+#[derive(Debug)]
+pub struct LogFileSink {
+    path: PathBuf,
+}
+
+impl LogFileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl NotificationSink for LogFileSink {
+    fn notify(&mut self, event: &AlarmEvent) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", event)
+    }
+}
+
+/// Default timeout of webhook notification steps
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sink posting events as JSON to a local HTTP endpoint
+#[derive(Debug)]
+pub struct WebhookSink {
+    /// Endpoint address, `host:port`
+    address: String,
+    /// Request path
+    path: String,
+    /// Timeout of connecting, sending event and reading response
+    timeout: Duration,
+}
+
+impl WebhookSink {
+    pub fn new(address: &str, path: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            path: path.to_string(),
+            timeout: WEBHOOK_TIMEOUT,
+        }
+    }
+    /// Sets timeout of each step of notification, `WEBHOOK_TIMEOUT` by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Connects to the first reachable endpoint address within timeout
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut error = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Webhook address {} is not resolved", self.address),
+        );
+        for address in self.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    fn notify(&mut self, event: &AlarmEvent) -> io::Result<()> {
+        let body = serde_json::to_string(event)?;
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.address,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes())?;
+        let mut status_line = [0; 12];
+        stream.read_exact(&mut status_line)?;
+        let status_line = String::from_utf8_lossy(&status_line);
+        if !status_line.starts_with("HTTP/1.1 2") && !status_line.starts_with("HTTP/1.0 2") {
+            return Err(io::Error::other(format!(
+                "Webhook responded with {}",
+                status_line
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Alarm manager struct
+///
+/// Stores alarms with unique names and sinks notified about alarm events
+#[derive(Debug, Default)]
+pub struct AlarmManager {
+    /// Vector of alarms
+    alarms: Vec<Alarm>,
+    /// Vector of notification sinks
+    sinks: Vec<Box<dyn NotificationSink>>,
+    /// Errors of the last notifications
+    sink_errors: Vec<io::Error>,
+}
+
+impl AlarmManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds new alarm
+    ///
+    /// Returns `Ok(())` if alarm name is unique, `Err` otherwise
+    pub fn add_alarm(&mut self, alarm: Alarm) -> Result<(), AlarmErrors> {
+        if self.alarms.iter().any(|a| a.name == alarm.name) {
+            return Err(AlarmErrors::AlarmNameExists(alarm.name));
+        }
+        self.alarms.push(alarm);
+        Ok(())
+    }
+    /// Removes alarm
+    ///
+    /// Returns `Ok(())` if `alarm_name` is found, `Err` otherwise
+    pub fn remove_alarm(&mut self, alarm_name: &str) -> Result<(), AlarmErrors> {
+        if self.alarms.iter().any(|a| a.name == alarm_name) {
+            self.alarms.retain(|a| a.name != alarm_name);
+            return Ok(());
+        }
+        Err(AlarmErrors::AlarmNameDoesNotExist(alarm_name.to_string()))
+    }
+    /// Adds notification sink
+    pub fn add_sink(&mut self, sink: Box<dyn NotificationSink>) {
+        self.sinks.push(sink);
+    }
+    /// Returns alarm by name
+    pub fn get_alarm(&self, alarm_name: &str) -> Option<&Alarm> {
+        self.alarms.iter().find(|a| a.name == alarm_name)
+    }
+    /// Returns raised and acknowledged alarms
+    pub fn active_alarms(&self) -> Vec<&Alarm> {
+        self.alarms.iter().filter(|a| a.is_active()).collect()
+    }
+    /// Returns errors of sinks which failed to deliver events during the last call
+    pub fn sink_errors(&self) -> &[io::Error] {
+        &self.sink_errors
+    }
+    /// Checks current home readings
+    ///
+    /// Returns events raised or cleared by this check
+    pub fn evaluate(&mut self, home: &Home) -> Vec<AlarmEvent> {
+        self.evaluate_at(home, SystemTime::now())
+    }
+    /// Checks home readings as if it is `now`
+    ///
+    /// Returns events raised or cleared by this check
+    pub fn evaluate_at(&mut self, home: &Home, now: SystemTime) -> Vec<AlarmEvent> {
+        let snapshot = home.snapshot();
+        let readings: Vec<(DeviceInfo, Option<Reading>)> = self
+            .alarms
+            .iter()
+            .map(|a| {
+                let reading = snapshot.device(&a.device).and_then(|d| d.reading);
+                (a.device.clone(), reading)
+            })
+            .collect();
+        let mut events = vec![];
+        for (alarm, (device, reading)) in self.alarms.iter_mut().zip(readings) {
+            if let Some(kind) = alarm.update(reading, now) {
+                events.push(AlarmEvent {
+                    alarm_name: alarm.name.clone(),
+                    kind,
+                    device,
+                    reading,
+                    time: now,
+                });
+            }
+        }
+        self.notify(&events);
+        events
+    }
+    /// Checks single device reading as if it is `now`
+    ///
+    /// Returns events raised or cleared by this reading
+    pub fn evaluate_reading(
+        &mut self,
+        device: &DeviceInfo,
+        reading: Option<Reading>,
+        now: SystemTime,
+    ) -> Vec<AlarmEvent> {
+        let events: Vec<AlarmEvent> = self
+            .alarms
+            .iter_mut()
+            .filter(|a| a.device == *device)
+            .filter_map(|a| {
+                a.update(reading, now).map(|kind| AlarmEvent {
+                    alarm_name: a.name.clone(),
+                    kind,
+                    device: device.clone(),
+                    reading,
+                    time: now,
+                })
+            })
+            .collect();
+        self.notify(&events);
+        events
+    }
+    /// Marks raised alarm as seen
+    ///
+    /// Returns `Ok(AlarmEvent)` if alarm is raised, `Err` otherwise
+    pub fn acknowledge(&mut self, alarm_name: &str) -> Result<AlarmEvent, AlarmErrors> {
+        let alarm = self.alarms.iter_mut().find(|a| a.name == alarm_name);
+        if alarm.is_none() {
+            return Err(AlarmErrors::AlarmNameDoesNotExist(alarm_name.to_string()));
+        }
+        let alarm = alarm.unwrap();
+        if !matches!(alarm.state, AlarmState::Raised { .. }) {
+            return Err(AlarmErrors::AlarmIsNotRaised(alarm_name.to_string()));
+        }
+        let now = SystemTime::now();
+        alarm.state = AlarmState::Acknowledged { at: now };
+        let event = AlarmEvent {
+            alarm_name: alarm.name.clone(),
+            kind: AlarmEventKind::Acknowledged,
+            device: alarm.device.clone(),
+            reading: None,
+            time: now,
+        };
+        self.notify(std::slice::from_ref(&event));
+        Ok(event)
+    }
+    /// Sends events to every sink and remembers failures
+    fn notify(&mut self, events: &[AlarmEvent]) {
+        self.sink_errors.clear();
+        for event in events {
+            for sink in self.sinks.iter_mut() {
+                if let Err(e) = sink.notify(event) {
+                    self.sink_errors.push(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temperature::Temperature;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn debounce() {
+        let mut alarm =
+            Alarm::above("hot", DeviceInfo::new("t", "r"), 30).lasting(Duration::from_secs(120));
+        let hot = Some(Reading::Temperature(31.into()));
+        assert_eq!(alarm.update(hot, at(0)), None);
+        assert_eq!(alarm.update(hot, at(60)), None);
+        assert_eq!(
+            alarm.update(Some(Reading::Temperature(25.into())), at(90)),
+            None
+        );
+        assert_eq!(alarm.update(hot, at(100)), None);
+        assert_eq!(alarm.update(hot, at(220)), Some(AlarmEventKind::Raised));
+        assert!(alarm.is_active());
+    }
+    #[test]
+    fn hysteresis() {
+        let mut alarm = Alarm::above("power", DeviceInfo::new("s", "r"), 90).with_hysteresis(5);
+        assert_eq!(
+            alarm.update(Some(Reading::Power(91)), at(0)),
+            Some(AlarmEventKind::Raised)
+        );
+        assert_eq!(alarm.update(Some(Reading::Power(88)), at(1)), None);
+        assert_eq!(alarm.update(None, at(2)), None);
+        assert_eq!(
+            alarm.update(Some(Reading::Power(85)), at(3)),
+            Some(AlarmEventKind::Cleared)
+        );
+        assert_eq!(alarm.state(), AlarmState::Normal);
+    }
+    #[test]
+    fn below_condition() {
+        let mut alarm = Alarm::below("cold", DeviceInfo::new("t", "r"), 5).with_hysteresis(2);
+        assert_eq!(
+            alarm.update(Some(Reading::Temperature(4.into())), at(0)),
+            Some(AlarmEventKind::Raised)
+        );
+        assert_eq!(
+            alarm.update(Some(Reading::Temperature(6.into())), at(1)),
+            None
+        );
+        assert_eq!(
+            alarm.update(Some(Reading::Temperature(7.into())), at(2)),
+            Some(AlarmEventKind::Cleared)
+        );
+    }
+    #[test]
+    fn fractional_temperature() {
+        let celsius = |t| Some(Reading::Temperature(Temperature::from_celsius(t)));
+        let mut alarm = Alarm::above("hot", DeviceInfo::new("t", "r"), 30).with_hysteresis(1);
+        assert_eq!(alarm.update(celsius(30.0), at(0)), None);
+        assert_eq!(
+            alarm.update(celsius(30.4), at(1)),
+            Some(AlarmEventKind::Raised)
+        );
+        assert_eq!(alarm.update(celsius(29.2), at(2)), None);
+        assert_eq!(
+            alarm.update(celsius(28.9), at(3)),
+            Some(AlarmEventKind::Cleared)
+        );
+    }
+}
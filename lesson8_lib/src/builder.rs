@@ -0,0 +1,107 @@
+use crate::{
+    device::Device,
+    errors::{home_errors::HomeErrors, room_errors::RoomErrors},
+    home::Home,
+};
+
+/// Fluent builder of a populated home
+///
+/// Devices are placed into the room declared last.
+/// Names are validated by `build`, which reports every error at once
+#[derive(Debug)]
+pub struct HomeBuilder<'a> {
+    /// Home name
+    name: String,
+    /// Rooms with their devices in declaration order
+    rooms: Vec<(String, Vec<&'a mut dyn Device>)>,
+    /// Devices declared before any room
+    orphans: Vec<String>,
+}
+
+impl<'a> HomeBuilder<'a> {
+    /// Returns builder of home without rooms
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            rooms: vec![],
+            orphans: vec![],
+        }
+    }
+    /// Declares new room, following devices are placed into it
+    pub fn room(mut self, room_name: &str) -> Self {
+        self.rooms.push((room_name.to_string(), vec![]));
+        self
+    }
+    /// Places device into the room declared last
+    pub fn device(mut self, device: &'a mut dyn Device) -> Self {
+        match self.rooms.last_mut() {
+            Some((_, devices)) => devices.push(device),
+            None => self.orphans.push(device.name().to_string()),
+        }
+        self
+    }
+    /// Returns errors making the home impossible to build
+    pub fn validate(&self) -> Vec<HomeErrors> {
+        let mut errors: Vec<HomeErrors> = self
+            .orphans
+            .iter()
+            .map(|name| HomeErrors::DeviceWithoutRoom(name.clone()))
+            .collect();
+        for (index, (room_name, devices)) in self.rooms.iter().enumerate() {
+            if self.rooms[..index].iter().any(|(r, _)| r == room_name) {
+                errors.push(HomeErrors::RoomNameExists(room_name.clone()));
+            }
+            for (index, device) in devices.iter().enumerate() {
+                if devices[..index].iter().any(|d| d.name() == device.name()) {
+                    errors.push(RoomErrors::DeviceNameExists(device.name().to_string()).into());
+                }
+            }
+        }
+        errors
+    }
+    /// Returns populated home
+    ///
+    /// Returns `Ok(Home)` if room names are unique in home, device names are unique
+    /// in room and every device is placed into a room, `Err` with all errors otherwise
+    pub fn build(self) -> Result<Home<'a>, Vec<HomeErrors>> {
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut home = Home::new(&self.name);
+        for (room_name, devices) in self.rooms {
+            home.add_room(&room_name).map_err(|e| vec![e])?;
+            for device in devices {
+                home.add_device(&room_name, device).map_err(|e| vec![e])?;
+            }
+        }
+        Ok(home)
+    }
+}
+
+/// Builds home from nested literal of rooms and devices
+///
+/// Expands to `HomeBuilder` calls and returns `Result<Home, Vec<HomeErrors>>`
+///
+/// ```
+/// use lesson8_lib::{home, Socket, Thermometer};
+///
+/// let mut socket = Socket::new("socket");
+/// let mut thermo = Thermometer::new("thermo");
+/// let home = home! {
+///     "home" => {
+///         "kitchen" => [&mut socket, &mut thermo],
+///         "garage" => [],
+///     }
+/// }
+/// .unwrap();
+/// assert_eq!(home.get_room_names(), vec!["kitchen", "garage"]);
+/// ```
+#[macro_export]
+macro_rules! home {
+    ($name:expr => { $($room:expr => [$($device:expr),* $(,)?]),* $(,)? }) => {
+        $crate::HomeBuilder::new($name)
+            $(.room($room) $(.device($device))*)*
+            .build()
+    };
+}
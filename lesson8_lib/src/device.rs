@@ -1,5 +1,9 @@
 use std::fmt::{Debug, Display};
 
+use serde::{Deserialize, Serialize};
+
+use crate::temperature::{Calibration, Temperature, TemperatureDisplay};
+
 /// Trait for house devices
 pub trait Device: Display + Debug {
     /// Change device status to DeviceState::On
@@ -14,10 +18,63 @@ pub trait Device: Display + Debug {
     fn get_report(&self) -> String {
         self.to_string()
     }
+    /// Returns device report with temperatures shown by `display`
+    fn get_report_in(&self, _display: &TemperatureDisplay) -> String {
+        self.get_report()
+    }
+    /// Returns current device reading.
+    /// `None` if device measures nothing or is turned off
+    fn reading(&self) -> Option<Reading> {
+        None
+    }
+    /// Takes reading and applies device protection.
+    /// Device exceeding its limit is turned off and tripped, so `None` is returned
+    fn measure(&mut self) -> Option<Reading> {
+        self.reading()
+    }
+    /// Returns reading which tripped device protection.
+    /// `None` if device is not tripped
+    fn tripped(&self) -> Option<Reading> {
+        None
+    }
+    /// Allows tripped device to be turned on again, device stays off.
+    /// Returns `true` if device was tripped
+    fn reset(&mut self) -> bool {
+        false
+    }
+    /// Trips device protection by `reading` as if it was measured, device is turned off.
+    /// Returns `false` if device has no protection tripped by such reading
+    fn trip(&mut self, _reading: Reading) -> bool {
+        false
+    }
+    /// Returns load shedding priority, devices with lower priority are turned off first
+    fn priority(&self) -> u8 {
+        0
+    }
+    /// Returns device metadata.
+    /// `None` if device does not store metadata
+    fn metadata(&self) -> Option<&DeviceMetadata> {
+        None
+    }
+    /// Returns mutable device metadata.
+    /// `None` if device does not store metadata
+    fn metadata_mut(&mut self) -> Option<&mut DeviceMetadata> {
+        None
+    }
+    /// Returns correction applied to raw readings.
+    /// `None` if device is not calibrated
+    fn calibration(&self) -> Option<&Calibration> {
+        None
+    }
+    /// Returns mutable correction applied to raw readings.
+    /// `None` if device is not calibrated
+    fn calibration_mut(&mut self) -> Option<&mut Calibration> {
+        None
+    }
 }
 
 /// Enum for device state
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeviceState {
     On,
     #[default]
@@ -33,7 +90,53 @@ impl Display for DeviceState {
     }
 }
 
+/// Enum for values measured by devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reading {
+    /// Socket power
+    Power(u32),
+    /// Calibrated thermometer temperature
+    Temperature(Temperature),
+}
+
+impl Reading {
+    /// Returns measured value without unit, temperature in whole degrees Celsius
+    pub fn value(&self) -> i64 {
+        match self {
+            Reading::Power(power) => *power as i64,
+            Reading::Temperature(temperature) => temperature.celsius().round() as i64,
+        }
+    }
+    /// Returns measured value in thousandths of unit, exact for temperature
+    pub fn milli_value(&self) -> i64 {
+        match self {
+            Reading::Power(power) => *power as i64 * 1000,
+            Reading::Temperature(temperature) => temperature.millicelsius() as i64,
+        }
+    }
+    /// Returns `value() - other.value()` if both readings are of the same kind
+    pub fn delta(&self, other: &Reading) -> Option<i64> {
+        match (self, other) {
+            (Reading::Power(_), Reading::Power(_))
+            | (Reading::Temperature(_), Reading::Temperature(_)) => {
+                Some(self.value() - other.value())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Reading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reading::Power(power) => write!(f, "power {}", power),
+            Reading::Temperature(temperature) => write!(f, "temperature {}", temperature),
+        }
+    }
+}
+
 /// Generic struct to describe specific device in house
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub device_name: String,
     pub room_name: String,
@@ -46,3 +149,82 @@ impl DeviceInfo {
         }
     }
 }
+
+/// Descriptive device information not affecting its behaviour
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceMetadata {
+    /// Free-form labels, e.g. `critical`
+    pub tags: Vec<String>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    /// Installation notes
+    pub notes: Option<String>,
+    /// Installation date in `YYYY-MM-DD` format
+    pub installed_on: Option<String>,
+}
+
+impl DeviceMetadata {
+    /// Returns empty metadata
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds tag if device does not have it yet
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+        self
+    }
+    pub fn with_vendor(mut self, vendor: &str) -> Self {
+        self.vendor = Some(vendor.to_string());
+        self
+    }
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+    pub fn with_serial_number(mut self, serial_number: &str) -> Self {
+        self.serial_number = Some(serial_number.to_string());
+        self
+    }
+    pub fn with_notes(mut self, notes: &str) -> Self {
+        self.notes = Some(notes.to_string());
+        self
+    }
+    pub fn installed_on(mut self, date: &str) -> Self {
+        self.installed_on = Some(date.to_string());
+        self
+    }
+    /// Returns `true` if device is tagged with `tag`
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+    /// Returns `true` if no field is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Writes a `field: value` line for every set field
+impl Display for DeviceMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.tags.is_empty() {
+            writeln!(f, "tags: {}", self.tags.join(", "))?;
+        }
+        let fields = [
+            ("vendor", &self.vendor),
+            ("model", &self.model),
+            ("serial number", &self.serial_number),
+            ("installed on", &self.installed_on),
+            ("notes", &self.notes),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                writeln!(f, "{}: {}", field, value)?;
+            }
+        }
+        Ok(())
+    }
+}
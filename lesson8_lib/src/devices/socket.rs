@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use rand::Rng;
 
-use crate::device::{Device, DeviceState};
+use crate::device::{Device, DeviceMetadata, DeviceState, Reading};
 
 /// Example socket
 #[derive(Debug)]
@@ -11,6 +11,14 @@ pub struct Socket {
     name: String,
     /// Device state
     state: DeviceState,
+    /// Tags, vendor and installation details
+    metadata: DeviceMetadata,
+    /// Power in watts tripping the socket off
+    power_limit: Option<u32>,
+    /// Power which tripped the socket, it stays off until reset
+    tripped: Option<u32>,
+    /// Load shedding priority
+    priority: u8,
 }
 
 impl Device for Socket {
@@ -23,12 +31,50 @@ impl Device for Socket {
     fn turn_off(&mut self) {
         self.state = DeviceState::Off;
     }
+    /// Tripped socket stays off until reset
     fn turn_on(&mut self) {
-        self.state = DeviceState::On;
+        if self.tripped.is_none() {
+            self.state = DeviceState::On;
+        }
     }
     fn get_report(&self) -> String {
         self.to_string()
     }
+    fn metadata(&self) -> Option<&DeviceMetadata> {
+        Some(&self.metadata)
+    }
+    fn metadata_mut(&mut self) -> Option<&mut DeviceMetadata> {
+        Some(&mut self.metadata)
+    }
+    fn reading(&self) -> Option<Reading> {
+        self.measure_power().map(Reading::Power)
+    }
+    fn measure(&mut self) -> Option<Reading> {
+        let power = self.measure_power()?;
+        if self.power_limit.is_some_and(|limit| power > limit) {
+            self.tripped = Some(power);
+            self.state = DeviceState::Off;
+            return None;
+        }
+        Some(Reading::Power(power))
+    }
+    fn tripped(&self) -> Option<Reading> {
+        self.tripped.map(Reading::Power)
+    }
+    fn reset(&mut self) -> bool {
+        self.tripped.take().is_some()
+    }
+    fn trip(&mut self, reading: Reading) -> bool {
+        let Reading::Power(power) = reading else {
+            return false;
+        };
+        self.tripped = Some(power);
+        self.state = DeviceState::Off;
+        true
+    }
+    fn priority(&self) -> u8 {
+        self.priority
+    }
 }
 
 impl Socket {
@@ -36,8 +82,38 @@ impl Socket {
         Self {
             name: name.to_string(),
             state: DeviceState::default(),
+            metadata: DeviceMetadata::default(),
+            power_limit: None,
+            tripped: None,
+            priority: 0,
         }
     }
+    /// Returns device with metadata
+    pub fn with_metadata(mut self, metadata: DeviceMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+    /// Returns socket tripping off when power exceeds `power_limit` watts
+    pub fn with_power_limit(mut self, power_limit: u32) -> Self {
+        self.power_limit = Some(power_limit);
+        self
+    }
+    /// Returns socket with load shedding priority, sockets with lower priority are turned off first
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn power_limit(&self) -> Option<u32> {
+        self.power_limit
+    }
+    /// Changes power limit, `None` disables protection
+    pub fn set_power_limit(&mut self, power_limit: Option<u32>) {
+        self.power_limit = power_limit;
+    }
+    /// Returns `true` if socket is latched off until reset
+    pub fn is_tripped(&self) -> bool {
+        self.tripped.is_some()
+    }
     /// Dummy function for measuring power
     fn measure_power(&self) -> Option<u32> {
         match self.state {
@@ -54,7 +130,14 @@ impl Display for Socket {
             "Socket name: {}\nstate: {}\ncurrent power: {}\n",
             self.name,
             self.state,
-            self.measure_power().unwrap_or_default()
-        )
+            self.measure_power().unwrap_or_default(),
+        )?;
+        if let Some(power_limit) = self.power_limit {
+            writeln!(f, "power limit: {}", power_limit)?;
+        }
+        if let Some(power) = self.tripped {
+            writeln!(f, "tripped at power: {}", power)?;
+        }
+        write!(f, "{}", self.metadata)
     }
 }
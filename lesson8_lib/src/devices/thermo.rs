@@ -2,7 +2,10 @@ use std::fmt::Display;
 
 use rand::Rng;
 
-use crate::device::{Device, DeviceState};
+use crate::{
+    device::{Device, DeviceMetadata, DeviceState, Reading},
+    temperature::{Calibration, Temperature, TemperatureDisplay},
+};
 
 /// Exampte thermometer
 #[derive(Debug)]
@@ -11,6 +14,10 @@ pub struct Thermometer {
     name: String,
     /// Device state
     state: DeviceState,
+    /// Tags, vendor and installation details
+    metadata: DeviceMetadata,
+    /// Correction of measured temperature
+    calibration: Calibration,
 }
 
 impl Device for Thermometer {
@@ -29,6 +36,24 @@ impl Device for Thermometer {
     fn get_report(&self) -> String {
         self.to_string()
     }
+    fn get_report_in(&self, display: &TemperatureDisplay) -> String {
+        self.report(display)
+    }
+    fn metadata(&self) -> Option<&DeviceMetadata> {
+        Some(&self.metadata)
+    }
+    fn metadata_mut(&mut self) -> Option<&mut DeviceMetadata> {
+        Some(&mut self.metadata)
+    }
+    fn calibration(&self) -> Option<&Calibration> {
+        Some(&self.calibration)
+    }
+    fn calibration_mut(&mut self) -> Option<&mut Calibration> {
+        Some(&mut self.calibration)
+    }
+    fn reading(&self) -> Option<Reading> {
+        self.temperature().map(Reading::Temperature)
+    }
 }
 
 impl Thermometer {
@@ -36,25 +61,54 @@ impl Thermometer {
         Self {
             name: name.to_string(),
             state: DeviceState::default(),
+            metadata: DeviceMetadata::default(),
+            calibration: Calibration::default(),
         }
     }
+    /// Returns device with metadata
+    pub fn with_metadata(mut self, metadata: DeviceMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+    /// Returns device with calibration
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+    /// Returns calibrated temperature, `None` if device is turned off
+    pub fn temperature(&self) -> Option<Temperature> {
+        self.measure_temperature()
+            .map(|raw| self.calibration.apply(raw))
+    }
     /// Dummy function for measuring temperature
-    fn measure_temperature(&self) -> Option<i32> {
+    fn measure_temperature(&self) -> Option<Temperature> {
         match self.state {
-            DeviceState::On => Some(rand::thread_rng().gen_range(-30..40)),
+            DeviceState::On => Some(Temperature::from_millicelsius(
+                rand::thread_rng().gen_range(-30_000..40_000),
+            )),
             DeviceState::Off => None,
         }
     }
+    fn report(&self, display: &TemperatureDisplay) -> String {
+        let calibration = match self.calibration.is_identity() {
+            true => String::new(),
+            false => format!("calibration: {}\n", self.calibration),
+        };
+        format!(
+            "Socket name: {}\nstate: {}\ncurrent temperature: {}\n{}{}",
+            self.name,
+            self.state,
+            self.temperature()
+                .map(|t| display.format(t))
+                .unwrap_or("none".to_string()),
+            calibration,
+            self.metadata
+        )
+    }
 }
 
 impl Display for Thermometer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Socket name: {}\nstate: {}\ncurrent temperature: {}\n",
-            self.name,
-            self.state,
-            self.measure_temperature().unwrap_or_default()
-        )
+        write!(f, "{}", self.report(&TemperatureDisplay::default()))
     }
 }
@@ -1,13 +1,28 @@
 pub mod home_errors {
     use std::{error::Error, fmt::Display};
 
-    use super::room_errors::{self, RoomErrors};
+    use super::{
+        access_errors::AccessErrors,
+        journal_errors::JournalErrors,
+        room_errors::{self, RoomErrors},
+    };
+    use crate::access::DeniedAttempt;
 
     #[derive(Debug)]
     pub enum HomeErrors {
         RoomNameExists(String),
         RoomNameDoesNotExist(String),
         InternalError(room_errors::RoomErrors),
+        /// Some operations of a transaction failed validation.
+        /// Holds results of every operation in the queued order
+        TransactionRejected(Vec<Result<(), HomeErrors>>),
+        NothingToUndo,
+        PermissionDenied(DeniedAttempt),
+        AccessError(AccessErrors),
+        /// Home builder got a device before any room
+        DeviceWithoutRoom(String),
+        /// Mutation was not applied because it could not be journaled
+        JournalError(JournalErrors),
     }
 
     impl From<RoomErrors> for HomeErrors {
@@ -16,6 +31,18 @@ pub mod home_errors {
         }
     }
 
+    impl From<AccessErrors> for HomeErrors {
+        fn from(value: AccessErrors) -> Self {
+            HomeErrors::AccessError(value)
+        }
+    }
+
+    impl From<JournalErrors> for HomeErrors {
+        fn from(value: JournalErrors) -> Self {
+            HomeErrors::JournalError(value)
+        }
+    }
+
     impl Display for HomeErrors {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
@@ -27,6 +54,18 @@ pub mod home_errors {
                         format!("Room with name {} already exists!", name),
                     HomeErrors::RoomNameDoesNotExist(name) =>
                         format!("Room with name {} does not exist!", name),
+                    HomeErrors::TransactionRejected(results) => format!(
+                        "Transaction rejected: {} of {} operations failed!",
+                        results.iter().filter(|r| r.is_err()).count(),
+                        results.len()
+                    ),
+                    HomeErrors::NothingToUndo => "Nothing to undo!".to_string(),
+                    HomeErrors::PermissionDenied(attempt) =>
+                        format!("Permission denied: {}!", attempt),
+                    HomeErrors::AccessError(value) => value.to_string(),
+                    HomeErrors::DeviceWithoutRoom(name) =>
+                        format!("Device with name {} is not placed in any room!", name),
+                    HomeErrors::JournalError(value) => value.to_string(),
                 }
             )
         }
@@ -35,13 +74,20 @@ pub mod home_errors {
     impl Error for HomeErrors {}
 }
 
-pub(crate) mod room_errors {
+pub mod room_errors {
     use std::{error::Error, fmt::Display};
 
+    /// Errors of devices in a room, wrapped by `HomeErrors::InternalError`
     #[derive(Debug)]
     pub enum RoomErrors {
         DeviceNameExists(String),
         DeviceNameDoesNotExist(String),
+        MetadataNotSupported(String),
+        CalibrationNotSupported(String),
+        /// Device protection keeps the device off until reset
+        DeviceTripped(String),
+        /// Device has no protection tripped by reading
+        ProtectionNotSupported(String),
     }
 
     impl Display for RoomErrors {
@@ -53,6 +99,14 @@ pub(crate) mod room_errors {
                     RoomErrors::DeviceNameExists(name) => format!("{} {}", name, "already exists!"),
                     RoomErrors::DeviceNameDoesNotExist(name) =>
                         format!("{} {}", name, "does not exist"),
+                    RoomErrors::MetadataNotSupported(name) =>
+                        format!("{} {}", name, "does not store metadata!"),
+                    RoomErrors::CalibrationNotSupported(name) =>
+                        format!("{} {}", name, "can not be calibrated!"),
+                    RoomErrors::DeviceTripped(name) =>
+                        format!("{} {}", name, "is tripped and has to be reset!"),
+                    RoomErrors::ProtectionNotSupported(name) =>
+                        format!("{} {}", name, "can not be tripped by the reading!"),
                 }
             )
         }
@@ -60,3 +114,124 @@ pub(crate) mod room_errors {
 
     impl Error for RoomErrors {}
 }
+
+pub mod access_errors {
+    use std::{error::Error, fmt::Display};
+
+    #[derive(Debug)]
+    pub enum AccessErrors {
+        UserNameExists(String),
+        UserNameDoesNotExist(String),
+        RoleNameExists(String),
+        RoleNameDoesNotExist(String),
+    }
+
+    impl Display for AccessErrors {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "{}",
+                match self {
+                    AccessErrors::UserNameExists(name) =>
+                        format!("User with name {} already exists!", name),
+                    AccessErrors::UserNameDoesNotExist(name) =>
+                        format!("User with name {} does not exist!", name),
+                    AccessErrors::RoleNameExists(name) =>
+                        format!("Role with name {} already exists!", name),
+                    AccessErrors::RoleNameDoesNotExist(name) =>
+                        format!("Role with name {} does not exist!", name),
+                }
+            )
+        }
+    }
+
+    impl Error for AccessErrors {}
+}
+
+pub mod alarm_errors {
+    use std::{error::Error, fmt::Display};
+
+    #[derive(Debug)]
+    pub enum AlarmErrors {
+        AlarmNameExists(String),
+        AlarmNameDoesNotExist(String),
+        AlarmIsNotRaised(String),
+    }
+
+    impl Display for AlarmErrors {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "Alarm with name {}",
+                match self {
+                    AlarmErrors::AlarmNameExists(name) => format!("{} already exists!", name),
+                    AlarmErrors::AlarmNameDoesNotExist(name) => format!("{} does not exist!", name),
+                    AlarmErrors::AlarmIsNotRaised(name) => format!("{} is not raised!", name),
+                }
+            )
+        }
+    }
+
+    impl Error for AlarmErrors {}
+}
+
+pub mod query_errors {
+    use std::{error::Error, fmt::Display};
+
+    #[derive(Debug)]
+    pub enum QueryErrors {
+        UnexpectedToken(String),
+        UnexpectedEnd,
+        UnknownField(String),
+        InvalidNumber(String),
+    }
+
+    impl Display for QueryErrors {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "{}",
+                match self {
+                    QueryErrors::UnexpectedToken(token) => format!("Unexpected token {}!", token),
+                    QueryErrors::UnexpectedEnd => "Unexpected end of query!".to_string(),
+                    QueryErrors::UnknownField(field) => format!("Unknown field {}!", field),
+                    QueryErrors::InvalidNumber(value) => format!("Invalid number {}!", value),
+                }
+            )
+        }
+    }
+
+    impl Error for QueryErrors {}
+}
+
+pub mod journal_errors {
+    use std::{error::Error, fmt::Display, io};
+
+    #[derive(Debug)]
+    pub enum JournalErrors {
+        Io(io::Error),
+        /// Snapshot or record which is not the last one can not be read
+        Corrupted(String),
+    }
+
+    impl From<io::Error> for JournalErrors {
+        fn from(value: io::Error) -> Self {
+            JournalErrors::Io(value)
+        }
+    }
+
+    impl Display for JournalErrors {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "{}",
+                match self {
+                    JournalErrors::Io(e) => format!("Journal I/O error: {}!", e),
+                    JournalErrors::Corrupted(place) => format!("Journal is corrupted: {}!", place),
+                }
+            )
+        }
+    }
+
+    impl Error for JournalErrors {}
+}
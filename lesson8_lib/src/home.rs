@@ -1,9 +1,18 @@
-use std::fmt::Display;
+use std::time::SystemTime;
 
 use crate::{
-    device::{Device, DeviceInfo},
-    errors::home_errors::HomeErrors,
-    room::Room,
+    access::{AccessControl, UserContext},
+    builder::HomeBuilder,
+    device::{Device, DeviceInfo, DeviceMetadata, DeviceState, Reading},
+    errors::{access_errors::AccessErrors, home_errors::HomeErrors, room_errors::RoomErrors},
+    journal::{Journal, JournalEntry},
+    power::{PowerEvent, PowerEventKind},
+    query::{Query, QueryMatch},
+    room::{Room, RoomView},
+    snapshot::{DeviceSnapshot, HomeSnapshot, RoomSnapshot},
+    temperature::{Calibration, TemperatureDisplay},
+    transaction::{Operation, OperationResult, Transaction, UndoOperation},
+    visitor::HomeVisitor,
 };
 
 /// Home struct
@@ -16,6 +25,18 @@ pub struct Home<'a> {
     name: String,
     /// vector of rooms
     rooms: Vec<Room<'a>>,
+    /// operations reverting the last committed transaction
+    last_transaction: Option<Vec<UndoOperation<'a>>>,
+    /// users, roles and audit of denied attempts
+    access_control: AccessControl,
+    /// journal receiving every mutation before it is applied
+    journal: Option<Journal>,
+    /// Unit and precision of temperatures in reports
+    temperature_display: TemperatureDisplay,
+    /// Limit of total power in watts, load over it is shed
+    power_budget: Option<u32>,
+    /// Log of tripped, shed and reset devices
+    power_events: Vec<PowerEvent>,
 }
 
 impl<'a> Home<'a> {
@@ -24,76 +45,233 @@ impl<'a> Home<'a> {
         Self {
             name: name.to_string(),
             rooms: vec![],
+            last_transaction: None,
+            access_control: AccessControl::new(),
+            journal: None,
+            temperature_display: TemperatureDisplay::default(),
+            power_budget: None,
+            power_events: vec![],
         }
     }
+    /// Returns home with rooms, devices, states, metadata, calibrations and trips from snapshot
+    ///
+    /// Devices are borrowed from `devices` by name, devices missing in snapshot are not used.
+    /// Returns `Ok(Home)` if every snapshot device is provided, `Err` with all errors otherwise
+    pub fn restore(
+        snapshot: &HomeSnapshot,
+        mut devices: Vec<&'a mut dyn Device>,
+    ) -> Result<Self, Vec<HomeErrors>> {
+        let mut errors = vec![];
+        let mut builder = HomeBuilder::new(&snapshot.name);
+        for room in snapshot.rooms.iter() {
+            builder = builder.room(&room.name);
+            for device_snapshot in room.devices.iter() {
+                let index = devices
+                    .iter()
+                    .position(|d| d.name() == device_snapshot.name);
+                let Some(index) = index else {
+                    errors.push(
+                        RoomErrors::DeviceNameDoesNotExist(device_snapshot.name.clone()).into(),
+                    );
+                    continue;
+                };
+                let device = devices.remove(index);
+                match device_snapshot.state {
+                    DeviceState::On => device.turn_on(),
+                    DeviceState::Off => device.turn_off(),
+                }
+                match device.metadata_mut() {
+                    Some(metadata) => *metadata = device_snapshot.metadata.clone(),
+                    None if !device_snapshot.metadata.is_empty() => errors.push(
+                        RoomErrors::MetadataNotSupported(device_snapshot.name.clone()).into(),
+                    ),
+                    None => (),
+                }
+                match (device.calibration_mut(), device_snapshot.calibration) {
+                    (Some(current), Some(calibration)) => *current = calibration,
+                    (None, Some(_)) => errors.push(
+                        RoomErrors::CalibrationNotSupported(device_snapshot.name.clone()).into(),
+                    ),
+                    _ => (),
+                }
+                match device_snapshot.tripped {
+                    Some(reading) if !device.trip(reading) => errors.push(
+                        RoomErrors::ProtectionNotSupported(device_snapshot.name.clone()).into(),
+                    ),
+                    Some(_) => (),
+                    None => {
+                        device.reset();
+                    }
+                }
+                builder = builder.device(device);
+            }
+        }
+        errors.extend(builder.validate());
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        builder.build()
+    }
+    /// Returns builder of populated home
+    pub fn builder(name: &str) -> HomeBuilder<'a> {
+        HomeBuilder::new(name)
+    }
+    /// Returns home name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Replaces users and roles of new home
+    ///
+    /// Home is changed and reported only by users, see `acting_as`
+    pub fn with_access_control(mut self, access_control: AccessControl) -> Self {
+        self.access_control = access_control;
+        self
+    }
+    /// Returns users, roles and audit of denied attempts
+    pub fn access_control(&self) -> &AccessControl {
+        &self.access_control
+    }
+    /// Returns mutable users, roles and audit of denied attempts
+    pub(crate) fn access_control_mut(&mut self) -> &mut AccessControl {
+        &mut self.access_control
+    }
+    /// Starts writing every following mutation to `journal` before applying it
+    ///
+    /// Returns previously attached journal
+    pub fn attach_journal(&mut self, journal: Journal) -> Option<Journal> {
+        self.journal.replace(journal)
+    }
+    /// Stops journaling and returns the journal
+    pub fn detach_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+    /// Returns attached journal
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+    /// Returns unit and precision of temperatures in reports
+    pub fn temperature_display(&self) -> &TemperatureDisplay {
+        &self.temperature_display
+    }
+    /// Sets unit and precision of temperatures in reports
+    pub(crate) fn set_temperature_display(&mut self, temperature_display: TemperatureDisplay) {
+        self.temperature_display = temperature_display;
+    }
+    /// Returns home accessed on behalf of a user
+    ///
+    /// This is the only way to change home and get its reports,
+    /// new home is administered by `OWNER`.
+    /// Returns `Ok(UserContext)` if `user_name` exists, `Err` otherwise
+    pub fn acting_as(&mut self, user_name: &str) -> Result<UserContext<'_, 'a>, HomeErrors> {
+        if !self.access_control.has_user(user_name) {
+            return Err(AccessErrors::UserNameDoesNotExist(user_name.to_string()).into());
+        }
+        Ok(UserContext::new(self, user_name))
+    }
     /// Adds new room
     ///
     /// Returns `Ok(())` if `room_name` is unique, `Err` otherwise
-    pub fn add_room(&mut self, room_name: &str) -> Result<(), HomeErrors> {
-        if self.rooms.iter().any(|r| r.name() == room_name) {
-            return Err(HomeErrors::RoomNameExists(room_name.to_string()));
-        }
-        let room = Room::new(room_name);
-        self.rooms.push(room);
+    pub(crate) fn add_room(&mut self, room_name: &str) -> Result<(), HomeErrors> {
+        self.apply_operation(Operation::AddRoom(room_name.to_string()))?;
+        self.last_transaction = None;
         Ok(())
     }
     /// Removes a room
     ///
     /// Returns `Ok(())` if `room_name` is found, `Err` otherwise
-    pub fn remove_room(&mut self, room_name: &str) -> Result<(), HomeErrors> {
-        if self.rooms.iter().any(|r| r.name() == room_name) {
-            self.rooms.retain(|el| el.name() != room_name);
-            return Ok(());
-        }
-        Err(HomeErrors::RoomNameDoesNotExist(room_name.to_string()))
+    pub(crate) fn remove_room(&mut self, room_name: &str) -> Result<(), HomeErrors> {
+        self.apply_operation(Operation::RemoveRoom(room_name.to_string()))?;
+        self.last_transaction = None;
+        Ok(())
     }
     /// Adds device
     ///
     /// Returns `Ok(())` if `room_name` is exists and `device.name` is unique,
     /// `Err` otherwise
-    pub fn add_device(
+    pub(crate) fn add_device(
         &mut self,
         room_name: &str,
         device: &'a mut dyn Device,
     ) -> Result<(), HomeErrors> {
-        let room = self.rooms.iter_mut().find(|r| r.name() == room_name);
-        if room.is_none() {
-            return Err(HomeErrors::RoomNameDoesNotExist(room_name.to_string()));
-        }
-        let room = room.unwrap();
-        room.add_device(device).map_err(|e| e.into())
+        self.apply_operation(Operation::AddDevice {
+            room_name: room_name.to_string(),
+            device,
+        })?;
+        self.last_transaction = None;
+        Ok(())
     }
     /// Removes device
     ///
     /// Returns `Ok(())` if `device_info.room_name` and `device_info.device_name` exist,
     /// `Err` otherwise
-    pub fn remove_device(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
-        let room = self
-            .rooms
-            .iter_mut()
-            .find(|r| r.name() == device_info.room_name);
-        if room.is_none() {
-            return Err(HomeErrors::RoomNameDoesNotExist(
-                device_info.room_name.to_string(),
-            ));
-        }
-        let room = room.unwrap();
-        room.remove_device(&device_info.device_name)
-            .map_err(|e| e.into())
+    pub(crate) fn remove_device(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
+        self.apply_operation(Operation::RemoveDevice(device_info.clone()))?;
+        self.last_transaction = None;
+        Ok(())
     }
     /// Returns vector with room names
     pub fn get_room_names(&self) -> Vec<&str> {
         self.rooms.iter().map(|r| r.name()).collect()
     }
+    /// Returns iterator over rooms in home order
+    pub fn rooms(&self) -> impl Iterator<Item = RoomView<'_, 'a>> {
+        self.rooms.iter().map(RoomView::new)
+    }
+    /// Returns iterator over room name and device pairs of all rooms
+    pub fn devices(&self) -> impl Iterator<Item = (&str, &dyn Device)> + use<'_, 'a> {
+        self.rooms
+            .iter()
+            .flat_map(|r| r.devices().map(move |d| (r.name(), d)))
+    }
+    /// Returns iterator over room name and mutable device pairs of all rooms
+    ///
+    /// Changes made directly to devices are not counted as state changes and not journaled
+    pub(crate) fn devices_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&str, &mut dyn Device)> + use<'_, 'a> {
+        self.rooms.iter_mut().flat_map(|r| r.devices_mut())
+    }
+    /// Walks all rooms and devices with visitor
+    pub fn accept(&self, visitor: &mut dyn HomeVisitor) {
+        for room in self.rooms() {
+            visitor.visit_room(room);
+            for device in room.devices() {
+                visitor.visit_device(room.name(), device);
+            }
+            visitor.leave_room(room);
+        }
+    }
+    /// Returns point-in-time copy of rooms, devices, states and readings
+    pub fn snapshot(&self) -> HomeSnapshot {
+        HomeSnapshot {
+            name: self.name.clone(),
+            rooms: self
+                .rooms
+                .iter()
+                .map(|r| RoomSnapshot {
+                    name: r.name().to_string(),
+                    devices: r.devices().map(DeviceSnapshot::new).collect(),
+                })
+                .collect(),
+        }
+    }
+    /// Returns devices matching query
+    pub fn query(&self, query: &Query) -> Vec<QueryMatch> {
+        query.run(&self.snapshot())
+    }
     /// Returns home report with all rooms and devices
-    pub fn get_home_report(&self) -> String {
-        self.to_string()
+    pub(crate) fn get_home_report(&self) -> String {
+        format!(
+            "Home name: {}\nrooms: [\n{}]",
+            self.name,
+            self.get_rooms_report()
+        )
     }
     /// Get device report
     ///
     /// Returns `Ok(String)` if `device_info.room_name` and `device_info.device_name` exist,
     /// `Err` otherwise
-    pub fn get_device_report(&self, device_info: &DeviceInfo) -> Result<String, HomeErrors> {
+    pub(crate) fn get_device_report(&self, device_info: &DeviceInfo) -> Result<String, HomeErrors> {
         let room = self
             .rooms
             .iter()
@@ -104,22 +282,210 @@ impl<'a> Home<'a> {
             ));
         }
         let room = room.unwrap();
-        room.get_device_report(&device_info.device_name)
+        room.get_device_report(&device_info.device_name, &self.temperature_display)
             .map_err(|e| e.into())
     }
-    /// Get devices reports
+    /// Returns number of times device was turned on or off by home
     ///
-    /// Returns vec with results.
-    /// `Ok(String)` if `device_info.room_name` and `device_info.device_name` exist,
+    /// Returns `Ok(u64)` if `device_info.room_name` and `device_info.device_name` exist,
     /// `Err` otherwise
-    pub fn get_devices_report(
+    pub fn get_state_changes(&self, device_info: &DeviceInfo) -> Result<u64, HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        self.rooms[index]
+            .get_state_changes(&device_info.device_name)
+            .map_err(|e| e.into())
+    }
+    /// Returns device metadata, empty if device does not store it
+    ///
+    /// Returns `Ok(DeviceMetadata)` if `device_info.room_name` and `device_info.device_name` exist,
+    /// `Err` otherwise
+    pub fn get_device_metadata(
         &self,
-        device_infos: Vec<&DeviceInfo>,
-    ) -> Vec<Result<String, HomeErrors>> {
-        device_infos
-            .iter()
-            .map(|di| self.get_device_report(di))
-            .collect()
+        device_info: &DeviceInfo,
+    ) -> Result<DeviceMetadata, HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        self.rooms[index]
+            .get_device_metadata(&device_info.device_name)
+            .map_err(|e| e.into())
+    }
+    /// Replaces device metadata
+    ///
+    /// Returns `Ok(())` if `device_info.room_name` and `device_info.device_name` exist
+    /// and the device stores metadata, `Err` otherwise
+    pub(crate) fn set_device_metadata(
+        &mut self,
+        device_info: &DeviceInfo,
+        metadata: DeviceMetadata,
+    ) -> Result<(), HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        let device = self.rooms[index]
+            .devices()
+            .find(|d| d.name() == device_info.device_name);
+        match device.map(|d| d.metadata().is_some()) {
+            None => {
+                return Err(
+                    RoomErrors::DeviceNameDoesNotExist(device_info.device_name.clone()).into(),
+                )
+            }
+            Some(false) => {
+                return Err(
+                    RoomErrors::MetadataNotSupported(device_info.device_name.clone()).into(),
+                )
+            }
+            Some(true) => (),
+        }
+        self.write_ahead(JournalEntry::SetMetadata {
+            device: device_info.clone(),
+            metadata: metadata.clone(),
+        })?;
+        self.rooms[index].set_device_metadata(&device_info.device_name, metadata)?;
+        self.last_transaction = None;
+        Ok(())
+    }
+    /// Returns device calibration, `None` if device is not calibrated
+    ///
+    /// Returns `Ok(Option<Calibration>)` if `device_info.room_name` and `device_info.device_name` exist,
+    /// `Err` otherwise
+    pub fn get_device_calibration(
+        &self,
+        device_info: &DeviceInfo,
+    ) -> Result<Option<Calibration>, HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        self.rooms[index]
+            .get_device_calibration(&device_info.device_name)
+            .map_err(|e| e.into())
+    }
+    /// Replaces correction applied to device readings
+    ///
+    /// Returns `Ok(())` if `device_info.room_name` and `device_info.device_name` exist
+    /// and the device is calibrated, `Err` otherwise
+    pub(crate) fn set_device_calibration(
+        &mut self,
+        device_info: &DeviceInfo,
+        calibration: Calibration,
+    ) -> Result<(), HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        if self.rooms[index]
+            .get_device_calibration(&device_info.device_name)?
+            .is_none()
+        {
+            return Err(
+                RoomErrors::CalibrationNotSupported(device_info.device_name.clone()).into(),
+            );
+        }
+        self.write_ahead(JournalEntry::SetCalibration {
+            device: device_info.clone(),
+            calibration,
+        })?;
+        self.rooms[index].set_device_calibration(&device_info.device_name, calibration)?;
+        self.last_transaction = None;
+        Ok(())
+    }
+    /// Returns limit of total power in watts
+    pub fn power_budget(&self) -> Option<u32> {
+        self.power_budget
+    }
+    /// Sets limit of total power in watts, `None` disables load shedding
+    pub(crate) fn set_power_budget(&mut self, power_budget: Option<u32>) {
+        self.power_budget = power_budget;
+    }
+    /// Returns log of tripped, shed and reset devices
+    pub fn power_events(&self) -> &[PowerEvent] {
+        &self.power_events
+    }
+    /// Measures devices, lets device protection trip and sheds load over power budget
+    ///
+    /// Returns events of this check
+    pub fn balance_power(&mut self) -> Result<Vec<PowerEvent>, HomeErrors> {
+        self.balance_power_at(SystemTime::now())
+    }
+    /// Balances power as if it is `now`
+    ///
+    /// Devices are turned off by ascending priority, the most consuming first among equal ones,
+    /// until total power is within budget.
+    /// Returns events of this check
+    pub fn balance_power_at(&mut self, now: SystemTime) -> Result<Vec<PowerEvent>, HomeErrors> {
+        let mut tripped = vec![];
+        let mut consumers = vec![];
+        for (room_name, device) in self.devices_mut() {
+            let device_info = DeviceInfo::new(device.name(), room_name);
+            let was_tripped = device.tripped().is_some();
+            match device.measure() {
+                Some(Reading::Power(power)) => {
+                    consumers.push((device_info, power, device.priority()))
+                }
+                _ if !was_tripped && device.tripped().is_some() => {
+                    tripped.push((device_info, device.tripped()))
+                }
+                _ => (),
+            }
+        }
+        let mut events = vec![];
+        for (device_info, reading) in tripped {
+            // device has already turned itself off
+            self.write_ahead(JournalEntry::SetTrip {
+                device: device_info.clone(),
+                tripped: reading,
+            })?;
+            events.push(PowerEvent {
+                kind: PowerEventKind::Tripped,
+                device: device_info,
+                reading,
+                time: now,
+            });
+        }
+        let mut total: u32 = consumers.iter().map(|(_, power, _)| power).sum();
+        if let Some(budget) = self.power_budget {
+            consumers.sort_by_key(|(_, power, priority)| (*priority, std::cmp::Reverse(*power)));
+            for (device_info, power, priority) in consumers {
+                if total <= budget {
+                    break;
+                }
+                self.apply_operation(Operation::TurnOff(device_info.clone()))?;
+                events.push(PowerEvent {
+                    kind: PowerEventKind::Shed {
+                        total,
+                        budget,
+                        priority,
+                    },
+                    device: device_info,
+                    reading: Some(Reading::Power(power)),
+                    time: now,
+                });
+                total -= power;
+            }
+        }
+        if !events.is_empty() {
+            self.last_transaction = None;
+        }
+        self.power_events.extend(events.iter().cloned());
+        Ok(events)
+    }
+    /// Allows tripped device to be turned on again, device stays off
+    ///
+    /// Returns `Ok(bool)` with `true` if device was tripped
+    /// and `device_info.room_name` and `device_info.device_name` exist, `Err` otherwise
+    pub(crate) fn reset_device(&mut self, device_info: &DeviceInfo) -> Result<bool, HomeErrors> {
+        let index = self.room_index(&device_info.room_name)?;
+        if self.rooms[index]
+            .get_device_trip(&device_info.device_name)?
+            .is_some()
+        {
+            self.write_ahead(JournalEntry::SetTrip {
+                device: device_info.clone(),
+                tripped: None,
+            })?;
+        }
+        let reset = self.rooms[index].reset_device(&device_info.device_name)?;
+        if reset {
+            self.power_events.push(PowerEvent {
+                kind: PowerEventKind::Reset,
+                device: device_info.clone(),
+                reading: None,
+                time: SystemTime::now(),
+            });
+        }
+        Ok(reset)
     }
     /// Returns `Ok(Vec<&str>)` if `room_name` exists, `Err` otherwise
     pub fn get_devices_in_room(&self, room_name: &str) -> Result<Vec<&str>, HomeErrors> {
@@ -133,50 +499,225 @@ impl<'a> Home<'a> {
     ///
     /// Returns `Ok(())` if `device_info.room_name` and `device_info.device_name` exist,
     /// `Err` otherwise
-    pub fn turn_on(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
-        let room = self
-            .rooms
-            .iter_mut()
-            .find(|r| r.name() == device_info.room_name);
-        if room.is_none() {
-            return Err(HomeErrors::RoomNameDoesNotExist(
-                device_info.room_name.to_string(),
-            ));
-        }
-        let room = room.unwrap();
-        room.turn_on(&device_info.device_name).map_err(|e| e.into())
+    pub(crate) fn turn_on(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
+        self.apply_operation(Operation::TurnOn(device_info.clone()))?;
+        self.last_transaction = None;
+        Ok(())
     }
     /// Turns off a device
     ///
     /// Returns `Ok(())` if `device_info.room_name` and `device_info.device_name` exist,
     /// `Err` otherwise
-    pub fn turn_off(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
-        let room = self
-            .rooms
-            .iter_mut()
-            .find(|r| r.name() == device_info.room_name);
-        if room.is_none() {
-            return Err(HomeErrors::RoomNameDoesNotExist(
-                device_info.room_name.to_string(),
-            ));
+    pub(crate) fn turn_off(&mut self, device_info: &DeviceInfo) -> Result<(), HomeErrors> {
+        self.apply_operation(Operation::TurnOff(device_info.clone()))?;
+        self.last_transaction = None;
+        Ok(())
+    }
+    /// Applies all transaction operations or none of them
+    ///
+    /// Returns `Ok(Vec<OperationResult>)` with result of every operation if all of them
+    /// are valid for current home state, `Err(HomeErrors::TransactionRejected)` with
+    /// the same results otherwise. Committed transaction can be reverted by `undo`
+    pub(crate) fn commit(
+        &mut self,
+        transaction: Transaction<'a>,
+    ) -> Result<Vec<OperationResult>, HomeErrors> {
+        let results = transaction.validate(self);
+        if results.iter().any(|r| r.is_err()) {
+            return Err(HomeErrors::TransactionRejected(results));
         }
-        let room = room.unwrap();
-        room.turn_off(&device_info.device_name)
-            .map_err(|e| e.into())
+        let mut undo_operations = vec![];
+        for operation in transaction.into_operations() {
+            match self.apply_operation(operation) {
+                Ok(undo_operation) => undo_operations.push(undo_operation),
+                Err(e) => {
+                    self.revert_operations(undo_operations)?;
+                    return Err(e);
+                }
+            }
+        }
+        self.last_transaction = Some(undo_operations);
+        Ok(results)
+    }
+    /// Reverts the last committed transaction
+    ///
+    /// Returns `Err(HomeErrors::NothingToUndo)` if there was no transaction
+    /// or home was changed after it
+    pub(crate) fn undo(&mut self) -> Result<(), HomeErrors> {
+        let undo_operations = self
+            .last_transaction
+            .take()
+            .ok_or(HomeErrors::NothingToUndo)?;
+        self.revert_operations(undo_operations)
+    }
+    /// Returns `true` if the last committed transaction can be reverted
+    pub fn can_undo(&self) -> bool {
+        self.last_transaction.is_some()
+    }
+    /// Applies single operation and returns the operation reverting it
+    fn apply_operation(
+        &mut self,
+        operation: Operation<'a>,
+    ) -> Result<UndoOperation<'a>, HomeErrors> {
+        Ok(match operation {
+            Operation::AddRoom(room_name) => {
+                if self.rooms.iter().any(|r| r.name() == room_name) {
+                    return Err(HomeErrors::RoomNameExists(room_name));
+                }
+                self.write_ahead(JournalEntry::AddRoom {
+                    room_name: room_name.clone(),
+                    index: self.rooms.len(),
+                })?;
+                self.rooms.push(Room::new(&room_name));
+                UndoOperation::RemoveRoom(room_name)
+            }
+            Operation::RemoveRoom(room_name) => {
+                let index = self.room_index(&room_name)?;
+                self.write_ahead(JournalEntry::RemoveRoom(room_name))?;
+                UndoOperation::RestoreRoom {
+                    index,
+                    room: self.rooms.remove(index),
+                }
+            }
+            Operation::AddDevice { room_name, device } => {
+                let device_info = DeviceInfo::new(device.name(), &room_name);
+                let index = self.room_index(&room_name)?;
+                let room = &self.rooms[index];
+                if room.get_devices().contains(&device.name()) {
+                    return Err(RoomErrors::DeviceNameExists(device_info.device_name).into());
+                }
+                let entry = JournalEntry::add_device(&room_name, room.get_devices().len(), device);
+                self.write_ahead(entry)?;
+                self.rooms[index].add_device(device)?;
+                UndoOperation::RemoveDevice(device_info)
+            }
+            Operation::RemoveDevice(device_info) => {
+                let index = self.room_index(&device_info.room_name)?;
+                self.rooms[index].get_device_state(&device_info.device_name)?;
+                self.write_ahead(JournalEntry::RemoveDevice(device_info.clone()))?;
+                let (index, device, state_changes) =
+                    self.rooms[index].take_device(&device_info.device_name)?;
+                UndoOperation::RestoreDevice {
+                    room_name: device_info.room_name,
+                    index,
+                    device,
+                    state_changes,
+                }
+            }
+            Operation::TurnOn(device_info) => {
+                let index = self.room_index(&device_info.room_name)?;
+                let room = &self.rooms[index];
+                let state = room.get_device_state(&device_info.device_name)?;
+                if room.get_device_trip(&device_info.device_name)?.is_some() {
+                    return Err(RoomErrors::DeviceTripped(device_info.device_name).into());
+                }
+                self.write_ahead(JournalEntry::SetState {
+                    device: device_info.clone(),
+                    state: DeviceState::On,
+                })?;
+                self.rooms[index].turn_on(&device_info.device_name)?;
+                UndoOperation::SetState { device_info, state }
+            }
+            Operation::TurnOff(device_info) => {
+                let index = self.room_index(&device_info.room_name)?;
+                let room = &self.rooms[index];
+                let state = room.get_device_state(&device_info.device_name)?;
+                self.write_ahead(JournalEntry::SetState {
+                    device: device_info.clone(),
+                    state: DeviceState::Off,
+                })?;
+                self.rooms[index].turn_off(&device_info.device_name)?;
+                UndoOperation::SetState { device_info, state }
+            }
+        })
+    }
+    /// Applies reverting operations in reverse order
+    fn revert_operations(
+        &mut self,
+        undo_operations: Vec<UndoOperation<'a>>,
+    ) -> Result<(), HomeErrors> {
+        for undo_operation in undo_operations.into_iter().rev() {
+            match undo_operation {
+                UndoOperation::RemoveRoom(room_name) => {
+                    let index = self.room_index(&room_name)?;
+                    self.write_ahead(JournalEntry::RemoveRoom(room_name))?;
+                    self.rooms.remove(index);
+                }
+                UndoOperation::RestoreRoom { index, room } => {
+                    if self.rooms.iter().any(|r| r.name() == room.name()) {
+                        return Err(HomeErrors::RoomNameExists(room.name().to_string()));
+                    }
+                    let index = index.min(self.rooms.len());
+                    self.write_ahead(JournalEntry::AddRoom {
+                        room_name: room.name().to_string(),
+                        index,
+                    })?;
+                    for (device_index, device) in room.devices().enumerate() {
+                        self.write_ahead(JournalEntry::add_device(
+                            room.name(),
+                            device_index,
+                            device,
+                        ))?;
+                    }
+                    self.rooms.insert(index, room);
+                }
+                UndoOperation::RemoveDevice(device_info) => {
+                    let index = self.room_index(&device_info.room_name)?;
+                    self.rooms[index].get_device_state(&device_info.device_name)?;
+                    self.write_ahead(JournalEntry::RemoveDevice(device_info.clone()))?;
+                    self.rooms[index].remove_device(&device_info.device_name)?;
+                }
+                UndoOperation::RestoreDevice {
+                    room_name,
+                    index,
+                    device,
+                    state_changes,
+                } => {
+                    let room_index = self.room_index(&room_name)?;
+                    let room = &self.rooms[room_index];
+                    if room.get_devices().contains(&device.name()) {
+                        return Err(RoomErrors::DeviceNameExists(device.name().to_string()).into());
+                    }
+                    let index = index.min(room.get_devices().len());
+                    self.write_ahead(JournalEntry::add_device(&room_name, index, device))?;
+                    self.rooms[room_index].insert_device(index, device, state_changes)?;
+                }
+                UndoOperation::SetState { device_info, state } => {
+                    let index = self.room_index(&device_info.room_name)?;
+                    self.rooms[index].get_device_state(&device_info.device_name)?;
+                    self.write_ahead(JournalEntry::SetState {
+                        device: device_info.clone(),
+                        state,
+                    })?;
+                    let room = &mut self.rooms[index];
+                    match state {
+                        DeviceState::On => room.turn_on(&device_info.device_name)?,
+                        DeviceState::Off => room.turn_off(&device_info.device_name)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Records mutation in attached journal before it is applied
+    fn write_ahead(&mut self, entry: JournalEntry) -> Result<(), HomeErrors> {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append(entry)?;
+        }
+        Ok(())
+    }
+    /// Returns index of room with `room_name`
+    fn room_index(&self, room_name: &str) -> Result<usize, HomeErrors> {
+        self.rooms
+            .iter()
+            .position(|r| r.name() == room_name)
+            .ok_or(HomeErrors::RoomNameDoesNotExist(room_name.to_string()))
     }
     /// Get reports from all rooms
     fn get_rooms_report(&self) -> String {
-        self.rooms.iter().map(|r| r.get_report()).collect()
-    }
-}
-
-impl<'a> Display for Home<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Home name: {}\nrooms: [\n{}]",
-            self.name,
-            self.get_rooms_report()
-        )
+        self.rooms
+            .iter()
+            .map(|r| r.get_report(&self.temperature_display))
+            .collect()
     }
 }
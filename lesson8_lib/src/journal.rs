@@ -0,0 +1,378 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    device::{Device, DeviceInfo, DeviceMetadata, DeviceState, Reading},
+    errors::journal_errors::JournalErrors,
+    snapshot::{DeviceSnapshot, HomeSnapshot, RoomSnapshot},
+    temperature::Calibration,
+};
+
+/// Name of append-only journal file in journal directory
+pub const JOURNAL_FILE: &str = "journal.log";
+/// Name of compacted snapshot file in journal directory
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Home mutation recorded in journal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEntry {
+    AddRoom {
+        room_name: String,
+        index: usize,
+    },
+    RemoveRoom(String),
+    /// Device with its state, metadata and calibration, reading is not recorded
+    AddDevice {
+        room_name: String,
+        index: usize,
+        device: DeviceSnapshot,
+    },
+    RemoveDevice(DeviceInfo),
+    SetState {
+        device: DeviceInfo,
+        state: DeviceState,
+    },
+    SetMetadata {
+        device: DeviceInfo,
+        metadata: DeviceMetadata,
+    },
+    SetCalibration {
+        device: DeviceInfo,
+        calibration: Calibration,
+    },
+    /// Device protection tripped by reading, which turns device off, or reset with `None`
+    SetTrip {
+        device: DeviceInfo,
+        tripped: Option<Reading>,
+    },
+}
+
+impl JournalEntry {
+    /// Returns entry adding `device` to room at `index`
+    pub fn add_device(room_name: &str, index: usize, device: &dyn Device) -> Self {
+        Self::AddDevice {
+            room_name: room_name.to_string(),
+            index,
+            device: DeviceSnapshot {
+                reading: None,
+                ..DeviceSnapshot::new(device)
+            },
+        }
+    }
+    /// Applies mutation to snapshot
+    ///
+    /// Entries referring to missing rooms or devices change nothing
+    pub fn apply(&self, snapshot: &mut HomeSnapshot) {
+        match self {
+            JournalEntry::AddRoom { room_name, index } => {
+                if snapshot.room(room_name).is_none() {
+                    let index = (*index).min(snapshot.rooms.len());
+                    snapshot.rooms.insert(
+                        index,
+                        RoomSnapshot {
+                            name: room_name.clone(),
+                            devices: vec![],
+                        },
+                    );
+                }
+            }
+            JournalEntry::RemoveRoom(room_name) => {
+                snapshot.rooms.retain(|r| r.name != *room_name);
+            }
+            JournalEntry::AddDevice {
+                room_name,
+                index,
+                device,
+            } => {
+                if let Some(room) = snapshot.rooms.iter_mut().find(|r| r.name == *room_name) {
+                    if !room.devices.iter().any(|d| d.name == device.name) {
+                        let index = (*index).min(room.devices.len());
+                        room.devices.insert(index, device.clone());
+                    }
+                }
+            }
+            JournalEntry::RemoveDevice(device_info) => {
+                if let Some(room) = snapshot
+                    .rooms
+                    .iter_mut()
+                    .find(|r| r.name == device_info.room_name)
+                {
+                    room.devices.retain(|d| d.name != device_info.device_name);
+                }
+            }
+            JournalEntry::SetState { device, state } => {
+                if let Some(device) = Self::device_mut(snapshot, device) {
+                    device.state = *state;
+                }
+            }
+            JournalEntry::SetMetadata { device, metadata } => {
+                if let Some(device) = Self::device_mut(snapshot, device) {
+                    device.metadata = metadata.clone();
+                }
+            }
+            JournalEntry::SetCalibration {
+                device,
+                calibration,
+            } => {
+                if let Some(device) = Self::device_mut(snapshot, device) {
+                    device.calibration = Some(*calibration);
+                }
+            }
+            JournalEntry::SetTrip { device, tripped } => {
+                if let Some(device) = Self::device_mut(snapshot, device) {
+                    device.tripped = *tripped;
+                    if tripped.is_some() {
+                        device.state = DeviceState::Off;
+                    }
+                }
+            }
+        }
+    }
+    /// Returns mutable device snapshot by its position in home
+    fn device_mut<'s>(
+        snapshot: &'s mut HomeSnapshot,
+        device_info: &DeviceInfo,
+    ) -> Option<&'s mut DeviceSnapshot> {
+        snapshot
+            .rooms
+            .iter_mut()
+            .find(|r| r.name == device_info.room_name)?
+            .devices
+            .iter_mut()
+            .find(|d| d.name == device_info.device_name)
+    }
+}
+
+/// Journal line payload
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    sequence: u64,
+    entry: JournalEntry,
+}
+
+/// Snapshot file content
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    /// Sequence number of the last entry included in `home`
+    sequence: u64,
+    home: HomeSnapshot,
+}
+
+/// Write-ahead journal of home mutations
+///
+/// Every entry is appended as a line `<checksum> <json>` and synced to disk.
+/// Compaction stores the replayed state in a snapshot file and empties the journal.
+/// Opening the journal replays the snapshot and the entries following it,
+/// a torn record at the end of the journal is dropped
+#[derive(Debug)]
+pub struct Journal {
+    /// Directory with journal and snapshot files
+    dir: PathBuf,
+    /// Journal file opened for appending
+    file: File,
+    /// Home state after all recorded entries
+    state: HomeSnapshot,
+    /// Sequence number of the last recorded entry
+    sequence: u64,
+    /// Number of entries since the last compaction
+    records: usize,
+    /// Compaction threshold
+    compact_every: Option<usize>,
+}
+
+impl Journal {
+    /// Opens journal in `dir` creating it if needed and replays recorded state
+    ///
+    /// `home_name` is used if there is no snapshot yet.
+    /// Returns `Err` if files can not be accessed or a record before the last one is corrupted
+    pub fn open(dir: impl AsRef<Path>, home_name: &str) -> Result<Self, JournalErrors> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let checkpoint = match fs::read_to_string(dir.join(SNAPSHOT_FILE)) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| JournalErrors::Corrupted(format!("{}: {}", SNAPSHOT_FILE, e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Checkpoint {
+                sequence: 0,
+                home: HomeSnapshot {
+                    name: home_name.to_string(),
+                    rooms: vec![],
+                },
+            },
+            Err(e) => return Err(e.into()),
+        };
+        let path = dir.join(JOURNAL_FILE);
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut state = checkpoint.home;
+        let mut sequence = checkpoint.sequence;
+        let mut records = 0;
+        let mut valid_length = 0;
+        let mut lines = content.split_inclusive(|b| *b == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let is_last = lines.peek().is_none();
+            let record = match parse_record(line) {
+                Some(record) => record,
+                None if is_last => break,
+                None => {
+                    return Err(JournalErrors::Corrupted(format!(
+                        "{} at byte {}",
+                        JOURNAL_FILE, valid_length
+                    )))
+                }
+            };
+            valid_length += line.len();
+            records += 1;
+            if record.sequence <= sequence {
+                continue;
+            }
+            record.entry.apply(&mut state);
+            sequence = record.sequence;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if valid_length < content.len() {
+            file.set_len(valid_length as u64)?;
+            file.sync_data()?;
+        }
+        Ok(Self {
+            dir,
+            file,
+            state,
+            sequence,
+            records,
+            compact_every: None,
+        })
+    }
+    /// Returns journal compacting itself after `records` entries
+    pub fn compact_every(mut self, records: usize) -> Self {
+        self.compact_every = Some(records.max(1));
+        self
+    }
+    /// Returns home state after all recorded entries
+    pub fn state(&self) -> &HomeSnapshot {
+        &self.state
+    }
+    /// Returns sequence number of the last recorded entry
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    /// Returns number of entries since the last compaction
+    pub fn records(&self) -> usize {
+        self.records
+    }
+    /// Appends entry and syncs it to disk
+    ///
+    /// Compacts journal if compaction threshold is reached
+    pub fn append(&mut self, entry: JournalEntry) -> Result<(), JournalErrors> {
+        let record = Record {
+            sequence: self.sequence + 1,
+            entry,
+        };
+        let json =
+            serde_json::to_string(&record).map_err(|e| JournalErrors::Corrupted(e.to_string()))?;
+        let line = format!("{:08x} {}\n", checksum(json.as_bytes()), json);
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        record.entry.apply(&mut self.state);
+        self.sequence = record.sequence;
+        self.records += 1;
+        if self.compact_every.is_some_and(|n| self.records >= n) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+    /// Stores current state in snapshot file and empties journal
+    ///
+    /// Snapshot is replaced atomically, entries left in journal by an interrupted
+    /// compaction are skipped on replay
+    pub fn compact(&mut self) -> Result<(), JournalErrors> {
+        let checkpoint = Checkpoint {
+            sequence: self.sequence,
+            home: self.state.clone(),
+        };
+        let json = serde_json::to_string_pretty(&checkpoint)
+            .map_err(|e| JournalErrors::Corrupted(e.to_string()))?;
+        let temp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(json.as_bytes())?;
+        temp.sync_all()?;
+        fs::rename(&temp_path, self.dir.join(SNAPSHOT_FILE))?;
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        self.records = 0;
+        Ok(())
+    }
+}
+
+/// Parses journal line, returns `None` for torn or corrupted line
+fn parse_record(line: &[u8]) -> Option<Record> {
+    let line = std::str::from_utf8(line).ok()?.strip_suffix('\n')?;
+    let (sum, json) = line.split_once(' ')?;
+    if u32::from_str_radix(sum, 16).ok()? != checksum(json.as_bytes()) {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+/// FNV-1a hash of record
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash: u32, b| {
+        (hash ^ *b as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_line_roundtrip() {
+        let record = Record {
+            sequence: 7,
+            entry: JournalEntry::RemoveRoom("kitchen".to_string()),
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        let line = format!("{:08x} {}\n", checksum(json.as_bytes()), json);
+        let parsed = parse_record(line.as_bytes()).unwrap();
+        assert_eq!(parsed.sequence, 7);
+        assert_eq!(parsed.entry, record.entry);
+        assert!(parse_record(&line.as_bytes()[..line.len() - 1]).is_none());
+        assert!(parse_record(line.replace("kitchen", "kitcheN").as_bytes()).is_none());
+    }
+    #[test]
+    fn apply_entries() {
+        let mut snapshot = HomeSnapshot {
+            name: "home".to_string(),
+            rooms: vec![],
+        };
+        let device = DeviceInfo::new("socket", "kitchen");
+        let entries = [
+            JournalEntry::AddRoom {
+                room_name: "garage".to_string(),
+                index: 0,
+            },
+            JournalEntry::AddRoom {
+                room_name: "kitchen".to_string(),
+                index: 0,
+            },
+            JournalEntry::add_device("kitchen", 0, &crate::Socket::new("socket")),
+            JournalEntry::SetState {
+                device: device.clone(),
+                state: DeviceState::On,
+            },
+            JournalEntry::RemoveRoom("garage".to_string()),
+        ];
+        entries.iter().for_each(|e| e.apply(&mut snapshot));
+        assert_eq!(snapshot.rooms.len(), 1);
+        assert_eq!(snapshot.device(&device).unwrap().state, DeviceState::On);
+    }
+}
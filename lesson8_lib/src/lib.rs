@@ -1,13 +1,58 @@
+mod access;
+mod alarm;
+mod builder;
 mod device;
 mod devices;
 mod errors;
 mod home;
+mod journal;
+mod metrics;
+mod mqtt;
+mod power;
+mod query;
 mod room;
+mod simulation;
+mod snapshot;
+mod temperature;
+mod transaction;
+mod visitor;
 
+pub use access::{AccessControl, DeniedAttempt, Permission, Role, Scope, User, UserContext, OWNER};
+pub use alarm::{
+    Alarm, AlarmEvent, AlarmEventKind, AlarmManager, AlarmState, Condition, LogFileSink,
+    NotificationSink, StdoutSink, WebhookSink, WEBHOOK_TIMEOUT,
+};
+pub use builder::HomeBuilder;
 pub use device::*;
 pub use devices::socket::Socket;
 pub use devices::thermo::Thermometer;
+pub use errors::{
+    access_errors::AccessErrors, alarm_errors::AlarmErrors, home_errors::HomeErrors,
+    journal_errors::JournalErrors, room_errors::RoomErrors,
+};
 pub use home::Home;
+pub use journal::{Journal, JournalEntry, JOURNAL_FILE, SNAPSHOT_FILE};
+pub use metrics::{render_metrics, METRICS_CONTENT_TYPE};
+pub use mqtt::{
+    bridge::{MqttBridge, MqttCommand},
+    packet::{topic_matches, MqttMessage, MqttPacket, MAX_PACKET},
+};
+pub use power::{PowerEvent, PowerEventKind};
+pub use query::{
+    glob_match, Comparison, Filter, Query, QueryMatch, ReadingKind, SortKey, SortOrder,
+};
+pub use room::RoomView;
+pub use simulation::{
+    devices::{SimulatedSocket, SimulatedThermometer},
+    trace::{Trace, TraceEntry, TraceEvent},
+    Simulator,
+};
+pub use snapshot::{
+    DeviceSnapshot, HomeDiff, HomeSnapshot, ReadingChange, RoomSnapshot, StateChange,
+};
+pub use temperature::{Calibration, Temperature, TemperatureDisplay, TemperatureUnit};
+pub use transaction::{Operation, OperationResult, Transaction};
+pub use visitor::HomeVisitor;
 #[cfg(test)]
 mod tests {
 
@@ -0,0 +1,108 @@
+use std::fmt::Write;
+
+use crate::{
+    device::{DeviceInfo, DeviceState, Reading},
+    home::Home,
+};
+
+/// Content type of Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Metric family rendered by exporter
+struct MetricFamily {
+    name: &'static str,
+    help: &'static str,
+    metric_type: &'static str,
+    /// Samples as label values and sample value
+    samples: Vec<(DeviceInfo, f64)>,
+}
+
+/// Renders home devices in Prometheus text exposition format
+///
+/// Every sample is labelled by home, room and device names.
+/// Readings are exported only for devices which measure something right now
+pub fn render_metrics(home: &Home) -> String {
+    let snapshot = home.snapshot();
+    let mut families = [
+        MetricFamily {
+            name: "home_device_on",
+            help: "Device state, 1 if device is on",
+            metric_type: "gauge",
+            samples: vec![],
+        },
+        MetricFamily {
+            name: "home_socket_power_watts",
+            help: "Current socket power",
+            metric_type: "gauge",
+            samples: vec![],
+        },
+        MetricFamily {
+            name: "home_thermometer_temperature_celsius",
+            help: "Current thermometer temperature",
+            metric_type: "gauge",
+            samples: vec![],
+        },
+        MetricFamily {
+            name: "home_device_state_changes_total",
+            help: "Number of device state changes",
+            metric_type: "counter",
+            samples: vec![],
+        },
+    ];
+    for room in snapshot.rooms.iter() {
+        for device in room.devices.iter() {
+            let device_info = DeviceInfo::new(&device.name, &room.name);
+            let is_on = (device.state == DeviceState::On) as u8 as f64;
+            families[0].samples.push((device_info.clone(), is_on));
+            match device.reading {
+                Some(Reading::Power(power)) => families[1]
+                    .samples
+                    .push((device_info.clone(), power as f64)),
+                Some(Reading::Temperature(temperature)) => families[2]
+                    .samples
+                    .push((device_info.clone(), temperature.celsius())),
+                None => (),
+            }
+            let state_changes = home.get_state_changes(&device_info).unwrap_or_default();
+            families[3]
+                .samples
+                .push((device_info, state_changes as f64));
+        }
+    }
+
+    let mut output = String::new();
+    for family in families.iter() {
+        let _ = writeln!(output, "# HELP {} {}", family.name, family.help);
+        let _ = writeln!(output, "# TYPE {} {}", family.name, family.metric_type);
+        for (device_info, value) in family.samples.iter() {
+            let _ = writeln!(
+                output,
+                "{}{{home=\"{}\",room=\"{}\",device=\"{}\"}} {}",
+                family.name,
+                escape_label(home.name()),
+                escape_label(&device_info.room_name),
+                escape_label(&device_info.device_name),
+                value
+            );
+        }
+    }
+    output
+}
+
+/// Escapes label value as required by exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
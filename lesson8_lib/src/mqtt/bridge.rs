@@ -0,0 +1,259 @@
+use std::{
+    io::{self, BufReader},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::packet::{topic_matches, MqttMessage, MqttPacket, MAX_PACKET};
+use crate::{
+    access::UserContext,
+    device::{DeviceInfo, DeviceState, Reading},
+    errors::home_errors::HomeErrors,
+    home::Home,
+    snapshot::DeviceSnapshot,
+    temperature::TemperatureDisplay,
+};
+
+/// Keep alive interval sent to broker
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Command received from a `<prefix>/<room>/<device>/set` topic
+#[derive(Debug)]
+pub struct MqttCommand {
+    pub device: DeviceInfo,
+    pub state: DeviceState,
+    /// Result of turning the device on or off
+    pub result: Result<(), HomeErrors>,
+}
+
+/// Bridge between home and MQTT broker
+///
+/// Publishes retained device states to `<prefix>/<room>/<device>/state` and readings
+/// to `<prefix>/<room>/<device>/power` or `.../temperature` in home temperature unit.
+/// Accepts `ON` and `OFF` payloads on `<prefix>/<room>/<device>/set`.
+/// Availability is published to `<prefix>/status` as retained `online`,
+/// broker publishes `offline` as last will if the bridge connection is lost
+#[derive(Debug)]
+pub struct MqttBridge {
+    /// Topic prefix
+    prefix: String,
+    /// Connection used for writing
+    stream: TcpStream,
+    /// Packets read by reader thread
+    incoming: Receiver<MqttPacket>,
+    /// Time of the last packet sent to broker
+    last_sent: Instant,
+}
+
+impl MqttBridge {
+    /// Connects to broker and subscribes to command topics
+    pub fn connect(address: impl ToSocketAddrs, client_id: &str, prefix: &str) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        MqttPacket::Connect {
+            client_id: client_id.to_string(),
+            keep_alive: KEEP_ALIVE.as_secs() as u16,
+            clean_session: true,
+            will: Some(MqttMessage::new(
+                &format!("{}/status", prefix),
+                b"offline",
+                true,
+            )),
+        }
+        .write(&mut stream)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        match MqttPacket::read(&mut reader, MAX_PACKET)? {
+            MqttPacket::ConnAck { return_code: 0, .. } => (),
+            packet => {
+                return Err(io::Error::other(format!(
+                    "Broker refused connection: {:?}",
+                    packet
+                )))
+            }
+        }
+        // commands are accepted as soon as the bridge is returned
+        MqttPacket::Subscribe {
+            packet_id: 1,
+            filters: vec![format!("{}/+/+/set", prefix)],
+        }
+        .write(&mut stream)?;
+        match MqttPacket::read(&mut reader, MAX_PACKET)? {
+            MqttPacket::SubAck { packet_id: 1, .. } => (),
+            packet => {
+                return Err(io::Error::other(format!(
+                    "Broker refused subscription: {:?}",
+                    packet
+                )))
+            }
+        }
+        stream.set_read_timeout(None)?;
+
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(packet) = MqttPacket::read(&mut reader, MAX_PACKET) {
+                if sender.send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut bridge = Self {
+            prefix: prefix.to_string(),
+            stream,
+            incoming,
+            last_sent: Instant::now(),
+        };
+        bridge.publish(&bridge.availability_topic(), b"online", true)?;
+        Ok(bridge)
+    }
+    /// Returns topic with `online` or `offline` bridge status
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+    /// Returns topic of device state
+    pub fn state_topic(&self, device_info: &DeviceInfo) -> String {
+        self.device_topic(device_info, "state")
+    }
+    /// Returns topic accepting device commands
+    pub fn command_topic(&self, device_info: &DeviceInfo) -> String {
+        self.device_topic(device_info, "set")
+    }
+    /// Publishes states and readings of all home devices
+    pub fn publish_home(&mut self, home: &Home) -> io::Result<()> {
+        let snapshot = home.snapshot();
+        for room in snapshot.rooms.iter() {
+            for device in room.devices.iter() {
+                self.publish_snapshot(
+                    &DeviceInfo::new(&device.name, &room.name),
+                    device,
+                    home.temperature_display(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+    /// Publishes state and reading of one device
+    ///
+    /// Returns `Err` with `NotFound` kind if device is not in home
+    pub fn publish_device(&mut self, home: &Home, device_info: &DeviceInfo) -> io::Result<()> {
+        let snapshot = home.snapshot();
+        let device = snapshot.device(device_info).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}/{}", device_info.room_name, device_info.device_name),
+            )
+        })?;
+        self.publish_snapshot(device_info, device, home.temperature_display())
+    }
+    /// Applies commands received within `timeout` and publishes changed states
+    ///
+    /// Commands are applied on behalf of the context user.
+    /// Waits for the first packet at most `timeout`, then takes already received ones.
+    /// Payloads other than `ON` and `OFF` are ignored
+    pub fn poll(
+        &mut self,
+        home: &mut UserContext,
+        timeout: Duration,
+    ) -> io::Result<Vec<MqttCommand>> {
+        if self.last_sent.elapsed() >= KEEP_ALIVE / 2 {
+            self.send(&MqttPacket::PingReq)?;
+        }
+        let mut packets = vec![];
+        match self.incoming.recv_timeout(timeout) {
+            Ok(packet) => packets.push(packet),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return Err(connection_lost()),
+        }
+        packets.extend(self.incoming.try_iter());
+        let mut commands = vec![];
+        for packet in packets {
+            let MqttPacket::Publish(message) = packet else {
+                continue;
+            };
+            if let Some(command) = self.apply(home, &message) {
+                if command.result.is_ok() {
+                    self.publish_device(home.home(), &command.device)?;
+                }
+                commands.push(command);
+            }
+        }
+        Ok(commands)
+    }
+    /// Publishes `offline` status and disconnects gracefully
+    ///
+    /// Dropping the bridge without `disconnect` makes broker publish the last will
+    pub fn disconnect(mut self) -> io::Result<()> {
+        self.publish(&self.availability_topic(), b"offline", true)?;
+        self.send(&MqttPacket::Disconnect)
+    }
+    /// Turns device on or off by command message
+    fn apply(&self, home: &mut UserContext, message: &MqttMessage) -> Option<MqttCommand> {
+        if !topic_matches(&format!("{}/+/+/set", self.prefix), &message.topic) {
+            return None;
+        }
+        let mut levels = message.topic.rsplit('/').skip(1);
+        let device_name = levels.next()?;
+        let room_name = levels.next()?;
+        let device = DeviceInfo::new(device_name, room_name);
+        let payload = String::from_utf8_lossy(&message.payload);
+        let (state, result) = match payload.trim() {
+            p if p.eq_ignore_ascii_case("on") => (DeviceState::On, home.turn_on(&device)),
+            p if p.eq_ignore_ascii_case("off") => (DeviceState::Off, home.turn_off(&device)),
+            _ => return None,
+        };
+        Some(MqttCommand {
+            device,
+            state,
+            result,
+        })
+    }
+    /// Publishes retained state and reading of device snapshot
+    fn publish_snapshot(
+        &mut self,
+        device_info: &DeviceInfo,
+        device: &DeviceSnapshot,
+        temperature_display: &TemperatureDisplay,
+    ) -> io::Result<()> {
+        let state_topic = self.state_topic(device_info);
+        self.publish(&state_topic, device.state.to_string().as_bytes(), true)?;
+        if let Some(reading) = device.reading {
+            let (kind, value) = match reading {
+                Reading::Power(power) => ("power", power.to_string()),
+                Reading::Temperature(temperature) => {
+                    ("temperature", temperature_display.value(temperature))
+                }
+            };
+            let reading_topic = self.device_topic(device_info, kind);
+            self.publish(&reading_topic, value.as_bytes(), true)?;
+        }
+        Ok(())
+    }
+    fn device_topic(&self, device_info: &DeviceInfo, suffix: &str) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.prefix, device_info.room_name, device_info.device_name, suffix
+        )
+    }
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+        self.send(&MqttPacket::Publish(MqttMessage::new(
+            topic, payload, retain,
+        )))
+    }
+    fn send(&mut self, packet: &MqttPacket) -> io::Result<()> {
+        packet.write(&mut self.stream)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for MqttBridge {
+    fn drop(&mut self) {
+        // stops reader thread holding a clone of the connection
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn connection_lost() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "Broker connection lost")
+}
@@ -0,0 +1,3 @@
+/// MQTT bridge with minimal MQTT 3.1.1 client
+pub mod bridge;
+pub mod packet;
@@ -0,0 +1,371 @@
+use std::io::{self, Read, Write};
+
+/// Largest remaining length allowed by MQTT 3.1.1
+const MAX_REMAINING_LENGTH: usize = 268_435_455;
+/// Default limit of remaining length of read packets
+pub const MAX_PACKET: usize = 64 * 1024;
+
+/// Application message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// Broker keeps the last retained message of a topic for new subscribers
+    pub retain: bool,
+}
+
+impl MqttMessage {
+    pub fn new(topic: &str, payload: &[u8], retain: bool) -> Self {
+        Self {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            retain,
+        }
+    }
+}
+
+/// MQTT 3.1.1 control packet
+///
+/// Only QoS 0 is supported, so publishing needs no acknowledgements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MqttPacket {
+    Connect {
+        client_id: String,
+        /// Keep alive interval in seconds
+        keep_alive: u16,
+        clean_session: bool,
+        /// Message published by broker if client disconnects without `Disconnect`
+        will: Option<MqttMessage>,
+    },
+    ConnAck {
+        session_present: bool,
+        return_code: u8,
+    },
+    Publish(MqttMessage),
+    Subscribe {
+        packet_id: u16,
+        filters: Vec<String>,
+    },
+    SubAck {
+        packet_id: u16,
+        return_codes: Vec<u8>,
+    },
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+impl MqttPacket {
+    /// Reads one packet
+    /// Returns `io::Err` of `InvalidData` kind before reading body
+    /// if its remaining length is over `max_packet`
+    pub fn read(reader: &mut impl Read, max_packet: usize) -> io::Result<Self> {
+        let mut header = [0; 1];
+        reader.read_exact(&mut header)?;
+        let mut length = 0;
+        let mut multiplier = 1;
+        loop {
+            let mut byte = [0; 1];
+            reader.read_exact(&mut byte)?;
+            length += (byte[0] & 0x7f) as usize * multiplier;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            multiplier *= 128;
+            if multiplier > 128 * 128 * 128 {
+                return Err(invalid_data("malformed remaining length"));
+            }
+        }
+        if length > max_packet {
+            return Err(invalid_data("packet is over size limit"));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        Self::decode(header[0], &body)
+    }
+    /// Writes packet as a single buffer
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (header, body) = self.encode();
+        if body.len() > MAX_REMAINING_LENGTH {
+            return Err(invalid_data("packet is too large"));
+        }
+        let mut packet = vec![header];
+        let mut length = body.len();
+        loop {
+            let mut byte = (length % 128) as u8;
+            length /= 128;
+            if length > 0 {
+                byte |= 0x80;
+            }
+            packet.push(byte);
+            if length == 0 {
+                break;
+            }
+        }
+        packet.extend(body);
+        writer.write_all(&packet)?;
+        writer.flush()
+    }
+    /// Returns first header byte and packet body
+    fn encode(&self) -> (u8, Vec<u8>) {
+        let mut body = vec![];
+        let header = match self {
+            MqttPacket::Connect {
+                client_id,
+                keep_alive,
+                clean_session,
+                will,
+            } => {
+                put_string(&mut body, "MQTT");
+                body.push(4);
+                let mut flags = 0;
+                if *clean_session {
+                    flags |= 0x02;
+                }
+                if let Some(will) = will {
+                    flags |= 0x04;
+                    if will.retain {
+                        flags |= 0x20;
+                    }
+                }
+                body.push(flags);
+                body.extend(keep_alive.to_be_bytes());
+                put_string(&mut body, client_id);
+                if let Some(will) = will {
+                    put_string(&mut body, &will.topic);
+                    put_bytes(&mut body, &will.payload);
+                }
+                0x10
+            }
+            MqttPacket::ConnAck {
+                session_present,
+                return_code,
+            } => {
+                body.push(*session_present as u8);
+                body.push(*return_code);
+                0x20
+            }
+            MqttPacket::Publish(message) => {
+                put_string(&mut body, &message.topic);
+                body.extend(&message.payload);
+                0x30 | message.retain as u8
+            }
+            MqttPacket::Subscribe { packet_id, filters } => {
+                body.extend(packet_id.to_be_bytes());
+                for filter in filters {
+                    put_string(&mut body, filter);
+                    body.push(0);
+                }
+                0x82
+            }
+            MqttPacket::SubAck {
+                packet_id,
+                return_codes,
+            } => {
+                body.extend(packet_id.to_be_bytes());
+                body.extend(return_codes);
+                0x90
+            }
+            MqttPacket::PingReq => 0xc0,
+            MqttPacket::PingResp => 0xd0,
+            MqttPacket::Disconnect => 0xe0,
+        };
+        (header, body)
+    }
+    /// Parses packet body by first header byte
+    fn decode(header: u8, body: &[u8]) -> io::Result<Self> {
+        let mut body = Body(body);
+        let packet = match header >> 4 {
+            1 => {
+                if body.string()? != "MQTT" || body.byte()? != 4 {
+                    return Err(invalid_data("unsupported protocol"));
+                }
+                let flags = body.byte()?;
+                let keep_alive = body.u16()?;
+                let client_id = body.string()?;
+                let will = if flags & 0x04 != 0 {
+                    Some(MqttMessage {
+                        topic: body.string()?,
+                        payload: body.bytes()?.to_vec(),
+                        retain: flags & 0x20 != 0,
+                    })
+                } else {
+                    None
+                };
+                MqttPacket::Connect {
+                    client_id,
+                    keep_alive,
+                    clean_session: flags & 0x02 != 0,
+                    will,
+                }
+            }
+            2 => MqttPacket::ConnAck {
+                session_present: body.byte()? & 0x01 != 0,
+                return_code: body.byte()?,
+            },
+            3 => {
+                let topic = body.string()?;
+                if (header >> 1) & 0x03 != 0 {
+                    // packet identifier of QoS 1 and 2 messages
+                    body.u16()?;
+                }
+                MqttPacket::Publish(MqttMessage {
+                    topic,
+                    payload: body.rest().to_vec(),
+                    retain: header & 0x01 != 0,
+                })
+            }
+            8 => {
+                let packet_id = body.u16()?;
+                let mut filters = vec![];
+                while !body.0.is_empty() {
+                    filters.push(body.string()?);
+                    body.byte()?;
+                }
+                MqttPacket::Subscribe { packet_id, filters }
+            }
+            9 => MqttPacket::SubAck {
+                packet_id: body.u16()?,
+                return_codes: body.rest().to_vec(),
+            },
+            12 => MqttPacket::PingReq,
+            13 => MqttPacket::PingResp,
+            14 => MqttPacket::Disconnect,
+            kind => return Err(invalid_data(&format!("unsupported packet type {}", kind))),
+        };
+        Ok(packet)
+    }
+}
+
+/// Returns `true` if `topic` matches subscription `filter` with `+` and `#` wildcards
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for filter_level in filter.split('/') {
+        match (filter_level, topic_levels.next()) {
+            ("#", _) => return true,
+            (_, None) => return false,
+            ("+", Some(_)) => (),
+            (level, Some(topic_level)) if level == topic_level => (),
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Cursor over packet body
+struct Body<'b>(&'b [u8]);
+
+impl<'b> Body<'b> {
+    fn take(&mut self, length: usize) -> io::Result<&'b [u8]> {
+        if self.0.len() < length {
+            return Err(invalid_data("packet is too short"));
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    fn bytes(&mut self) -> io::Result<&'b [u8]> {
+        let length = self.u16()? as usize;
+        self.take(length)
+    }
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid_data("invalid UTF-8"))
+    }
+    fn rest(&mut self) -> &'b [u8] {
+        std::mem::take(&mut self.0)
+    }
+}
+
+fn put_bytes(body: &mut Vec<u8>, bytes: &[u8]) {
+    body.extend((bytes.len() as u16).to_be_bytes());
+    body.extend(bytes);
+}
+
+fn put_string(body: &mut Vec<u8>, string: &str) {
+    put_bytes(body, string.as_bytes());
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(packet: MqttPacket) {
+        let mut buffer = vec![];
+        packet.write(&mut buffer).unwrap();
+        assert_eq!(
+            MqttPacket::read(&mut buffer.as_slice(), MAX_PACKET).unwrap(),
+            packet
+        );
+    }
+
+    #[test]
+    fn packets_roundtrip() {
+        roundtrip(MqttPacket::Connect {
+            client_id: "bridge".to_string(),
+            keep_alive: 30,
+            clean_session: true,
+            will: Some(MqttMessage::new("home/status", b"offline", true)),
+        });
+        roundtrip(MqttPacket::ConnAck {
+            session_present: false,
+            return_code: 0,
+        });
+        roundtrip(MqttPacket::Publish(MqttMessage::new(
+            "home/kitchen/socket/state",
+            &[b'x'; 300],
+            false,
+        )));
+        roundtrip(MqttPacket::Subscribe {
+            packet_id: 1,
+            filters: vec!["home/+/+/set".to_string(), "home/#".to_string()],
+        });
+        roundtrip(MqttPacket::SubAck {
+            packet_id: 1,
+            return_codes: vec![0, 0],
+        });
+        roundtrip(MqttPacket::PingReq);
+        roundtrip(MqttPacket::Disconnect);
+    }
+    #[test]
+    fn remaining_length_encoding() {
+        let mut buffer = vec![];
+        MqttPacket::Publish(MqttMessage::new("t", &[0; 200], true))
+            .write(&mut buffer)
+            .unwrap();
+        assert_eq!(&buffer[..3], &[0x31, 0xcb, 0x01]);
+        assert!(MqttPacket::read(&mut &buffer[..100], MAX_PACKET).is_err());
+    }
+    #[test]
+    fn packet_over_limit_is_rejected() {
+        let mut buffer = vec![];
+        MqttPacket::Publish(MqttMessage::new("t", &[0; 200], false))
+            .write(&mut buffer)
+            .unwrap();
+        let error = MqttPacket::read(&mut buffer.as_slice(), 100).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // length announcing the largest packet is rejected without reading the body
+        let header = [0x30, 0xff, 0xff, 0xff, 0x7f];
+        assert!(MqttPacket::read(&mut header.as_slice(), MAX_PACKET).is_err());
+        assert!(MqttPacket::read(&mut buffer.as_slice(), 203).is_ok());
+    }
+    #[test]
+    fn topic_filters() {
+        assert!(topic_matches("home/+/+/set", "home/kitchen/socket/set"));
+        assert!(topic_matches("home/#", "home/kitchen/socket/state"));
+        assert!(topic_matches("#", "home"));
+        assert!(!topic_matches("home/+/set", "home/kitchen/socket/set"));
+        assert!(!topic_matches("home/+/+/set", "home/kitchen/socket/state"));
+        assert!(!topic_matches("home/kitchen", "home"));
+    }
+}
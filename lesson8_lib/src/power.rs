@@ -0,0 +1,64 @@
+use std::{fmt::Display, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::device::{DeviceInfo, Reading};
+
+/// Enum for reasons of power protection events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerEventKind {
+    /// Device reading exceeded its own limit, device is latched off until reset
+    Tripped,
+    /// Device was switched off because home consumption exceeded the budget
+    Shed {
+        /// Home consumption before the device was switched off
+        total: u32,
+        budget: u32,
+        /// Shedding priority of the device
+        priority: u8,
+    },
+    /// Tripped device was allowed to turn on again
+    Reset,
+}
+
+/// Device protection or load shedding event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerEvent {
+    pub kind: PowerEventKind,
+    pub device: DeviceInfo,
+    /// Reading which caused the event
+    pub reading: Option<Reading>,
+    pub time: SystemTime,
+}
+
+impl Display for PowerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}/{}",
+            match self.kind {
+                PowerEventKind::Tripped => "Tripped",
+                PowerEventKind::Shed { .. } => "Shed",
+                PowerEventKind::Reset => "Reset",
+            },
+            self.device.room_name,
+            self.device.device_name
+        )?;
+        if let Some(reading) = self.reading {
+            write!(f, " {}", reading)?;
+        }
+        match self.kind {
+            PowerEventKind::Tripped => write!(f, " above device limit"),
+            PowerEventKind::Shed {
+                total,
+                budget,
+                priority,
+            } => write!(
+                f,
+                ", priority {}, home total {} above budget {}",
+                priority, total, budget
+            ),
+            PowerEventKind::Reset => Ok(()),
+        }
+    }
+}
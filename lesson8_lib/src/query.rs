@@ -0,0 +1,705 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::{
+    device::{DeviceInfo, DeviceState, Reading},
+    errors::query_errors::QueryErrors,
+    snapshot::{DeviceSnapshot, HomeSnapshot},
+};
+
+/// Enum for comparison operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    /// Returns `true` if `left <op> right`
+    fn compare(&self, left: i64, right: i64) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = QueryErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "=" | "==" => Ok(Self::Equal),
+            "!=" => Ok(Self::NotEqual),
+            ">" => Ok(Self::Greater),
+            ">=" => Ok(Self::GreaterOrEqual),
+            "<" => Ok(Self::Less),
+            "<=" => Ok(Self::LessOrEqual),
+            _ => Err(QueryErrors::UnexpectedToken(s.to_string())),
+        }
+    }
+}
+
+/// Enum for readings a filter is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingKind {
+    /// Any reading
+    Any,
+    /// Socket power
+    Power,
+    /// Thermometer temperature
+    Temperature,
+}
+
+impl ReadingKind {
+    /// Returns reading value in thousandths of unit if reading is of this kind
+    fn milli_value(&self, reading: &Reading) -> Option<i64> {
+        match (self, reading) {
+            (ReadingKind::Any, _)
+            | (ReadingKind::Power, Reading::Power(_))
+            | (ReadingKind::Temperature, Reading::Temperature(_)) => Some(reading.milli_value()),
+            _ => None,
+        }
+    }
+}
+
+/// Device filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Device has state
+    State(DeviceState),
+    /// Device has reading of kind compared with value
+    Reading {
+        kind: ReadingKind,
+        comparison: Comparison,
+        value: i64,
+    },
+    /// Room name matches glob pattern with `*` and `?`
+    Room(String),
+    /// Device name matches glob pattern with `*` and `?`
+    Device(String),
+    /// Device is tagged with tag
+    Tag(String),
+    /// Device vendor matches glob pattern with `*` and `?`
+    Vendor(String),
+    /// Device model matches glob pattern with `*` and `?`
+    Model(String),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Returns filter by device state
+    pub fn state(state: DeviceState) -> Self {
+        Self::State(state)
+    }
+    /// Returns filter by socket power
+    pub fn power(comparison: Comparison, value: i64) -> Self {
+        Self::Reading {
+            kind: ReadingKind::Power,
+            comparison,
+            value,
+        }
+    }
+    /// Returns filter by thermometer temperature
+    pub fn temperature(comparison: Comparison, value: i64) -> Self {
+        Self::Reading {
+            kind: ReadingKind::Temperature,
+            comparison,
+            value,
+        }
+    }
+    /// Returns filter by room name pattern
+    pub fn room(pattern: &str) -> Self {
+        Self::Room(pattern.to_string())
+    }
+    /// Returns filter by device name pattern
+    pub fn device(pattern: &str) -> Self {
+        Self::Device(pattern.to_string())
+    }
+    /// Returns filter by device tag
+    pub fn tag(tag: &str) -> Self {
+        Self::Tag(tag.to_string())
+    }
+    /// Returns filter by device vendor pattern
+    pub fn vendor(pattern: &str) -> Self {
+        Self::Vendor(pattern.to_string())
+    }
+    /// Returns filter by device model pattern
+    pub fn model(pattern: &str) -> Self {
+        Self::Model(pattern.to_string())
+    }
+    /// Returns filter matching both filters
+    pub fn and(self, other: Filter) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+    /// Returns filter matching any filter
+    pub fn or(self, other: Filter) -> Self {
+        Self::Or(Box::new(self), Box::new(other))
+    }
+    /// Returns inverted filter
+    pub fn negate(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+    /// Returns `true` if device in room matches the filter
+    pub fn matches(&self, room_name: &str, device: &DeviceSnapshot) -> bool {
+        match self {
+            Filter::State(state) => device.state == *state,
+            Filter::Reading {
+                kind,
+                comparison,
+                value,
+            } => device
+                .reading
+                .and_then(|r| kind.milli_value(&r))
+                .is_some_and(|v| comparison.compare(v, value.saturating_mul(1000))),
+            Filter::Room(pattern) => glob_match(pattern, room_name),
+            Filter::Device(pattern) => glob_match(pattern, &device.name),
+            Filter::Tag(tag) => device.metadata.has_tag(tag),
+            Filter::Vendor(pattern) => device
+                .metadata
+                .vendor
+                .as_ref()
+                .is_some_and(|v| glob_match(pattern, v)),
+            Filter::Model(pattern) => device
+                .metadata
+                .model
+                .as_ref()
+                .is_some_and(|m| glob_match(pattern, m)),
+            Filter::And(left, right) => {
+                left.matches(room_name, device) && right.matches(room_name, device)
+            }
+            Filter::Or(left, right) => {
+                left.matches(room_name, device) || right.matches(room_name, device)
+            }
+            Filter::Not(filter) => !filter.matches(room_name, device),
+        }
+    }
+}
+
+/// Enum for sorting keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Room,
+    Device,
+    State,
+    /// Power readings go before temperatures, devices without reading go last
+    Reading,
+}
+
+/// Enum for sorting order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Device found by query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMatch {
+    pub room_name: String,
+    pub device: DeviceSnapshot,
+}
+
+impl QueryMatch {
+    /// Returns device position in home
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo::new(&self.device.name, &self.room_name)
+    }
+}
+
+/// Query struct
+///
+/// Selects devices by filter, sorts and limits them.
+/// Can be built in code or parsed from text like
+/// `state = On and power > 50 in rooms matching kitchen* sort by power desc limit 3`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Device filter, all devices match if `None`
+    filter: Option<Filter>,
+    /// Sorting key and order, home order if `None`
+    sort: Option<(SortKey, SortOrder)>,
+    /// Max number of matches
+    limit: Option<usize>,
+}
+
+impl Query {
+    /// Returns query matching all devices
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds filter which should match together with the previous ones
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter {
+            Some(previous) => previous.and(filter),
+            None => filter,
+        });
+        self
+    }
+    /// Keeps devices in rooms with names matching `pattern`
+    pub fn in_rooms(self, pattern: &str) -> Self {
+        self.filter(Filter::room(pattern))
+    }
+    /// Sorts matches
+    pub fn sort_by(mut self, key: SortKey, order: SortOrder) -> Self {
+        self.sort = Some((key, order));
+        self
+    }
+    /// Limits number of matches
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    /// Runs query over home snapshot
+    pub fn run(&self, snapshot: &HomeSnapshot) -> Vec<QueryMatch> {
+        let mut matches: Vec<QueryMatch> = snapshot
+            .rooms
+            .iter()
+            .flat_map(|r| {
+                r.devices.iter().map(|d| QueryMatch {
+                    room_name: r.name.clone(),
+                    device: d.clone(),
+                })
+            })
+            .filter(|m| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(&m.room_name, &m.device))
+            })
+            .collect();
+        if let Some((key, order)) = self.sort {
+            matches.sort_by(|a, b| Self::compare(key, order, a, b));
+        }
+        if let Some(limit) = self.limit {
+            matches.truncate(limit);
+        }
+        matches
+    }
+    /// Compares matches by key
+    fn compare(key: SortKey, order: SortOrder, a: &QueryMatch, b: &QueryMatch) -> Ordering {
+        let ordering = match key {
+            SortKey::Room => a.room_name.cmp(&b.room_name),
+            SortKey::Device => a.device.name.cmp(&b.device.name),
+            SortKey::State => {
+                (a.device.state == DeviceState::On).cmp(&(b.device.state == DeviceState::On))
+            }
+            SortKey::Reading => match (a.device.reading, b.device.reading) {
+                (Some(Reading::Power(a)), Some(Reading::Power(b))) => a.cmp(&b),
+                (Some(Reading::Temperature(a)), Some(Reading::Temperature(b))) => a.cmp(&b),
+                (Some(Reading::Power(_)), Some(Reading::Temperature(_))) => Ordering::Less,
+                (Some(Reading::Temperature(_)), Some(Reading::Power(_))) => Ordering::Greater,
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        match order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s)?.parse_query()
+    }
+}
+
+/// Returns `true` if `text` matches `pattern` with `*` and `?` wildcards
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Recursive descent parser of query text
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Result<Self, QueryErrors> {
+        Ok(Self {
+            tokens: Self::tokenize(s)?,
+            position: 0,
+        })
+    }
+    /// Splits text into words, numbers, patterns, operators and parentheses
+    fn tokenize(s: &str) -> Result<Vec<String>, QueryErrors> {
+        let mut tokens = vec![];
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() => (),
+                '(' | ')' => tokens.push(c.to_string()),
+                '=' | '!' | '<' | '>' => {
+                    let mut token = c.to_string();
+                    if chars.peek() == Some(&'=') {
+                        token.push(chars.next().unwrap());
+                    }
+                    if token == "!" {
+                        return Err(QueryErrors::UnexpectedToken(token));
+                    }
+                    tokens.push(token);
+                }
+                _ => {
+                    let mut token = c.to_string();
+                    while let Some(c) = chars.peek() {
+                        if c.is_whitespace() || "()=!<>".contains(*c) {
+                            break;
+                        }
+                        token.push(chars.next().unwrap());
+                    }
+                    tokens.push(token);
+                }
+            }
+        }
+        Ok(tokens)
+    }
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|t| t.as_str())
+    }
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.eq_ignore_ascii_case(keyword))
+    }
+    fn next(&mut self) -> Result<String, QueryErrors> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(QueryErrors::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), QueryErrors> {
+        let token = self.next()?;
+        if !token.eq_ignore_ascii_case(keyword) {
+            return Err(QueryErrors::UnexpectedToken(token));
+        }
+        Ok(())
+    }
+    /// query := [expr] ["in" "rooms" "matching" GLOB] ["sort" "by" KEY [ORDER]] ["limit" N]
+    fn parse_query(&mut self) -> Result<Query, QueryErrors> {
+        let mut query = Query::new();
+        if self.peek().is_some() && !["in", "sort", "limit"].iter().any(|k| self.peek_keyword(k)) {
+            query = query.filter(self.parse_or()?);
+        }
+        if self.peek_keyword("in") {
+            self.next()?;
+            self.expect_keyword("rooms")?;
+            self.expect_keyword("matching")?;
+            query = query.in_rooms(&self.next()?);
+        }
+        if self.peek_keyword("sort") {
+            self.next()?;
+            self.expect_keyword("by")?;
+            let key = self.next()?;
+            let key = match key.to_lowercase().as_str() {
+                "room" => SortKey::Room,
+                "device" | "name" => SortKey::Device,
+                "state" => SortKey::State,
+                "reading" | "power" | "temperature" => SortKey::Reading,
+                _ => return Err(QueryErrors::UnexpectedToken(key)),
+            };
+            let mut order = SortOrder::Ascending;
+            if self.peek_keyword("asc") {
+                self.next()?;
+            } else if self.peek_keyword("desc") {
+                self.next()?;
+                order = SortOrder::Descending;
+            }
+            query = query.sort_by(key, order);
+        }
+        if self.peek_keyword("limit") {
+            self.next()?;
+            let limit = self.next()?;
+            query = query.limit(
+                limit
+                    .parse()
+                    .map_err(|_| QueryErrors::InvalidNumber(limit))?,
+            );
+        }
+        if let Some(token) = self.peek() {
+            return Err(QueryErrors::UnexpectedToken(token.to_string()));
+        }
+        Ok(query)
+    }
+    /// expr := and_expr ("or" and_expr)*
+    fn parse_or(&mut self) -> Result<Filter, QueryErrors> {
+        let mut filter = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.next()?;
+            filter = filter.or(self.parse_and()?);
+        }
+        Ok(filter)
+    }
+    /// and_expr := unary ("and" unary)*
+    fn parse_and(&mut self) -> Result<Filter, QueryErrors> {
+        let mut filter = self.parse_unary()?;
+        while self.peek_keyword("and") {
+            self.next()?;
+            filter = filter.and(self.parse_unary()?);
+        }
+        Ok(filter)
+    }
+    /// unary := "not" unary | "(" expr ")" | FIELD OP VALUE
+    fn parse_unary(&mut self) -> Result<Filter, QueryErrors> {
+        if self.peek_keyword("not") {
+            self.next()?;
+            return Ok(self.parse_unary()?.negate());
+        }
+        if self.peek() == Some("(") {
+            self.next()?;
+            let filter = self.parse_or()?;
+            let token = self.next()?;
+            if token != ")" {
+                return Err(QueryErrors::UnexpectedToken(token));
+            }
+            return Ok(filter);
+        }
+        let field = self.next()?;
+        let comparison: Comparison = self.next()?.parse()?;
+        let value = self.next()?;
+        let filter = match field.to_lowercase().as_str() {
+            "state" => {
+                let state = match value.to_lowercase().as_str() {
+                    "on" => DeviceState::On,
+                    "off" => DeviceState::Off,
+                    _ => return Err(QueryErrors::UnexpectedToken(value)),
+                };
+                return Self::equality(Filter::state(state), comparison);
+            }
+            "room" => return Self::equality(Filter::room(&value), comparison),
+            "device" | "name" => return Self::equality(Filter::device(&value), comparison),
+            "tag" => return Self::equality(Filter::tag(&value), comparison),
+            "vendor" => return Self::equality(Filter::vendor(&value), comparison),
+            "model" => return Self::equality(Filter::model(&value), comparison),
+            "power" => ReadingKind::Power,
+            "temperature" => ReadingKind::Temperature,
+            "reading" => ReadingKind::Any,
+            _ => return Err(QueryErrors::UnknownField(field)),
+        };
+        Ok(Filter::Reading {
+            kind: filter,
+            comparison,
+            value: value
+                .parse()
+                .map_err(|_| QueryErrors::InvalidNumber(value))?,
+        })
+    }
+    /// Applies `=` or `!=` to filter
+    fn equality(filter: Filter, comparison: Comparison) -> Result<Filter, QueryErrors> {
+        match comparison {
+            Comparison::Equal => Ok(filter),
+            Comparison::NotEqual => Ok(filter.negate()),
+            _ => Err(QueryErrors::UnexpectedToken(format!("{:?}", comparison))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{device::DeviceMetadata, snapshot::RoomSnapshot, temperature::Temperature};
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("kitchen*", "kitchen"));
+        assert!(glob_match("kitchen*", "kitchen_2"));
+        assert!(glob_match("*room", "living_room"));
+        assert!(glob_match("r?om", "room"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("kitchen*", "garage"));
+        assert!(!glob_match("r?om", "rooom"));
+    }
+    #[test]
+    fn parse_query() {
+        let query: Query =
+            "state = On and power > 50 in rooms matching kitchen* sort by power desc limit 3"
+                .parse()
+                .unwrap();
+        let expected = Query::new()
+            .filter(Filter::state(DeviceState::On).and(Filter::power(Comparison::Greater, 50)))
+            .in_rooms("kitchen*")
+            .sort_by(SortKey::Reading, SortOrder::Descending)
+            .limit(3);
+        assert_eq!(query, expected);
+    }
+    #[test]
+    fn parse_precedence() {
+        let query: Query = "device = a or not (state != off) and temperature<=5"
+            .parse()
+            .unwrap();
+        let expected = Query::new().filter(
+            Filter::device("a").or(Filter::state(DeviceState::Off)
+                .negate()
+                .negate()
+                .and(Filter::temperature(Comparison::LessOrEqual, 5))),
+        );
+        assert_eq!(query, expected);
+        assert_eq!("".parse::<Query>().unwrap(), Query::new());
+    }
+    #[test]
+    fn parse_metadata_fields() {
+        let query: Query = "tag = critical and vendor != Acme* or model = X1"
+            .parse()
+            .unwrap();
+        let expected = Query::new().filter(
+            Filter::tag("critical")
+                .and(Filter::vendor("Acme*").negate())
+                .or(Filter::model("X1")),
+        );
+        assert_eq!(query, expected);
+    }
+    #[test]
+    fn run_query() {
+        let device = |name: &str, state, reading| DeviceSnapshot {
+            name: name.to_string(),
+            state,
+            reading,
+            metadata: DeviceMetadata::default(),
+            calibration: None,
+            tripped: None,
+        };
+        let snapshot = HomeSnapshot {
+            name: "home".to_string(),
+            rooms: vec![
+                RoomSnapshot {
+                    name: "kitchen".to_string(),
+                    devices: vec![
+                        device("kettle", DeviceState::On, Some(Reading::Power(60))),
+                        device("fridge", DeviceState::On, Some(Reading::Power(90))),
+                        device("lamp", DeviceState::Off, None),
+                    ],
+                },
+                RoomSnapshot {
+                    name: "garage".to_string(),
+                    devices: vec![device("heater", DeviceState::On, Some(Reading::Power(99)))],
+                },
+            ],
+        };
+        let query: Query =
+            "state = On and power > 50 in rooms matching kitchen* sort by power desc"
+                .parse()
+                .unwrap();
+        let names: Vec<String> = query
+            .run(&snapshot)
+            .into_iter()
+            .map(|m| m.device.name)
+            .collect();
+        assert_eq!(names, vec!["fridge", "kettle"]);
+
+        let query = Query::new()
+            .sort_by(SortKey::Reading, SortOrder::Ascending)
+            .limit(2);
+        let matches = query.run(&snapshot);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(
+            matches[0].device_info(),
+            DeviceInfo::new("kettle", "kitchen")
+        );
+        assert_eq!(matches[1].device.name, "fridge");
+    }
+    #[test]
+    fn sort_by_reading_kind() {
+        let device = |name: &str, reading| DeviceSnapshot {
+            name: name.to_string(),
+            state: DeviceState::On,
+            reading,
+            metadata: DeviceMetadata::default(),
+            calibration: None,
+            tripped: None,
+        };
+        let snapshot = HomeSnapshot {
+            name: "home".to_string(),
+            rooms: vec![RoomSnapshot {
+                name: "kitchen".to_string(),
+                devices: vec![
+                    device(
+                        "warm",
+                        Some(Reading::Temperature(Temperature::from_celsius(20.4))),
+                    ),
+                    device("lamp", None),
+                    device("kettle", Some(Reading::Power(1500))),
+                    device(
+                        "cool",
+                        Some(Reading::Temperature(Temperature::from_celsius(20.2))),
+                    ),
+                    device("fridge", Some(Reading::Power(90))),
+                ],
+            }],
+        };
+        let names = |order| -> Vec<String> {
+            Query::new()
+                .sort_by(SortKey::Reading, order)
+                .run(&snapshot)
+                .into_iter()
+                .map(|m| m.device.name)
+                .collect()
+        };
+        assert_eq!(
+            names(SortOrder::Ascending),
+            vec!["fridge", "kettle", "cool", "warm", "lamp"]
+        );
+        assert_eq!(
+            names(SortOrder::Descending),
+            vec!["warm", "cool", "kettle", "fridge", "lamp"]
+        );
+        // temperatures are compared without rounding
+        let query: Query = "temperature > 20".parse().unwrap();
+        assert_eq!(query.run(&snapshot).len(), 2);
+    }
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            "colour = red".parse::<Query>(),
+            Err(QueryErrors::UnknownField(_))
+        ));
+        assert!(matches!(
+            "power > many".parse::<Query>(),
+            Err(QueryErrors::InvalidNumber(_))
+        ));
+        assert!(matches!(
+            "state =".parse::<Query>(),
+            Err(QueryErrors::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            "state > on".parse::<Query>(),
+            Err(QueryErrors::UnexpectedToken(_))
+        ));
+        assert!(matches!(
+            "state = on limit 3 extra".parse::<Query>(),
+            Err(QueryErrors::UnexpectedToken(_))
+        ));
+    }
+}
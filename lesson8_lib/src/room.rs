@@ -1,6 +1,10 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
-use crate::{device::Device, errors::room_errors::RoomErrors};
+use crate::{
+    device::{Device, DeviceMetadata, DeviceState, Reading},
+    errors::room_errors::RoomErrors,
+    temperature::{Calibration, TemperatureDisplay},
+};
 
 /// Room struct
 ///
@@ -12,6 +16,8 @@ pub(crate) struct Room<'a> {
     name: String,
     /// Vec to store pointers to devices
     devices: Vec<&'a mut dyn Device>,
+    /// Number of state changes by device name
+    state_changes: HashMap<String, u64>,
 }
 
 impl<'a> Room<'a> {
@@ -20,6 +26,7 @@ impl<'a> Room<'a> {
         Self {
             name: name.to_string(),
             devices: vec![],
+            state_changes: HashMap::new(),
         }
     }
     /// Returns room name
@@ -45,32 +52,205 @@ impl<'a> Room<'a> {
     pub fn remove_device(&mut self, device_name: &str) -> Result<(), RoomErrors> {
         if self.devices.iter().any(|d| d.name() == device_name) {
             self.devices.retain(|d| d.name() != device_name);
+            self.state_changes.remove(device_name);
             return Ok(());
         }
         Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()))
     }
-    /// Returns room report with all internal devices
-    pub fn get_report(&self) -> String {
-        self.to_string()
+    /// Removes device from room and gives it back with its number of state changes
+    ///
+    /// Returns `Ok((index, device, state_changes))` if `device_name` is found,
+    /// `Err` with description otherwise
+    ///
+    pub fn take_device(
+        &mut self,
+        device_name: &str,
+    ) -> Result<(usize, &'a mut dyn Device, u64), RoomErrors> {
+        let index = self.devices.iter().position(|d| d.name() == device_name);
+        if index.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        let index = index.unwrap();
+        let state_changes = self.state_changes.remove(device_name).unwrap_or_default();
+        Ok((index, self.devices.remove(index), state_changes))
+    }
+    /// Puts device taken by `take_device` back to room at `index`
+    ///
+    /// Returns `Ok(())` if `device_name` is unique, `Err` with description otherwise
+    ///
+    pub fn insert_device(
+        &mut self,
+        index: usize,
+        device: &'a mut dyn Device,
+        state_changes: u64,
+    ) -> Result<(), RoomErrors> {
+        if self.devices.iter().any(|d| d.name() == device.name()) {
+            return Err(RoomErrors::DeviceNameExists(device.name().to_string()));
+        }
+        if state_changes > 0 {
+            self.state_changes
+                .insert(device.name().to_string(), state_changes);
+        }
+        self.devices.insert(index.min(self.devices.len()), device);
+        Ok(())
+    }
+    /// Returns device state
+    ///
+    /// Returns `Ok(DeviceState)` if `device_name` is found, `Err` with description otherwise
+    ///
+    pub fn get_device_state(&self, device_name: &str) -> Result<DeviceState, RoomErrors> {
+        let dev = self.devices.iter().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(*dev.unwrap().state())
+    }
+    /// Returns device metadata, empty if device does not store it
+    ///
+    /// Returns `Ok(DeviceMetadata)` if `device_name` is found, `Err` with description otherwise
+    ///
+    pub fn get_device_metadata(&self, device_name: &str) -> Result<DeviceMetadata, RoomErrors> {
+        let dev = self.devices.iter().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(dev.unwrap().metadata().cloned().unwrap_or_default())
+    }
+    /// Replaces device metadata
+    ///
+    /// Returns `Ok(())` if `device_name` is found and the device stores metadata,
+    /// `Err` with description otherwise
+    ///
+    pub fn set_device_metadata(
+        &mut self,
+        device_name: &str,
+        metadata: DeviceMetadata,
+    ) -> Result<(), RoomErrors> {
+        let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        match dev.unwrap().metadata_mut() {
+            Some(current) => *current = metadata,
+            None => return Err(RoomErrors::MetadataNotSupported(device_name.to_string())),
+        }
+        Ok(())
+    }
+    /// Returns device calibration, `None` if device is not calibrated
+    ///
+    /// Returns `Ok(Option<Calibration>)` if `device_name` is found, `Err` with description otherwise
+    ///
+    pub fn get_device_calibration(
+        &self,
+        device_name: &str,
+    ) -> Result<Option<Calibration>, RoomErrors> {
+        let dev = self.devices.iter().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(dev.unwrap().calibration().copied())
+    }
+    /// Replaces device calibration
+    ///
+    /// Returns `Ok(())` if `device_name` is found and the device is calibrated,
+    /// `Err` with description otherwise
+    ///
+    pub fn set_device_calibration(
+        &mut self,
+        device_name: &str,
+        calibration: Calibration,
+    ) -> Result<(), RoomErrors> {
+        let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        match dev.unwrap().calibration_mut() {
+            Some(current) => *current = calibration,
+            None => return Err(RoomErrors::CalibrationNotSupported(device_name.to_string())),
+        }
+        Ok(())
+    }
+    /// Returns reading which tripped device protection, `None` if device is not tripped
+    ///
+    /// Returns `Ok(Option<Reading>)` if `device_name` is found, `Err` with description otherwise
+    ///
+    pub fn get_device_trip(&self, device_name: &str) -> Result<Option<Reading>, RoomErrors> {
+        let dev = self.devices.iter().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(dev.unwrap().tripped())
+    }
+    /// Allows tripped device to be turned on again
+    ///
+    /// Returns `Ok(bool)` with `true` if device was tripped and `device_name` is found,
+    /// `Err` with description otherwise
+    ///
+    pub fn reset_device(&mut self, device_name: &str) -> Result<bool, RoomErrors> {
+        let dev = self.devices.iter_mut().find(|d| d.name() == device_name);
+        if dev.is_none() {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(dev.unwrap().reset())
+    }
+    /// Returns number of device state changes made by the room
+    ///
+    /// Returns `Ok(u64)` if `device_name` is found, `Err` with description otherwise
+    ///
+    pub fn get_state_changes(&self, device_name: &str) -> Result<u64, RoomErrors> {
+        if !self.devices.iter().any(|d| d.name() == device_name) {
+            return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
+        }
+        Ok(self
+            .state_changes
+            .get(device_name)
+            .copied()
+            .unwrap_or_default())
+    }
+    /// Returns room report with all internal devices and temperatures shown by `display`
+    pub fn get_report(&self, display: &TemperatureDisplay) -> String {
+        format!(
+            "Room name: {}\n\tdevices: [\n{}]\n",
+            self.name,
+            self.get_devices_report(display)
+        )
     }
     /// Returns device report
     ///
     /// Returns `Ok(String)` if `device_name` is found, `Err` with description otherwise
     ///
-    pub fn get_device_report(&self, device_name: &str) -> Result<String, RoomErrors> {
+    pub fn get_device_report(
+        &self,
+        device_name: &str,
+        display: &TemperatureDisplay,
+    ) -> Result<String, RoomErrors> {
         let dev = self.devices.iter().find(|d| d.name() == device_name);
         if dev.is_none() {
             return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
         }
-        Ok(dev.unwrap().get_report())
+        Ok(dev.unwrap().get_report_in(display))
     }
     /// Returns vec with devices' names    
     pub fn get_devices(&self) -> Vec<&str> {
         self.devices.iter().map(|d| d.name()).collect()
     }
+    /// Returns iterator over devices
+    pub fn devices(&self) -> impl Iterator<Item = &dyn Device> + use<'_, 'a> {
+        self.devices.iter().map(|d| &**d as &dyn Device)
+    }
+    /// Returns iterator over room name and mutable devices
+    pub fn devices_mut(&mut self) -> impl Iterator<Item = (&str, &mut dyn Device)> + use<'_, 'a> {
+        let name = self.name.as_str();
+        self.devices
+            .iter_mut()
+            .map(move |d| (name, &mut **d as &mut dyn Device))
+    }
     /// Returns devices' reports
-    fn get_devices_report(&self) -> String {
-        self.devices.iter().map(|d| d.get_report()).collect()
+    fn get_devices_report(&self, display: &TemperatureDisplay) -> String {
+        self.devices
+            .iter()
+            .map(|d| d.get_report_in(display))
+            .collect()
     }
     /// Turns on a device
     ///
@@ -81,7 +261,17 @@ impl<'a> Room<'a> {
         if dev.is_none() {
             return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
         }
-        dev.unwrap().turn_on();
+        let dev = dev.unwrap();
+        if dev.tripped().is_some() {
+            return Err(RoomErrors::DeviceTripped(device_name.to_string()));
+        }
+        if *dev.state() != DeviceState::On {
+            *self
+                .state_changes
+                .entry(device_name.to_string())
+                .or_default() += 1;
+        }
+        dev.turn_on();
         Ok(())
     }
     /// Turns off a device
@@ -93,19 +283,49 @@ impl<'a> Room<'a> {
         if dev.is_none() {
             return Err(RoomErrors::DeviceNameDoesNotExist(device_name.to_string()));
         }
-        dev.unwrap().turn_off();
+        let dev = dev.unwrap();
+        if *dev.state() != DeviceState::Off {
+            *self
+                .state_changes
+                .entry(device_name.to_string())
+                .or_default() += 1;
+        }
+        dev.turn_off();
         Ok(())
     }
 }
 
+/// Read-only view of a room
+#[derive(Debug, Clone, Copy)]
+pub struct RoomView<'r, 'a> {
+    room: &'r Room<'a>,
+}
+
+impl<'r, 'a> RoomView<'r, 'a> {
+    pub(crate) fn new(room: &'r Room<'a>) -> Self {
+        Self { room }
+    }
+    /// Returns room name
+    pub fn name(&self) -> &'r str {
+        self.room.name()
+    }
+    /// Returns iterator over devices in room order
+    pub fn devices(&self) -> impl Iterator<Item = &'r dyn Device> + use<'r, 'a> {
+        self.room.devices()
+    }
+    /// Returns number of devices
+    pub fn len(&self) -> usize {
+        self.room.devices.len()
+    }
+    /// Returns `true` if room has no devices
+    pub fn is_empty(&self) -> bool {
+        self.room.devices.is_empty()
+    }
+}
+
 impl<'a> Display for Room<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Room name: {}\n\tdevices: [\n{}]\n",
-            self.name,
-            self.get_devices_report()
-        )
+        write!(f, "{}", self.get_report(&TemperatureDisplay::default()))
     }
 }
 
@@ -153,9 +373,78 @@ mod tests {
         assert!(room.turn_off(DEVICE_NAME).is_ok());
     }
     #[test]
+    fn count_state_changes() {
+        let mut room = Room::new(ROOM_NAME);
+        let mut device = Socket::new(DEVICE_NAME);
+        room.add_device(&mut device).unwrap();
+        room.turn_on(DEVICE_NAME).unwrap();
+        room.turn_on(DEVICE_NAME).unwrap();
+        room.turn_off(DEVICE_NAME).unwrap();
+        assert_eq!(room.get_state_changes(DEVICE_NAME).unwrap(), 2);
+        assert!(room.get_state_changes("missing").is_err());
+
+        // device added again under the same name starts counting from zero
+        room.remove_device(DEVICE_NAME).unwrap();
+        let mut replacement = Socket::new(DEVICE_NAME);
+        room.add_device(&mut replacement).unwrap();
+        assert_eq!(room.get_state_changes(DEVICE_NAME).unwrap(), 0);
+        room.turn_on(DEVICE_NAME).unwrap();
+        let (index, device, state_changes) = room.take_device(DEVICE_NAME).unwrap();
+        assert_eq!(state_changes, 1);
+        room.insert_device(index, device, state_changes).unwrap();
+        assert_eq!(room.get_state_changes(DEVICE_NAME).unwrap(), 1);
+    }
+    #[test]
+    fn device_metadata() {
+        let mut room = Room::new(ROOM_NAME);
+        let mut device = Socket::new(DEVICE_NAME);
+        room.add_device(&mut device).unwrap();
+        assert!(room.get_device_metadata(DEVICE_NAME).unwrap().is_empty());
+        let metadata = DeviceMetadata::new()
+            .with_tag("critical")
+            .with_vendor("Acme");
+        room.set_device_metadata(DEVICE_NAME, metadata.clone())
+            .unwrap();
+        assert_eq!(room.get_device_metadata(DEVICE_NAME).unwrap(), metadata);
+        assert!(room.to_string().contains("tags: critical\nvendor: Acme\n"));
+        assert!(room
+            .set_device_metadata("missing", DeviceMetadata::new())
+            .is_err());
+    }
+    #[test]
     fn turn_on_off_non_existing_device() {
         let mut room = Room::new(ROOM_NAME);
         assert!(room.turn_on(DEVICE_NAME).is_err());
         assert!(room.turn_off(DEVICE_NAME).is_err());
     }
+    #[test]
+    fn iterate_devices_mut() {
+        let mut room = Room::new(ROOM_NAME);
+        let mut first = Socket::new("first");
+        let mut second = Socket::new("second");
+        room.add_device(&mut first).unwrap();
+        room.add_device(&mut second).unwrap();
+        for (room_name, device) in room.devices_mut() {
+            assert_eq!(room_name, ROOM_NAME);
+            device.turn_on();
+        }
+        assert!(room.devices().all(|d| *d.state() == DeviceState::On));
+        let view = RoomView::new(&room);
+        assert_eq!(view.len(), 2);
+        assert_eq!(view.devices().next().unwrap().name(), "first");
+    }
+    #[test]
+    fn take_and_insert_device() {
+        let mut room = Room::new(ROOM_NAME);
+        let mut first = Socket::new("first");
+        let mut second = Socket::new("second");
+        room.add_device(&mut first).unwrap();
+        room.add_device(&mut second).unwrap();
+        let (index, device, state_changes) = room.take_device("first").unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(room.get_devices(), vec!["second"]);
+        assert!(room.insert_device(index, device, state_changes).is_ok());
+        assert_eq!(room.get_devices(), vec!["first", "second"]);
+        assert!(room.take_device(DEVICE_NAME).is_err());
+    }
 }
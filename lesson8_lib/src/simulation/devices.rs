@@ -0,0 +1,152 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicI32, AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    device::{Device, DeviceState, Reading},
+    temperature::{Calibration, Temperature, TemperatureDisplay},
+};
+
+/// Thermometer measuring temperature of simulated room
+#[derive(Debug)]
+pub struct SimulatedThermometer {
+    name: String,
+    state: DeviceState,
+    /// Raw temperature in millidegrees Celsius written by simulator
+    temperature: Arc<AtomicI32>,
+    calibration: Calibration,
+}
+
+impl SimulatedThermometer {
+    pub(crate) fn new(name: &str, temperature: Arc<AtomicI32>) -> Self {
+        Self {
+            name: name.to_string(),
+            state: DeviceState::On,
+            temperature,
+            calibration: Calibration::default(),
+        }
+    }
+    /// Returns device with calibration
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+    fn temperature(&self) -> Option<Temperature> {
+        match self.state {
+            DeviceState::On => Some(self.calibration.apply(Temperature::from_millicelsius(
+                self.temperature.load(Ordering::Relaxed),
+            ))),
+            DeviceState::Off => None,
+        }
+    }
+}
+
+impl Device for SimulatedThermometer {
+    fn turn_on(&mut self) {
+        self.state = DeviceState::On;
+    }
+    fn turn_off(&mut self) {
+        self.state = DeviceState::Off;
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+    fn get_report_in(&self, display: &TemperatureDisplay) -> String {
+        format!(
+            "Simulated thermometer name: {}\nstate: {}\ncurrent temperature: {}\n",
+            self.name,
+            self.state,
+            self.temperature()
+                .map(|t| display.format(t))
+                .unwrap_or("none".to_string())
+        )
+    }
+    fn reading(&self) -> Option<Reading> {
+        self.temperature().map(Reading::Temperature)
+    }
+    fn calibration(&self) -> Option<&Calibration> {
+        Some(&self.calibration)
+    }
+    fn calibration_mut(&mut self) -> Option<&mut Calibration> {
+        Some(&mut self.calibration)
+    }
+}
+
+impl Display for SimulatedThermometer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get_report_in(&TemperatureDisplay::default()))
+    }
+}
+
+/// Socket drawing power of simulated load
+#[derive(Debug)]
+pub struct SimulatedSocket {
+    name: String,
+    state: DeviceState,
+    /// Power in watts written by simulator
+    power: Arc<AtomicU32>,
+    /// Load shedding priority
+    priority: u8,
+}
+
+impl SimulatedSocket {
+    pub(crate) fn new(name: &str, power: Arc<AtomicU32>) -> Self {
+        Self {
+            name: name.to_string(),
+            state: DeviceState::Off,
+            power,
+            priority: 0,
+        }
+    }
+    /// Returns socket with load shedding priority
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+    fn power(&self) -> Option<u32> {
+        match self.state {
+            DeviceState::On => Some(self.power.load(Ordering::Relaxed)),
+            DeviceState::Off => None,
+        }
+    }
+}
+
+impl Device for SimulatedSocket {
+    fn turn_on(&mut self) {
+        self.state = DeviceState::On;
+    }
+    fn turn_off(&mut self) {
+        self.state = DeviceState::Off;
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+    fn reading(&self) -> Option<Reading> {
+        self.power().map(Reading::Power)
+    }
+    fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+impl Display for SimulatedSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Simulated socket name: {}\nstate: {}\ncurrent power: {}\n",
+            self.name,
+            self.state,
+            self.power().unwrap_or_default()
+        )
+    }
+}
@@ -0,0 +1,341 @@
+//! Home simulation over simulated time
+
+pub mod devices;
+pub mod trace;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use self::{
+    devices::{SimulatedSocket, SimulatedThermometer},
+    trace::{Trace, TraceEvent},
+};
+use crate::{
+    device::{DeviceInfo, DeviceState},
+    home::Home,
+    temperature::Temperature,
+};
+
+/// Heat given off by one occupant in watts
+const OCCUPANT_WATTS: f64 = 100.0;
+
+/// Enum for loads plugged into simulated sockets
+#[derive(Debug, Clone, Copy)]
+enum Load {
+    /// Draws rated power and heats its room
+    Heater { watts: u32 },
+    /// Draws idle power plus power for every occupant of its room
+    Appliance {
+        idle_watts: u32,
+        watts_per_occupant: u32,
+    },
+}
+
+/// Load with power read by simulated socket
+#[derive(Debug)]
+struct SimulatedLoad {
+    device_name: String,
+    load: Load,
+    power: Arc<AtomicU32>,
+}
+
+#[derive(Debug)]
+struct Occupant {
+    name: String,
+    room_name: String,
+}
+
+/// Simulator driving home over simulated time
+///
+/// Occupants move between home rooms at random, room temperatures follow a first-order
+/// thermal model heated by heater sockets and occupants and cooled towards outdoor temperature.
+/// Simulated devices are created by simulator and matched to home devices by name,
+/// so their names should be unique within simulator.
+/// Time is advanced in steps without waiting, runs with the same seed produce the same trace
+#[derive(Debug)]
+pub struct Simulator {
+    rng: StdRng,
+    /// Time the simulation starts at
+    start: SystemTime,
+    /// Simulated time since start
+    elapsed: Duration,
+    step: Duration,
+    outdoor_temperature: f64,
+    /// Temperature of rooms when simulation sees them first
+    initial_temperature: f64,
+    /// Time for room to lose ~63% of difference with outdoor temperature
+    time_constant: Duration,
+    /// Room temperature rise per kWh of heat without losses
+    degrees_per_kwh: f64,
+    /// Probability of occupant moving during a step
+    move_probability: f64,
+    room_temperatures: HashMap<String, f64>,
+    occupants: Vec<Occupant>,
+    /// Raw temperatures of simulated thermometers by device name
+    thermometers: Vec<(String, Arc<AtomicI32>)>,
+    loads: Vec<SimulatedLoad>,
+}
+
+impl Simulator {
+    /// Returns simulator with random decisions made from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            start: SystemTime::UNIX_EPOCH,
+            elapsed: Duration::ZERO,
+            step: Duration::from_secs(60),
+            outdoor_temperature: 5.0,
+            initial_temperature: 20.0,
+            time_constant: Duration::from_secs(4 * 3600),
+            degrees_per_kwh: 2.0,
+            move_probability: 0.05,
+            room_temperatures: HashMap::new(),
+            occupants: vec![],
+            thermometers: vec![],
+            loads: vec![],
+        }
+    }
+    /// Sets simulated time the simulation starts at
+    pub fn starting_at(mut self, start: SystemTime) -> Self {
+        self.start = start;
+        self
+    }
+    /// Sets simulated time between two steps
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+    /// Sets outdoor temperature in degrees Celsius
+    pub fn with_outdoor_temperature(mut self, celsius: f64) -> Self {
+        self.outdoor_temperature = celsius;
+        self
+    }
+    /// Sets temperature of rooms in degrees Celsius at start
+    pub fn with_initial_temperature(mut self, celsius: f64) -> Self {
+        self.initial_temperature = celsius;
+        self
+    }
+    /// Sets how fast rooms cool down and how much heat warms them up
+    pub fn with_thermal_model(mut self, time_constant: Duration, degrees_per_kwh: f64) -> Self {
+        self.time_constant = time_constant;
+        self.degrees_per_kwh = degrees_per_kwh;
+        self
+    }
+    /// Sets probability of every occupant moving to another room during a step
+    pub fn with_move_probability(mut self, probability: f64) -> Self {
+        self.move_probability = probability.clamp(0.0, 1.0);
+        self
+    }
+    /// Adds occupant staying in `room_name` at start
+    pub fn with_occupant(mut self, name: &str, room_name: &str) -> Self {
+        self.occupants.push(Occupant {
+            name: name.to_string(),
+            room_name: room_name.to_string(),
+        });
+        self
+    }
+    /// Returns thermometer measuring temperature of the room it is placed in
+    pub fn thermometer(&mut self, name: &str) -> SimulatedThermometer {
+        let temperature = Arc::new(AtomicI32::new(
+            Temperature::from_celsius(self.initial_temperature).millicelsius(),
+        ));
+        self.thermometers
+            .push((name.to_string(), temperature.clone()));
+        SimulatedThermometer::new(name, temperature)
+    }
+    /// Returns socket with heater drawing `watts` and heating its room while turned on
+    pub fn heater(&mut self, name: &str, watts: u32) -> SimulatedSocket {
+        self.socket(name, Load::Heater { watts })
+    }
+    /// Returns socket with appliance drawing `idle_watts`
+    /// and `watts_per_occupant` for every occupant of its room
+    pub fn appliance(
+        &mut self,
+        name: &str,
+        idle_watts: u32,
+        watts_per_occupant: u32,
+    ) -> SimulatedSocket {
+        self.socket(
+            name,
+            Load::Appliance {
+                idle_watts,
+                watts_per_occupant,
+            },
+        )
+    }
+    /// Returns current simulated time
+    pub fn now(&self) -> SystemTime {
+        self.start + self.elapsed
+    }
+    /// Returns simulated time since start
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    /// Returns modelled room temperature, `None` if simulator has not seen the room yet
+    pub fn room_temperature(&self, room_name: &str) -> Option<Temperature> {
+        self.room_temperatures
+            .get(room_name)
+            .map(|t| Temperature::from_celsius(*t))
+    }
+    /// Returns room occupant stays in
+    pub fn occupant_room(&self, occupant_name: &str) -> Option<&str> {
+        self.occupants
+            .iter()
+            .find(|o| o.name == occupant_name)
+            .map(|o| o.room_name.as_str())
+    }
+    /// Returns number of occupants in room
+    pub fn occupants_in(&self, room_name: &str) -> usize {
+        self.occupants
+            .iter()
+            .filter(|o| o.room_name == room_name)
+            .count()
+    }
+    /// Runs simulation of home for `duration`
+    pub fn run(&mut self, home: &mut Home, duration: Duration) -> Trace {
+        self.run_with(home, duration, |_, _| ())
+    }
+    /// Runs simulation calling `automation` with home and simulated time before every step
+    ///
+    /// Returns trace with occupant moves, device state changes made by automation
+    /// and readings of all home devices after every step
+    pub fn run_with<'a>(
+        &mut self,
+        home: &mut Home<'a>,
+        duration: Duration,
+        mut automation: impl FnMut(&mut Home<'a>, SystemTime),
+    ) -> Trace {
+        let mut trace = Trace::default();
+        let end = self.elapsed + duration;
+        self.publish(home);
+        while self.elapsed < end {
+            let states = device_states(home);
+            automation(home, self.now());
+            for (device, state) in device_states(home) {
+                if !states.contains(&(device.clone(), state)) {
+                    trace.push(self.elapsed, TraceEvent::StateChanged { device, state });
+                }
+            }
+
+            let step = self.step.min(end - self.elapsed);
+            self.elapsed += step;
+            for (occupant, from, to) in self.move_occupants(home) {
+                trace.push(self.elapsed, TraceEvent::Moved { occupant, from, to });
+            }
+            self.update_temperatures(home, step);
+            self.publish(home);
+            for (room_name, device) in home.devices() {
+                if let Some(reading) = device.reading() {
+                    let device = DeviceInfo::new(device.name(), room_name);
+                    trace.push(self.elapsed, TraceEvent::Reading { device, reading });
+                }
+            }
+        }
+        trace
+    }
+    fn socket(&mut self, name: &str, load: Load) -> SimulatedSocket {
+        let power = Arc::new(AtomicU32::new(0));
+        self.loads.push(SimulatedLoad {
+            device_name: name.to_string(),
+            load,
+            power: power.clone(),
+        });
+        SimulatedSocket::new(name, power)
+    }
+    /// Moves occupants to random other rooms
+    ///
+    /// Returns occupant names with source and destination rooms
+    fn move_occupants(&mut self, home: &Home) -> Vec<(String, String, String)> {
+        let rooms = home.get_room_names();
+        let mut moves = vec![];
+        if rooms.len() < 2 {
+            return moves;
+        }
+        for occupant in self.occupants.iter_mut() {
+            if !self.rng.gen_bool(self.move_probability) {
+                continue;
+            }
+            let others: Vec<&str> = rooms
+                .iter()
+                .copied()
+                .filter(|r| *r != occupant.room_name)
+                .collect();
+            let to = others[self.rng.gen_range(0..others.len())].to_string();
+            let from = std::mem::replace(&mut occupant.room_name, to.clone());
+            moves.push((occupant.name.clone(), from, to));
+        }
+        moves
+    }
+    /// Advances room temperatures by `step` with heat of turned on heaters and occupants
+    fn update_temperatures(&mut self, home: &Home, step: Duration) {
+        let mut heat: HashMap<&str, f64> = HashMap::new();
+        for (room_name, device) in home.devices() {
+            let watts = match self.load(device.name()).map(|l| l.load) {
+                Some(Load::Heater { watts }) if *device.state() == DeviceState::On => watts,
+                _ => continue,
+            };
+            *heat.entry(room_name).or_default() += watts as f64;
+        }
+        let time_constant = self.time_constant.as_secs_f64() / 3600.0;
+        let decay = (-step.as_secs_f64() / 3600.0 / time_constant).exp();
+        for room_name in home.get_room_names() {
+            let watts = heat.get(room_name).copied().unwrap_or_default()
+                + self.occupants_in(room_name) as f64 * OCCUPANT_WATTS;
+            // temperature the room settles at with constant heat
+            let steady =
+                self.outdoor_temperature + watts / 1000.0 * self.degrees_per_kwh * time_constant;
+            let temperature = self
+                .room_temperatures
+                .entry(room_name.to_string())
+                .or_insert(self.initial_temperature);
+            *temperature = steady + (*temperature - steady) * decay;
+        }
+    }
+    /// Writes modelled temperatures and powers to simulated devices placed in home
+    fn publish(&mut self, home: &Home) {
+        for (room_name, device) in home.devices() {
+            if let Some((_, temperature)) =
+                self.thermometers.iter().find(|(n, _)| n == device.name())
+            {
+                let celsius = *self
+                    .room_temperatures
+                    .entry(room_name.to_string())
+                    .or_insert(self.initial_temperature);
+                temperature.store(
+                    Temperature::from_celsius(celsius).millicelsius(),
+                    Ordering::Relaxed,
+                );
+            }
+            if let Some(load) = self.load(device.name()) {
+                let watts = match load.load {
+                    Load::Heater { watts } => watts,
+                    Load::Appliance {
+                        idle_watts,
+                        watts_per_occupant,
+                    } => idle_watts.saturating_add(
+                        watts_per_occupant.saturating_mul(self.occupants_in(room_name) as u32),
+                    ),
+                };
+                load.power.store(watts, Ordering::Relaxed);
+            }
+        }
+    }
+    fn load(&self, device_name: &str) -> Option<&SimulatedLoad> {
+        self.loads.iter().find(|l| l.device_name == device_name)
+    }
+}
+
+/// Returns states of all home devices
+fn device_states(home: &Home) -> Vec<(DeviceInfo, DeviceState)> {
+    home.devices()
+        .map(|(room_name, device)| (DeviceInfo::new(device.name(), room_name), *device.state()))
+        .collect()
+}
@@ -0,0 +1,121 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::device::{DeviceInfo, DeviceState, Reading};
+
+/// Enum for things happened in simulated home
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// Occupant went to another room
+    Moved {
+        occupant: String,
+        from: String,
+        to: String,
+    },
+    /// Device was turned on or off during the step, e.g. by automation
+    StateChanged {
+        device: DeviceInfo,
+        state: DeviceState,
+    },
+    /// Device reading at the end of the step
+    Reading {
+        device: DeviceInfo,
+        reading: Reading,
+    },
+}
+
+/// Trace event with simulated time elapsed since simulation start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub elapsed: Duration,
+    pub event: TraceEvent,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.elapsed.as_secs();
+        write!(
+            f,
+            "{:02}:{:02}:{:02} ",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        match &self.event {
+            TraceEvent::Moved { occupant, from, to } => {
+                write!(f, "{} moved {} -> {}", occupant, from, to)
+            }
+            TraceEvent::StateChanged { device, state } => {
+                write!(f, "{}/{} {}", device.room_name, device.device_name, state)
+            }
+            TraceEvent::Reading { device, reading } => {
+                write!(f, "{}/{} {}", device.room_name, device.device_name, reading)
+            }
+        }
+    }
+}
+
+/// Events recorded by simulator in time order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub(crate) fn push(&mut self, elapsed: Duration, event: TraceEvent) {
+        self.entries.push(TraceEntry { elapsed, event });
+    }
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Returns readings of device in time order
+    pub fn readings(&self, device_info: &DeviceInfo) -> Vec<(Duration, Reading)> {
+        self.entries
+            .iter()
+            .filter_map(|e| match &e.event {
+                TraceEvent::Reading { device, reading } if device == device_info => {
+                    Some((e.elapsed, *reading))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Returns moves of occupant as time, source and destination rooms
+    pub fn moves(&self, occupant_name: &str) -> Vec<(Duration, &str, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|e| match &e.event {
+                TraceEvent::Moved { occupant, from, to } if occupant == occupant_name => {
+                    Some((e.elapsed, from.as_str(), to.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Returns state changes of device in time order
+    pub fn state_changes(&self, device_info: &DeviceInfo) -> Vec<(Duration, DeviceState)> {
+        self.entries
+            .iter()
+            .filter_map(|e| match &e.event {
+                TraceEvent::StateChanged { device, state } if device == device_info => {
+                    Some((e.elapsed, *state))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    device::{Device, DeviceInfo, DeviceMetadata, DeviceState, Reading},
+    temperature::Calibration,
+};
+
+/// Point-in-time copy of home state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HomeSnapshot {
+    /// Home name
+    pub name: String,
+    /// Rooms in home order
+    pub rooms: Vec<RoomSnapshot>,
+}
+
+/// Point-in-time copy of room state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomSnapshot {
+    /// Room name
+    pub name: String,
+    /// Devices in room order
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+/// Point-in-time copy of device state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    /// Device name
+    pub name: String,
+    /// Device state
+    pub state: DeviceState,
+    /// Latest device reading
+    pub reading: Option<Reading>,
+    /// Tags, vendor and installation details
+    #[serde(default, skip_serializing_if = "DeviceMetadata::is_empty")]
+    pub metadata: DeviceMetadata,
+    /// Correction of readings, `None` if device is not calibrated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+    /// Reading which tripped device protection, `None` if device is not tripped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tripped: Option<Reading>,
+}
+
+impl DeviceSnapshot {
+    /// Captures current device state
+    pub fn new(device: &dyn Device) -> Self {
+        Self {
+            name: device.name().to_string(),
+            state: *device.state(),
+            reading: device.reading(),
+            metadata: device.metadata().cloned().unwrap_or_default(),
+            calibration: device.calibration().copied(),
+            tripped: device.tripped(),
+        }
+    }
+}
+
+impl HomeSnapshot {
+    /// Returns room snapshot by name
+    pub fn room(&self, room_name: &str) -> Option<&RoomSnapshot> {
+        self.rooms.iter().find(|r| r.name == room_name)
+    }
+    /// Returns device snapshot by its position in home
+    pub fn device(&self, device_info: &DeviceInfo) -> Option<&DeviceSnapshot> {
+        self.room(&device_info.room_name)?
+            .devices
+            .iter()
+            .find(|d| d.name == device_info.device_name)
+    }
+    /// Returns changes made between `self` and `newer` snapshot
+    pub fn diff(&self, newer: &HomeSnapshot) -> HomeDiff {
+        let mut diff = HomeDiff::default();
+        for room in self.rooms.iter() {
+            if newer.room(&room.name).is_none() {
+                diff.removed_rooms.push(room.name.clone());
+            }
+        }
+        for room in newer.rooms.iter() {
+            let old_room = self.room(&room.name);
+            if old_room.is_none() {
+                diff.added_rooms.push(room.name.clone());
+            }
+            for device in room.devices.iter() {
+                let device_info = DeviceInfo::new(&device.name, &room.name);
+                match self.device(&device_info) {
+                    None => diff.added_devices.push(device_info),
+                    Some(old) => diff.push_device_changes(device_info, old, device),
+                }
+            }
+        }
+        for room in self.rooms.iter() {
+            for device in room.devices.iter() {
+                let device_info = DeviceInfo::new(&device.name, &room.name);
+                if newer.device(&device_info).is_none() {
+                    diff.removed_devices.push(device_info);
+                }
+            }
+        }
+        diff
+    }
+}
+
+/// Device state change between snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateChange {
+    pub device: DeviceInfo,
+    pub from: DeviceState,
+    pub to: DeviceState,
+}
+
+/// Device reading change between snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadingChange {
+    pub device: DeviceInfo,
+    pub from: Option<Reading>,
+    pub to: Option<Reading>,
+    /// `to - from` if both readings exist and are of the same kind
+    pub delta: Option<i64>,
+}
+
+/// Changes between two home snapshots
+///
+/// Devices of added or removed rooms are listed as added or removed devices too
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HomeDiff {
+    pub added_rooms: Vec<String>,
+    pub removed_rooms: Vec<String>,
+    pub added_devices: Vec<DeviceInfo>,
+    pub removed_devices: Vec<DeviceInfo>,
+    pub state_changes: Vec<StateChange>,
+    pub reading_changes: Vec<ReadingChange>,
+}
+
+impl HomeDiff {
+    /// Returns `true` if snapshots are equal
+    pub fn is_empty(&self) -> bool {
+        self.added_rooms.is_empty()
+            && self.removed_rooms.is_empty()
+            && self.added_devices.is_empty()
+            && self.removed_devices.is_empty()
+            && self.state_changes.is_empty()
+            && self.reading_changes.is_empty()
+    }
+    /// Records state and reading changes of the same device
+    fn push_device_changes(
+        &mut self,
+        device: DeviceInfo,
+        old: &DeviceSnapshot,
+        new: &DeviceSnapshot,
+    ) {
+        if old.state != new.state {
+            self.state_changes.push(StateChange {
+                device: device.clone(),
+                from: old.state,
+                to: new.state,
+            });
+        }
+        if old.reading != new.reading {
+            let delta = match (&new.reading, &old.reading) {
+                (Some(new), Some(old)) => new.delta(old),
+                _ => None,
+            };
+            self.reading_changes.push(ReadingChange {
+                device,
+                from: old.reading,
+                to: new.reading,
+                delta,
+            });
+        }
+    }
+}
+
+/// Formats optional reading for diff report
+fn reading_to_string(reading: &Option<Reading>) -> String {
+    match reading {
+        Some(reading) => reading.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+impl Display for HomeDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for room in self.added_rooms.iter() {
+            writeln!(f, "+ room {}", room)?;
+        }
+        for room in self.removed_rooms.iter() {
+            writeln!(f, "- room {}", room)?;
+        }
+        for device in self.added_devices.iter() {
+            writeln!(f, "+ device {}/{}", device.room_name, device.device_name)?;
+        }
+        for device in self.removed_devices.iter() {
+            writeln!(f, "- device {}/{}", device.room_name, device.device_name)?;
+        }
+        for change in self.state_changes.iter() {
+            writeln!(
+                f,
+                "~ state {}/{}: {} -> {}",
+                change.device.room_name, change.device.device_name, change.from, change.to
+            )?;
+        }
+        for change in self.reading_changes.iter() {
+            write!(
+                f,
+                "~ reading {}/{}: {} -> {}",
+                change.device.room_name,
+                change.device.device_name,
+                reading_to_string(&change.from),
+                reading_to_string(&change.to)
+            )?;
+            match change.delta {
+                Some(delta) => writeln!(f, " ({:+})", delta)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for HomeSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Home name: {}", self.name)?;
+        for room in self.rooms.iter() {
+            writeln!(f, "Room name: {}", room.name)?;
+            for device in room.devices.iter() {
+                writeln!(
+                    f,
+                    "\t{}: {}, {}",
+                    device.name,
+                    device.state,
+                    reading_to_string(&device.reading)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str, state: DeviceState, reading: Option<Reading>) -> DeviceSnapshot {
+        DeviceSnapshot {
+            name: name.to_string(),
+            state,
+            reading,
+            metadata: DeviceMetadata::default(),
+            calibration: None,
+            tripped: None,
+        }
+    }
+
+    #[test]
+    fn diff_of_equal_snapshots_is_empty() {
+        let snapshot = HomeSnapshot {
+            name: "home".to_string(),
+            rooms: vec![RoomSnapshot {
+                name: "room".to_string(),
+                devices: vec![device("dev", DeviceState::Off, None)],
+            }],
+        };
+        let diff = snapshot.diff(&snapshot.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No changes\n");
+    }
+    #[test]
+    fn reading_delta() {
+        let old = HomeSnapshot {
+            name: "home".to_string(),
+            rooms: vec![RoomSnapshot {
+                name: "room".to_string(),
+                devices: vec![device(
+                    "t",
+                    DeviceState::On,
+                    Some(Reading::Temperature(20.into())),
+                )],
+            }],
+        };
+        let mut new = old.clone();
+        new.rooms[0].devices[0].reading = Some(Reading::Temperature(17.into()));
+        let diff = old.diff(&new);
+        assert_eq!(diff.reading_changes.len(), 1);
+        assert_eq!(diff.reading_changes[0].delta, Some(-3));
+        assert_eq!(
+            diff.to_string(),
+            "~ reading room/t: temperature 20.0 °C -> temperature 17.0 °C (-3)\n"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Millidegrees in one degree
+const MILLIS: f64 = 1000.0;
+/// Absolute zero in degrees Celsius
+const ABSOLUTE_ZERO: f64 = -273.15;
+
+/// Enum for temperature scales
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    /// Returns unit symbol used in reports
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+}
+
+impl Display for TemperatureUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for TemperatureUnit {
+    type Err = String;
+
+    /// Parses unit name or symbol ignoring case, e.g. `celsius`, `C` or `°F`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().trim_start_matches('°').to_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnit::Kelvin),
+            _ => Err(format!("Unknown temperature unit {}", s)),
+        }
+    }
+}
+
+/// Temperature with millidegree precision
+///
+/// Stored as whole millidegrees Celsius, so readings and snapshots stay comparable.
+/// Serialized as degrees Celsius
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(from = "f64", into = "f64")]
+pub struct Temperature {
+    millicelsius: i32,
+}
+
+impl Temperature {
+    pub fn from_millicelsius(millicelsius: i32) -> Self {
+        Self { millicelsius }
+    }
+    /// Returns temperature rounded to millidegrees
+    pub fn from_celsius(celsius: f64) -> Self {
+        Self::from_millicelsius((celsius * MILLIS).round() as i32)
+    }
+    /// Returns temperature of `value` measured in `unit`
+    pub fn from_unit(value: f64, unit: TemperatureUnit) -> Self {
+        Self::from_celsius(match unit {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            TemperatureUnit::Kelvin => value + ABSOLUTE_ZERO,
+        })
+    }
+    pub fn millicelsius(&self) -> i32 {
+        self.millicelsius
+    }
+    pub fn celsius(&self) -> f64 {
+        self.millicelsius as f64 / MILLIS
+    }
+    /// Returns temperature value in `unit`
+    pub fn value_in(&self, unit: TemperatureUnit) -> f64 {
+        let celsius = self.celsius();
+        match unit {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TemperatureUnit::Kelvin => celsius - ABSOLUTE_ZERO,
+        }
+    }
+}
+
+/// Whole degrees Celsius
+impl From<i32> for Temperature {
+    fn from(celsius: i32) -> Self {
+        Self::from_millicelsius(celsius.saturating_mul(1000))
+    }
+}
+
+impl From<f64> for Temperature {
+    fn from(celsius: f64) -> Self {
+        Self::from_celsius(celsius)
+    }
+}
+
+impl From<Temperature> for f64 {
+    fn from(temperature: Temperature) -> Self {
+        temperature.celsius()
+    }
+}
+
+/// Formats degrees Celsius with one decimal unless precision is given, e.g. `{:.2}`
+impl Display for Temperature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display = TemperatureDisplay::default();
+        let precision = f.precision().unwrap_or(display.precision);
+        write!(f, "{}", display.with_precision(precision).format(*self))
+    }
+}
+
+/// Linear correction of raw sensor temperature: `raw * scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "CalibrationFields", into = "CalibrationFields")]
+pub struct Calibration {
+    /// Added after scaling
+    offset: Temperature,
+    /// Scale in millionths
+    scale_micros: i64,
+}
+
+/// Serialized form of calibration
+#[derive(Serialize, Deserialize)]
+struct CalibrationFields {
+    offset: Temperature,
+    scale: f64,
+}
+
+impl Calibration {
+    /// Returns calibration with offset in degrees Celsius and scale rounded to millionths
+    pub fn new(offset: f64, scale: f64) -> Self {
+        Self {
+            offset: Temperature::from_celsius(offset),
+            scale_micros: (scale * 1_000_000.0).round() as i64,
+        }
+    }
+    /// Returns calibration not changing temperatures
+    pub fn identity() -> Self {
+        Self::new(0.0, 1.0)
+    }
+    pub fn offset(&self) -> Temperature {
+        self.offset
+    }
+    pub fn scale(&self) -> f64 {
+        self.scale_micros as f64 / 1_000_000.0
+    }
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+    /// Returns calibrated temperature
+    pub fn apply(&self, raw: Temperature) -> Temperature {
+        let scaled =
+            (raw.millicelsius() as i64).saturating_mul(self.scale_micros) as f64 / 1_000_000.0;
+        Temperature::from_millicelsius(
+            (scaled.round() as i32).saturating_add(self.offset.millicelsius()),
+        )
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<CalibrationFields> for Calibration {
+    fn from(fields: CalibrationFields) -> Self {
+        Self {
+            offset: fields.offset,
+            ..Self::new(0.0, fields.scale)
+        }
+    }
+}
+
+impl From<Calibration> for CalibrationFields {
+    fn from(calibration: Calibration) -> Self {
+        Self {
+            offset: calibration.offset,
+            scale: calibration.scale(),
+        }
+    }
+}
+
+impl Display for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "x{} {:+.3}", self.scale(), self.offset.celsius())
+    }
+}
+
+/// Unit and number of decimals used to show temperatures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemperatureDisplay {
+    pub unit: TemperatureUnit,
+    /// Digits after decimal point, at most 3
+    pub precision: usize,
+}
+
+impl TemperatureDisplay {
+    pub fn new(unit: TemperatureUnit) -> Self {
+        Self {
+            unit,
+            ..Self::default()
+        }
+    }
+    /// Sets digits after decimal point, capped to millidegrees
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = precision.min(3);
+        self
+    }
+    /// Returns temperature value rounded to precision, e.g. `70.3`
+    pub fn value(&self, temperature: Temperature) -> String {
+        format!(
+            "{:.*}",
+            self.precision.min(3),
+            temperature.value_in(self.unit)
+        )
+    }
+    /// Returns temperature value with unit, e.g. `70.3 °F`
+    pub fn format(&self, temperature: Temperature) -> String {
+        format!("{} {}", self.value(temperature), self.unit)
+    }
+}
+
+impl Default for TemperatureDisplay {
+    fn default() -> Self {
+        Self {
+            unit: TemperatureUnit::Celsius,
+            precision: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_conversions() {
+        let boiling = Temperature::from(100);
+        assert_eq!(boiling.value_in(TemperatureUnit::Fahrenheit), 212.0);
+        assert_eq!(boiling.value_in(TemperatureUnit::Kelvin), 373.15);
+        assert_eq!(
+            Temperature::from_unit(-40.0, TemperatureUnit::Fahrenheit),
+            Temperature::from(-40)
+        );
+        assert_eq!(
+            Temperature::from_unit(0.0, TemperatureUnit::Kelvin).millicelsius(),
+            -273_150
+        );
+        assert_eq!("°f".parse(), Ok(TemperatureUnit::Fahrenheit));
+        assert_eq!("Kelvin".parse(), Ok(TemperatureUnit::Kelvin));
+        assert!("rankine".parse::<TemperatureUnit>().is_err());
+    }
+    #[test]
+    fn formatting() {
+        let temperature = Temperature::from_celsius(21.4567);
+        assert_eq!(temperature.to_string(), "21.5 °C");
+        assert_eq!(format!("{:.3}", temperature), "21.457 °C");
+        let display = TemperatureDisplay::new(TemperatureUnit::Fahrenheit).with_precision(2);
+        assert_eq!(display.format(temperature), "70.62 °F");
+        assert_eq!(
+            TemperatureDisplay::new(TemperatureUnit::Kelvin)
+                .with_precision(0)
+                .format(temperature),
+            "295 K"
+        );
+    }
+    #[test]
+    fn calibration() {
+        let calibration = Calibration::new(-0.5, 1.02);
+        assert_eq!(
+            calibration.apply(Temperature::from(20)),
+            Temperature::from_celsius(19.9)
+        );
+        assert_eq!(
+            Calibration::identity().apply(Temperature::from_celsius(-3.21)),
+            Temperature::from_celsius(-3.21)
+        );
+        assert_eq!(
+            Calibration::new(1.0, 1.0).apply(Temperature::from_millicelsius(i32::MAX)),
+            Temperature::from_millicelsius(i32::MAX)
+        );
+        let json = serde_json::to_string(&calibration).unwrap();
+        assert_eq!(json, r#"{"offset":-0.5,"scale":1.02}"#);
+        assert_eq!(
+            serde_json::from_str::<Calibration>(&json).unwrap(),
+            calibration
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    device::{Device, DeviceInfo, DeviceState},
+    errors::{home_errors::HomeErrors, room_errors::RoomErrors},
+    home::Home,
+    room::Room,
+};
+
+/// Result of a single transaction operation
+pub type OperationResult = Result<(), HomeErrors>;
+
+/// Operation queued in a transaction
+#[derive(Debug)]
+pub enum Operation<'a> {
+    AddRoom(String),
+    RemoveRoom(String),
+    AddDevice {
+        room_name: String,
+        device: &'a mut dyn Device,
+    },
+    RemoveDevice(DeviceInfo),
+    TurnOn(DeviceInfo),
+    TurnOff(DeviceInfo),
+}
+
+/// Transaction struct
+///
+/// Queues home operations which are applied by `Home::commit`
+/// all together or not at all
+#[derive(Debug, Default)]
+pub struct Transaction<'a> {
+    /// Queued operations
+    operations: Vec<Operation<'a>>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Returns new empty transaction
+    pub fn new() -> Self {
+        Self { operations: vec![] }
+    }
+    /// Queues room adding
+    pub fn add_room(&mut self, room_name: &str) -> &mut Self {
+        self.push(Operation::AddRoom(room_name.to_string()))
+    }
+    /// Queues room removing
+    pub fn remove_room(&mut self, room_name: &str) -> &mut Self {
+        self.push(Operation::RemoveRoom(room_name.to_string()))
+    }
+    /// Queues device adding
+    pub fn add_device(&mut self, room_name: &str, device: &'a mut dyn Device) -> &mut Self {
+        self.push(Operation::AddDevice {
+            room_name: room_name.to_string(),
+            device,
+        })
+    }
+    /// Queues device removing
+    pub fn remove_device(&mut self, device_info: &DeviceInfo) -> &mut Self {
+        self.push(Operation::RemoveDevice(device_info.clone()))
+    }
+    /// Queues turning on a device
+    pub fn turn_on(&mut self, device_info: &DeviceInfo) -> &mut Self {
+        self.push(Operation::TurnOn(device_info.clone()))
+    }
+    /// Queues turning off a device
+    pub fn turn_off(&mut self, device_info: &DeviceInfo) -> &mut Self {
+        self.push(Operation::TurnOff(device_info.clone()))
+    }
+    /// Queues any operation
+    pub fn push(&mut self, operation: Operation<'a>) -> &mut Self {
+        self.operations.push(operation);
+        self
+    }
+    /// Returns queued operations
+    pub fn operations(&self) -> &[Operation<'a>] {
+        &self.operations
+    }
+    /// Returns number of queued operations
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+    /// Returns `true` if nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+    /// Checks queued operations one after another against `home`
+    /// without changing it
+    ///
+    /// Failed operation does not affect the following ones
+    pub fn validate(&self, home: &Home) -> Vec<OperationResult> {
+        let mut rooms: Vec<(String, Vec<String>)> = home
+            .get_room_names()
+            .into_iter()
+            .map(|r| {
+                let devices = home
+                    .get_devices_in_room(r)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|d| d.to_string())
+                    .collect();
+                (r.to_string(), devices)
+            })
+            .collect();
+        self.operations
+            .iter()
+            .map(|op| Self::validate_operation(&mut rooms, op))
+            .collect()
+    }
+    /// Checks one operation against room model and updates the model
+    fn validate_operation(
+        rooms: &mut Vec<(String, Vec<String>)>,
+        operation: &Operation,
+    ) -> OperationResult {
+        let room_index = |rooms: &Vec<(String, Vec<String>)>, room_name: &str| {
+            rooms
+                .iter()
+                .position(|(r, _)| r == room_name)
+                .ok_or(HomeErrors::RoomNameDoesNotExist(room_name.to_string()))
+        };
+        let device_index = |devices: &Vec<String>, device_name: &str| {
+            devices
+                .iter()
+                .position(|d| d == device_name)
+                .ok_or(HomeErrors::InternalError(
+                    RoomErrors::DeviceNameDoesNotExist(device_name.to_string()),
+                ))
+        };
+        match operation {
+            Operation::AddRoom(room_name) => {
+                if room_index(rooms, room_name).is_ok() {
+                    return Err(HomeErrors::RoomNameExists(room_name.to_string()));
+                }
+                rooms.push((room_name.to_string(), vec![]));
+            }
+            Operation::RemoveRoom(room_name) => {
+                let index = room_index(rooms, room_name)?;
+                rooms.remove(index);
+            }
+            Operation::AddDevice { room_name, device } => {
+                let index = room_index(rooms, room_name)?;
+                let devices = &mut rooms[index].1;
+                if device_index(devices, device.name()).is_ok() {
+                    return Err(HomeErrors::InternalError(RoomErrors::DeviceNameExists(
+                        device.name().to_string(),
+                    )));
+                }
+                devices.push(device.name().to_string());
+            }
+            Operation::RemoveDevice(device_info) => {
+                let index = room_index(rooms, &device_info.room_name)?;
+                let devices = &mut rooms[index].1;
+                let index = device_index(devices, &device_info.device_name)?;
+                devices.remove(index);
+            }
+            Operation::TurnOn(device_info) | Operation::TurnOff(device_info) => {
+                let index = room_index(rooms, &device_info.room_name)?;
+                device_index(&rooms[index].1, &device_info.device_name)?;
+            }
+        }
+        Ok(())
+    }
+    /// Takes queued operations
+    pub(crate) fn into_operations(self) -> Vec<Operation<'a>> {
+        self.operations
+    }
+}
+
+/// Operation reverting an applied transaction operation
+#[derive(Debug)]
+pub(crate) enum UndoOperation<'a> {
+    RemoveRoom(String),
+    RestoreRoom {
+        index: usize,
+        room: Room<'a>,
+    },
+    RemoveDevice(DeviceInfo),
+    RestoreDevice {
+        room_name: String,
+        index: usize,
+        device: &'a mut dyn Device,
+        /// Number of state changes counted before removal
+        state_changes: u64,
+    },
+    SetState {
+        device_info: DeviceInfo,
+        state: DeviceState,
+    },
+}
@@ -0,0 +1,13 @@
+use crate::{device::Device, room::RoomView};
+
+/// Visitor walking rooms and devices of a home in order
+///
+/// Every method does nothing by default
+pub trait HomeVisitor {
+    /// Called before devices of the room
+    fn visit_room(&mut self, _room: RoomView) {}
+    /// Called for every device of the room
+    fn visit_device(&mut self, _room_name: &str, _device: &dyn Device) {}
+    /// Called after devices of the room
+    fn leave_room(&mut self, _room: RoomView) {}
+}
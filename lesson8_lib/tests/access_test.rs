@@ -0,0 +1,149 @@
+use lesson8_lib::*;
+
+const HOME_NAME: &str = "home";
+const KITCHEN: &str = "kitchen";
+const GARAGE: &str = "garage";
+const DEVICE_NAME: &str = "socket";
+
+fn access_control() -> AccessControl {
+    let mut access_control = AccessControl::new();
+    access_control
+        .add_role(Role::new("admin").grant(Scope::Home, Permission::Administer))
+        .unwrap();
+    access_control
+        .add_role(Role::new("cook").grant(Scope::Room(KITCHEN.to_string()), Permission::Control))
+        .unwrap();
+    access_control
+        .add_role(Role::new("guest").grant(
+            Scope::Device(DeviceInfo::new(DEVICE_NAME, GARAGE)),
+            Permission::View,
+        ))
+        .unwrap();
+    access_control
+        .add_user(User::new("alice").with_role("admin"))
+        .unwrap();
+    access_control
+        .add_user(User::new("bob").with_role("cook"))
+        .unwrap();
+    access_control
+        .add_user(User::new("eve").with_role("guest"))
+        .unwrap();
+    access_control
+}
+
+#[test]
+fn add_user_with_unknown_role() {
+    let mut access_control = access_control();
+    assert!(matches!(
+        access_control.add_user(User::new("mallory").with_role("root")),
+        Err(AccessErrors::RoleNameDoesNotExist(_))
+    ));
+    assert!(matches!(
+        access_control.add_user(User::new("alice")),
+        Err(AccessErrors::UserNameExists(_))
+    ));
+}
+#[test]
+fn unknown_user_cannot_act() {
+    let mut home = Home::new(HOME_NAME).with_access_control(access_control());
+    assert!(matches!(
+        home.acting_as("mallory"),
+        Err(HomeErrors::AccessError(_))
+    ));
+}
+#[test]
+fn permissions_are_enforced() {
+    let mut kitchen_socket = Socket::new(DEVICE_NAME);
+    let mut garage_socket = Socket::new(DEVICE_NAME);
+    let kitchen_info = DeviceInfo::new(DEVICE_NAME, KITCHEN);
+    let garage_info = DeviceInfo::new(DEVICE_NAME, GARAGE);
+    let mut home = Home::new(HOME_NAME).with_access_control(access_control());
+
+    let mut alice = home.acting_as("alice").unwrap();
+    assert!(alice.add_room(KITCHEN).is_ok());
+    assert!(alice.add_room(GARAGE).is_ok());
+    assert!(alice.add_device(KITCHEN, &mut kitchen_socket).is_ok());
+    assert!(alice.add_device(GARAGE, &mut garage_socket).is_ok());
+    assert!(alice.get_home_report().is_ok());
+
+    let mut bob = home.acting_as("bob").unwrap();
+    assert!(bob.turn_on(&kitchen_info).is_ok());
+    assert!(bob.get_device_report(&kitchen_info).is_ok());
+    assert_eq!(bob.get_room_names(), vec![KITCHEN]);
+    assert!(matches!(
+        bob.turn_on(&garage_info),
+        Err(HomeErrors::PermissionDenied(_))
+    ));
+    assert!(bob.remove_room(KITCHEN).is_err());
+    assert!(bob.get_home_report().is_err());
+
+    let mut eve = home.acting_as("eve").unwrap();
+    assert!(eve.get_device_report(&garage_info).is_ok());
+    assert!(eve.turn_off(&garage_info).is_err());
+    assert!(eve.get_devices_in_room(GARAGE).is_err());
+
+    assert_eq!(home.get_room_names(), vec![KITCHEN, GARAGE]);
+    let denied = home.access_control().denied_attempts();
+    assert_eq!(denied.len(), 5);
+    assert_eq!(denied[0].user_name, "bob");
+    assert_eq!(denied[0].action, "turn_on");
+    assert_eq!(denied[0].scope, Scope::Device(garage_info.clone()));
+    assert_eq!(denied[0].permission, Permission::Control);
+    assert_eq!(
+        denied[0].to_string(),
+        "User bob has no Control permission for device garage/socket to turn_on"
+    );
+}
+#[test]
+fn transaction_requires_all_permissions() {
+    let mut socket = Socket::new(DEVICE_NAME);
+    let mut home = Home::new(HOME_NAME).with_access_control(access_control());
+    let mut alice = home.acting_as("alice").unwrap();
+    alice.add_room(KITCHEN).unwrap();
+    alice.add_device(KITCHEN, &mut socket).unwrap();
+
+    let mut transaction = Transaction::new();
+    transaction
+        .turn_on(&DeviceInfo::new(DEVICE_NAME, KITCHEN))
+        .add_room(GARAGE);
+    let mut bob = home.acting_as("bob").unwrap();
+    assert!(matches!(
+        bob.commit(transaction),
+        Err(HomeErrors::PermissionDenied(_))
+    ));
+    assert_eq!(home.get_room_names(), vec![KITCHEN]);
+    assert!(home
+        .acting_as("alice")
+        .unwrap()
+        .get_device_report(&DeviceInfo::new(DEVICE_NAME, KITCHEN))
+        .unwrap()
+        .contains("state: Off"));
+}
+#[test]
+fn roles_are_administered_by_users() {
+    let mut home = Home::new(HOME_NAME).with_access_control(access_control());
+    assert!(home.acting_as(OWNER).is_ok());
+
+    let mut bob = home.acting_as("bob").unwrap();
+    assert!(matches!(
+        bob.add_role(Role::new("root").grant(Scope::Home, Permission::Administer)),
+        Err(HomeErrors::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        bob.add_user(User::new("bob2").with_role("admin")),
+        Err(HomeErrors::PermissionDenied(_))
+    ));
+    assert!(bob.remove_user("alice").is_err());
+
+    let mut alice = home.acting_as("alice").unwrap();
+    assert!(alice
+        .add_role(Role::new("mechanic").grant(Scope::Room(GARAGE.to_string()), Permission::Control))
+        .is_ok());
+    assert!(alice
+        .add_user(User::new("carol").with_role("mechanic"))
+        .is_ok());
+    assert!(alice.remove_user("eve").is_ok());
+    assert!(home.acting_as("carol").is_ok());
+    assert!(home.acting_as("eve").is_err());
+    assert_eq!(home.access_control().denied_attempts().len(), 3);
+}
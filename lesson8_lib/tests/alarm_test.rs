@@ -0,0 +1,198 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use lesson8_lib::*;
+
+const ROOM_NAME: &str = "server_room";
+const DEVICE_NAME: &str = "thermo1";
+
+/// Device with reading set by test
+#[derive(Debug)]
+struct FixedThermometer {
+    name: String,
+    state: DeviceState,
+    temperature: i32,
+}
+
+impl Display for FixedThermometer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.temperature)
+    }
+}
+
+impl Device for FixedThermometer {
+    fn turn_on(&mut self) {
+        self.state = DeviceState::On;
+    }
+    fn turn_off(&mut self) {
+        self.state = DeviceState::Off;
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+    fn reading(&self) -> Option<Reading> {
+        match self.state {
+            DeviceState::On => Some(Reading::Temperature(self.temperature.into())),
+            DeviceState::Off => None,
+        }
+    }
+}
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn alarm_lifecycle_over_home() {
+    let mut thermo = FixedThermometer {
+        name: DEVICE_NAME.to_string(),
+        state: DeviceState::On,
+        temperature: 35,
+    };
+    let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
+    let mut home = Home::new("home");
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room(ROOM_NAME).unwrap();
+    owner.add_device(ROOM_NAME, &mut thermo).unwrap();
+
+    let mut manager = AlarmManager::new();
+    manager
+        .add_alarm(Alarm::above("hot", device_info.clone(), 30).lasting(Duration::from_secs(120)))
+        .unwrap();
+    assert!(manager
+        .add_alarm(Alarm::above("hot", device_info.clone(), 40))
+        .is_err());
+
+    assert!(manager.evaluate_at(&home, at(0)).is_empty());
+    assert!(manager.evaluate_at(&home, at(60)).is_empty());
+    let events = manager.evaluate_at(&home, at(121));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, AlarmEventKind::Raised);
+    assert_eq!(events[0].reading, Some(Reading::Temperature(35.into())));
+    assert_eq!(
+        events[0].to_string(),
+        "[Raised] hot: server_room/thermo1 temperature 35.0 °C"
+    );
+    assert_eq!(manager.active_alarms().len(), 1);
+
+    let event = manager.acknowledge("hot").unwrap();
+    assert_eq!(event.kind, AlarmEventKind::Acknowledged);
+    assert!(matches!(
+        manager.get_alarm("hot").unwrap().state(),
+        AlarmState::Acknowledged { .. }
+    ));
+    assert!(manager.acknowledge("hot").is_err());
+    assert!(manager.acknowledge("missing").is_err());
+
+    let events =
+        manager.evaluate_reading(&device_info, Some(Reading::Temperature(20.into())), at(200));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, AlarmEventKind::Cleared);
+    assert!(manager.active_alarms().is_empty());
+}
+#[test]
+fn log_file_sink() {
+    let path = std::env::temp_dir().join(format!("lesson8_alarm_{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let device_info = DeviceInfo::new("socket1", ROOM_NAME);
+    let mut manager = AlarmManager::new();
+    manager.add_sink(Box::new(LogFileSink::new(&path)));
+    manager
+        .add_alarm(Alarm::above("power", device_info.clone(), 90).with_hysteresis(10))
+        .unwrap();
+    manager.evaluate_reading(&device_info, Some(Reading::Power(95)), at(0));
+    manager.evaluate_reading(&device_info, Some(Reading::Power(85)), at(1));
+    manager.evaluate_reading(&device_info, Some(Reading::Power(70)), at(2));
+    assert!(manager.sink_errors().is_empty());
+    let log = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        log,
+        "[Raised] power: server_room/socket1 power 95\n[Cleared] power: server_room/socket1 power 70\n"
+    );
+}
+#[test]
+fn webhook_sink() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+        (request_line, String::from_utf8(body).unwrap())
+    });
+
+    let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
+    let mut manager = AlarmManager::new();
+    manager.add_sink(Box::new(WebhookSink::new(&address, "/alarms")));
+    manager
+        .add_alarm(Alarm::below("cold", device_info.clone(), 5))
+        .unwrap();
+    let events =
+        manager.evaluate_reading(&device_info, Some(Reading::Temperature(2.into())), at(0));
+    assert!(manager.sink_errors().is_empty());
+
+    let (request_line, body) = server.join().unwrap();
+    assert_eq!(request_line, "POST /alarms HTTP/1.1\r\n");
+    let event: AlarmEvent = serde_json::from_str(&body).unwrap();
+    assert_eq!(event, events[0]);
+}
+#[test]
+fn failed_sink_is_reported() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
+    let mut manager = AlarmManager::new();
+    manager.add_sink(Box::new(WebhookSink::new(&address, "/alarms")));
+    manager.add_sink(Box::new(StdoutSink));
+    manager
+        .add_alarm(Alarm::above("hot", device_info.clone(), 30))
+        .unwrap();
+    let events =
+        manager.evaluate_reading(&device_info, Some(Reading::Temperature(31.into())), at(0));
+    assert_eq!(events.len(), 1);
+    assert_eq!(manager.sink_errors().len(), 1);
+}
+#[test]
+fn silent_webhook_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
+    let mut manager = AlarmManager::new();
+    let sink = WebhookSink::new(&address, "/alarms").with_timeout(Duration::from_millis(100));
+    manager.add_sink(Box::new(sink));
+    manager
+        .add_alarm(Alarm::above("hot", device_info.clone(), 30))
+        .unwrap();
+    // connection is accepted by backlog but never answered
+    manager.evaluate_reading(&device_info, Some(Reading::Temperature(31.into())), at(0));
+    assert_eq!(manager.sink_errors().len(), 1);
+    drop(listener);
+}
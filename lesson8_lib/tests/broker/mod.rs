@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufReader},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use lesson8_lib::{topic_matches, MqttMessage, MqttPacket, MAX_PACKET};
+
+/// Connection of a client, packets are written by one thread at a time
+type Connection = Arc<Mutex<TcpStream>>;
+
+/// Connected client
+#[derive(Debug)]
+struct Session {
+    connection: Connection,
+    filters: Vec<String>,
+}
+
+/// State shared by connection threads
+#[derive(Debug, Default)]
+struct BrokerState {
+    sessions: HashMap<usize, Session>,
+    /// The last retained message by topic
+    retained: BTreeMap<String, MqttMessage>,
+}
+
+impl BrokerState {
+    /// Stores retained message, returns connections of subscribers
+    /// and message forwarded to them
+    fn route(&mut self, message: MqttMessage) -> (Vec<Connection>, MqttPacket) {
+        if message.retain {
+            if message.payload.is_empty() {
+                self.retained.remove(&message.topic);
+            } else {
+                self.retained.insert(message.topic.clone(), message.clone());
+            }
+        }
+        let subscribers = self
+            .sessions
+            .values()
+            .filter(|s| s.filters.iter().any(|f| topic_matches(f, &message.topic)))
+            .map(|s| s.connection.clone())
+            .collect();
+        let forwarded = MqttPacket::Publish(MqttMessage {
+            retain: false,
+            ..message
+        });
+        (subscribers, forwarded)
+    }
+}
+
+/// Writes packets into connection, errors are left to its reading thread
+fn send(connection: &Connection, packets: &[MqttPacket]) {
+    let mut stream = connection.lock().unwrap();
+    for packet in packets {
+        let _ = packet.write(&mut *stream);
+    }
+}
+
+/// Minimal in-process MQTT broker used by tests
+///
+/// Supports QoS 0, retained messages and last will. Sessions are not persisted.
+/// Packets are written after the shared state is unlocked, so a slow subscriber
+/// does not block other clients. Stops accepting connections and disconnects
+/// clients when dropped
+#[derive(Debug)]
+pub struct MqttBroker {
+    address: SocketAddr,
+    state: Arc<Mutex<BrokerState>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl MqttBroker {
+    /// Starts broker listening on `address`, port 0 picks a free port
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let broker = Self {
+            address: listener.local_addr()?,
+            state: Arc::new(Mutex::new(BrokerState::default())),
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        let state = broker.state.clone();
+        let stopped = broker.stopped.clone();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let state = state.clone();
+                thread::spawn(move || Self::serve(id, stream, state));
+            }
+        });
+        Ok(broker)
+    }
+    /// Returns address the broker listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+    /// Returns retained message of topic
+    pub fn retained(&self, topic: &str) -> Option<MqttMessage> {
+        self.state.lock().unwrap().retained.get(topic).cloned()
+    }
+    /// Handles client connection until it is closed
+    fn serve(id: usize, stream: TcpStream, state: Arc<Mutex<BrokerState>>) {
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        let mut reader = BufReader::new(stream);
+        let will = match MqttPacket::read(&mut reader, MAX_PACKET) {
+            Ok(MqttPacket::Connect { will, .. }) => will,
+            _ => return,
+        };
+        let connack = MqttPacket::ConnAck {
+            session_present: false,
+            return_code: 0,
+        };
+        if connack.write(&mut writer).is_err() {
+            return;
+        }
+        let connection = Arc::new(Mutex::new(writer));
+        state.lock().unwrap().sessions.insert(
+            id,
+            Session {
+                connection: connection.clone(),
+                filters: vec![],
+            },
+        );
+
+        let graceful = loop {
+            let packet = match MqttPacket::read(&mut reader, MAX_PACKET) {
+                Ok(packet) => packet,
+                Err(_) => break false,
+            };
+            match packet {
+                MqttPacket::Publish(message) => {
+                    let (subscribers, forwarded) = state.lock().unwrap().route(message);
+                    for subscriber in &subscribers {
+                        send(subscriber, std::slice::from_ref(&forwarded));
+                    }
+                }
+                MqttPacket::Subscribe { packet_id, filters } => {
+                    let mut packets = vec![MqttPacket::SubAck {
+                        packet_id,
+                        return_codes: vec![0; filters.len()],
+                    }];
+                    {
+                        let mut state = state.lock().unwrap();
+                        packets.extend(
+                            state
+                                .retained
+                                .values()
+                                .filter(|m| filters.iter().any(|f| topic_matches(f, &m.topic)))
+                                .cloned()
+                                .map(MqttPacket::Publish),
+                        );
+                        let Some(session) = state.sessions.get_mut(&id) else {
+                            break false;
+                        };
+                        session.filters.extend(filters);
+                    }
+                    send(&connection, &packets);
+                }
+                MqttPacket::PingReq => send(&connection, &[MqttPacket::PingResp]),
+                MqttPacket::Disconnect => break true,
+                _ => break false,
+            }
+        };
+
+        let route = {
+            let mut state = state.lock().unwrap();
+            state.sessions.remove(&id);
+            match (graceful, will) {
+                (false, Some(will)) => Some(state.route(will)),
+                _ => None,
+            }
+        };
+        let _ = connection.lock().unwrap().shutdown(Shutdown::Both);
+        if let Some((subscribers, forwarded)) = route {
+            for subscriber in &subscribers {
+                send(subscriber, std::slice::from_ref(&forwarded));
+            }
+        }
+    }
+}
+
+impl Drop for MqttBroker {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wakes up accepting thread
+        let _ = TcpStream::connect(self.address);
+        let connections: Vec<Connection> = self
+            .state
+            .lock()
+            .unwrap()
+            .sessions
+            .values()
+            .map(|s| s.connection.clone())
+            .collect();
+        for connection in connections {
+            let _ = connection.lock().unwrap().shutdown(Shutdown::Both);
+        }
+    }
+}
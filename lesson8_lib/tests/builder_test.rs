@@ -0,0 +1,76 @@
+use lesson8_lib::*;
+
+#[test]
+fn build_home() {
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let mut heater = Socket::new("heater");
+    let home = Home::builder("home")
+        .room("kitchen")
+        .device(&mut socket)
+        .device(&mut thermo)
+        .room("garage")
+        .device(&mut heater)
+        .room("attic")
+        .build()
+        .unwrap();
+    assert_eq!(home.name(), "home");
+    assert_eq!(home.get_room_names(), vec!["kitchen", "garage", "attic"]);
+    assert_eq!(
+        home.get_devices_in_room("kitchen").unwrap(),
+        vec!["socket", "thermo"]
+    );
+    assert_eq!(home.get_devices_in_room("garage").unwrap(), vec!["heater"]);
+    assert!(home.get_devices_in_room("attic").unwrap().is_empty());
+    assert!(!home.can_undo());
+}
+#[test]
+fn build_reports_all_errors() {
+    let mut orphan = Socket::new("orphan");
+    let mut first = Socket::new("socket");
+    let mut second = Socket::new("socket");
+    let errors = HomeBuilder::new("home")
+        .device(&mut orphan)
+        .room("kitchen")
+        .device(&mut first)
+        .device(&mut second)
+        .room("kitchen")
+        .build()
+        .unwrap_err();
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        vec![
+            "Device with name orphan is not placed in any room!",
+            "Device with name socket already exists!",
+            "Room with name kitchen already exists!",
+        ]
+    );
+}
+#[test]
+fn home_macro() {
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let home = home! {
+        "home" => {
+            "kitchen" => [&mut socket],
+            "bedroom" => [&mut thermo,],
+            "garage" => [],
+        }
+    }
+    .unwrap();
+    assert_eq!(home.get_room_names(), vec!["kitchen", "bedroom", "garage"]);
+    assert_eq!(home.get_devices_in_room("bedroom").unwrap(), vec!["thermo"]);
+
+    let mut first = Socket::new("socket");
+    let mut second = Thermometer::new("socket");
+    let errors = home! {
+        "home" => {
+            "kitchen" => [&mut first, &mut second],
+            "kitchen" => []
+        }
+    }
+    .unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(home! { "empty" => {} }.unwrap().get_room_names().is_empty());
+}
@@ -0,0 +1,246 @@
+use lesson8_lib::*;
+use proptest::prelude::*;
+
+const ROOM_NAMES: [&str; 3] = ["a", "b", "c"];
+const DEVICE_NAMES: [&str; 3] = ["x", "y", "z"];
+
+/// Home operation generated by proptest
+#[derive(Debug, Clone)]
+enum Op {
+    AddRoom(&'static str),
+    RemoveRoom(&'static str),
+    AddDevice(&'static str, &'static str),
+    RemoveDevice(&'static str, &'static str),
+    TurnOn(&'static str, &'static str),
+    TurnOff(&'static str, &'static str),
+}
+
+impl Op {
+    /// Name of device the operation adds
+    fn new_device_name(&self) -> &'static str {
+        match self {
+            Op::AddDevice(_, device) => device,
+            _ => "unused",
+        }
+    }
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    let room = prop::sample::select(&ROOM_NAMES[..]);
+    let device = prop::sample::select(&DEVICE_NAMES[..]);
+    prop_oneof![
+        room.clone().prop_map(Op::AddRoom),
+        room.clone().prop_map(Op::RemoveRoom),
+        (room.clone(), device.clone()).prop_map(|(r, d)| Op::AddDevice(r, d)),
+        (room.clone(), device.clone()).prop_map(|(r, d)| Op::RemoveDevice(r, d)),
+        (room.clone(), device.clone()).prop_map(|(r, d)| Op::TurnOn(r, d)),
+        (room, device).prop_map(|(r, d)| Op::TurnOff(r, d)),
+    ]
+}
+
+/// Expected error of an operation
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Ok,
+    RoomExists,
+    RoomDoesNotExist,
+    DeviceExists,
+    DeviceDoesNotExist,
+}
+
+impl Expected {
+    fn of(result: &Result<(), HomeErrors>) -> Self {
+        match result {
+            Ok(()) => Expected::Ok,
+            Err(HomeErrors::RoomNameExists(_)) => Expected::RoomExists,
+            Err(HomeErrors::RoomNameDoesNotExist(_)) => Expected::RoomDoesNotExist,
+            Err(HomeErrors::InternalError(RoomErrors::DeviceNameExists(_))) => {
+                Expected::DeviceExists
+            }
+            Err(HomeErrors::InternalError(RoomErrors::DeviceNameDoesNotExist(_))) => {
+                Expected::DeviceDoesNotExist
+            }
+            Err(e) => panic!("unexpected error {e}"),
+        }
+    }
+}
+
+/// Reference model: rooms in order with devices in order
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Model {
+    rooms: Vec<(String, Vec<(String, DeviceState)>)>,
+}
+
+impl Model {
+    fn room(&mut self, room: &str) -> Option<&mut Vec<(String, DeviceState)>> {
+        self.rooms
+            .iter_mut()
+            .find(|(r, _)| r == room)
+            .map(|(_, devices)| devices)
+    }
+    fn apply(&mut self, op: &Op) -> Expected {
+        match op {
+            Op::AddRoom(room) => {
+                if self.room(room).is_some() {
+                    return Expected::RoomExists;
+                }
+                self.rooms.push((room.to_string(), vec![]));
+            }
+            Op::RemoveRoom(room) => {
+                if self.room(room).is_none() {
+                    return Expected::RoomDoesNotExist;
+                }
+                self.rooms.retain(|(r, _)| r != room);
+            }
+            Op::AddDevice(room, device) => {
+                let Some(devices) = self.room(room) else {
+                    return Expected::RoomDoesNotExist;
+                };
+                if devices.iter().any(|(d, _)| d == device) {
+                    return Expected::DeviceExists;
+                }
+                devices.push((device.to_string(), DeviceState::Off));
+            }
+            Op::RemoveDevice(room, device) => {
+                let Some(devices) = self.room(room) else {
+                    return Expected::RoomDoesNotExist;
+                };
+                if !devices.iter().any(|(d, _)| d == device) {
+                    return Expected::DeviceDoesNotExist;
+                }
+                devices.retain(|(d, _)| d != device);
+            }
+            Op::TurnOn(room, device) | Op::TurnOff(room, device) => {
+                let Some(devices) = self.room(room) else {
+                    return Expected::RoomDoesNotExist;
+                };
+                let Some((_, state)) = devices.iter_mut().find(|(d, _)| d == device) else {
+                    return Expected::DeviceDoesNotExist;
+                };
+                *state = match op {
+                    Op::TurnOn(..) => DeviceState::On,
+                    _ => DeviceState::Off,
+                };
+            }
+        }
+        Expected::Ok
+    }
+    /// Builds model from home state
+    fn of(home: &Home) -> Self {
+        Self {
+            rooms: home
+                .snapshot()
+                .rooms
+                .into_iter()
+                .map(|r| {
+                    let devices = r.devices.into_iter().map(|d| (d.name, d.state)).collect();
+                    (r.name, devices)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Checks that every home accessor agrees with the model
+fn check_consistency(home: &mut Home, model: &Model) -> Result<(), TestCaseError> {
+    prop_assert_eq!(&Model::of(home), model);
+    let room_names: Vec<&str> = model.rooms.iter().map(|(r, _)| r.as_str()).collect();
+    prop_assert_eq!(home.get_room_names(), room_names);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    let report = owner.get_home_report().unwrap();
+    for (room, devices) in model.rooms.iter() {
+        let device_names: Vec<&str> = devices.iter().map(|(d, _)| d.as_str()).collect();
+        prop_assert_eq!(owner.get_devices_in_room(room).unwrap(), device_names);
+        let room_header = format!("Room name: {}\n", room);
+        prop_assert!(report.contains(&room_header));
+        for (device, state) in devices.iter() {
+            let device_report = owner.get_device_report(&DeviceInfo::new(device, room));
+            prop_assert!(device_report.is_ok());
+            let device_report = device_report.unwrap();
+            let state_line = format!("state: {}\n", state);
+            prop_assert!(device_report.contains(&state_line));
+        }
+    }
+    Ok(())
+}
+
+/// Applies operation to home
+fn apply<'a>(home: &mut Home<'a>, op: &Op, device: &'a mut Socket) -> Result<(), HomeErrors> {
+    let mut owner = home.acting_as(OWNER).unwrap();
+    match op {
+        Op::AddRoom(room) => owner.add_room(room),
+        Op::RemoveRoom(room) => owner.remove_room(room),
+        Op::AddDevice(room, _) => owner.add_device(room, device),
+        Op::RemoveDevice(room, device) => owner.remove_device(&DeviceInfo::new(device, room)),
+        Op::TurnOn(room, device) => owner.turn_on(&DeviceInfo::new(device, room)),
+        Op::TurnOff(room, device) => owner.turn_off(&DeviceInfo::new(device, room)),
+    }
+}
+
+/// Queues operation into transaction
+fn queue<'a>(transaction: &mut Transaction<'a>, op: &Op, device: &'a mut Socket) {
+    match op {
+        Op::AddRoom(room) => transaction.add_room(room),
+        Op::RemoveRoom(room) => transaction.remove_room(room),
+        Op::AddDevice(room, _) => transaction.add_device(room, device),
+        Op::RemoveDevice(room, device) => transaction.remove_device(&DeviceInfo::new(device, room)),
+        Op::TurnOn(room, device) => transaction.turn_on(&DeviceInfo::new(device, room)),
+        Op::TurnOff(room, device) => transaction.turn_off(&DeviceInfo::new(device, room)),
+    };
+}
+
+/// Devices for every operation, only used by `AddDevice`
+fn device_pool(ops: &[Op]) -> Vec<Socket> {
+    ops.iter()
+        .map(|op| Socket::new(op.new_device_name()))
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn home_matches_model(ops in prop::collection::vec(op_strategy(), 0..40)) {
+        let mut pool = device_pool(&ops);
+        let mut home = Home::new("home");
+        let mut model = Model::default();
+        for (op, device) in ops.iter().zip(pool.iter_mut()) {
+            let result = apply(&mut home, op, device);
+            prop_assert_eq!(Expected::of(&result), model.apply(op), "operation {:?}", op);
+            check_consistency(&mut home, &model)?;
+        }
+    }
+
+    #[test]
+    fn transaction_is_all_or_nothing(
+        setup in prop::collection::vec(op_strategy(), 0..20),
+        batch in prop::collection::vec(op_strategy(), 0..10),
+    ) {
+        let mut setup_pool = device_pool(&setup);
+        let mut batch_pool = device_pool(&batch);
+        let mut home = Home::new("home");
+        let mut model = Model::default();
+        for (op, device) in setup.iter().zip(setup_pool.iter_mut()) {
+            let _ = apply(&mut home, op, device);
+            model.apply(op);
+        }
+        let before = model.clone();
+        let expected: Vec<Expected> = batch.iter().map(|op| model.apply(op)).collect();
+        let is_valid = expected.iter().all(|e| *e == Expected::Ok);
+
+        let mut transaction = Transaction::new();
+        for (op, device) in batch.iter().zip(batch_pool.iter_mut()) {
+            queue(&mut transaction, op, device);
+        }
+        let results = match home.acting_as(OWNER).unwrap().commit(transaction) {
+            Ok(results) => results,
+            Err(HomeErrors::TransactionRejected(results)) => results,
+            Err(e) => panic!("unexpected error {e}"),
+        };
+        let results: Vec<Expected> = results.iter().map(Expected::of).collect();
+        prop_assert_eq!(results, expected);
+        if is_valid {
+            check_consistency(&mut home, &model)?;
+            prop_assert!(home.acting_as(OWNER).unwrap().undo().is_ok());
+        }
+        check_consistency(&mut home, &before)?;
+    }
+}
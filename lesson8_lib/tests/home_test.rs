@@ -6,38 +6,43 @@ const DEVICE_NAME: &str = "dev";
 #[test]
 fn add_room() {
     let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
-    assert_eq!(home.get_room_names().len(), 1);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert_eq!(owner.get_room_names().len(), 1);
 }
 #[test]
 fn add_non_unique_room() {
     let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
-    assert!(home.add_room(ROOM_NAME).is_err());
-    assert_eq!(home.get_room_names().len(), 1);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert!(owner.add_room(ROOM_NAME).is_err());
+    assert_eq!(owner.get_room_names().len(), 1);
 }
 #[test]
 fn remove_room() {
     let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
-    assert_eq!(home.get_room_names().len(), 1);
-    assert!(home.remove_room(ROOM_NAME).is_ok());
-    assert_eq!(home.get_room_names().len(), 0);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert_eq!(owner.get_room_names().len(), 1);
+    assert!(owner.remove_room(ROOM_NAME).is_ok());
+    assert_eq!(owner.get_room_names().len(), 0);
 }
 #[test]
 fn remove_non_existing_room() {
     let mut home = Home::new(HOME_NAME);
-    assert_eq!(home.get_room_names().len(), 0);
-    assert!(home.remove_room(ROOM_NAME).is_err());
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert_eq!(owner.get_room_names().len(), 0);
+    assert!(owner.remove_room(ROOM_NAME).is_err());
 }
 
 #[test]
 fn add_device() {
-    let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
     let mut device = Socket::new(DEVICE_NAME);
-    assert!(home.add_device(ROOM_NAME, &mut device).is_ok());
-    let devices_in_room = home.get_devices_in_room(ROOM_NAME);
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert!(owner.add_device(ROOM_NAME, &mut device).is_ok());
+    let devices_in_room = owner.get_devices_in_room(ROOM_NAME);
     assert!(devices_in_room.is_ok());
     let dev_vec = devices_in_room.unwrap();
     assert_eq!(dev_vec.len(), 1);
@@ -45,29 +50,31 @@ fn add_device() {
 }
 #[test]
 fn add_device_with_existing_name() {
-    let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
     let mut device = Socket::new(DEVICE_NAME);
-    assert!(home.add_device(ROOM_NAME, &mut device).is_ok());
     let mut device_duplicate = Socket::new(DEVICE_NAME);
-    assert!(home.add_device(ROOM_NAME, &mut device_duplicate).is_err());
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert!(owner.add_device(ROOM_NAME, &mut device).is_ok());
+    assert!(owner.add_device(ROOM_NAME, &mut device_duplicate).is_err());
 }
 
 #[test]
 fn remove_device() {
-    let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
     let mut device = Socket::new(DEVICE_NAME);
-    assert!(home.add_device(ROOM_NAME, &mut device).is_ok());
-    let devices_in_room = home.get_devices_in_room(ROOM_NAME);
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    assert!(owner.add_device(ROOM_NAME, &mut device).is_ok());
+    let devices_in_room = owner.get_devices_in_room(ROOM_NAME);
     assert!(devices_in_room.is_ok());
     let dev_vec = devices_in_room.unwrap();
     assert_eq!(dev_vec.len(), 1);
     assert_eq!(dev_vec[0], format!("{}", DEVICE_NAME));
 
     let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
-    assert!(home.remove_device(&device_info).is_ok());
-    let devices_in_room = home.get_devices_in_room(ROOM_NAME);
+    assert!(owner.remove_device(&device_info).is_ok());
+    let devices_in_room = owner.get_devices_in_room(ROOM_NAME);
     assert!(devices_in_room.is_ok());
     let dev_vec = devices_in_room.unwrap();
     assert_eq!(dev_vec.len(), 0);
@@ -75,32 +82,248 @@ fn remove_device() {
 #[test]
 fn remove_non_existing_device() {
     let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
-    let devices_in_room = home.get_devices_in_room(ROOM_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    let devices_in_room = owner.get_devices_in_room(ROOM_NAME);
     assert!(devices_in_room.is_ok());
     let dev_vec = devices_in_room.unwrap();
     assert_eq!(dev_vec.len(), 0);
 
     let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
-    assert!(home.remove_device(&device_info).is_err());
+    assert!(owner.remove_device(&device_info).is_err());
 }
 
 #[test]
 fn turn_on_off_device() {
-    let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
     let mut device = Socket::new(DEVICE_NAME);
-    home.add_device(ROOM_NAME, &mut device).unwrap();
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
+    owner.add_device(ROOM_NAME, &mut device).unwrap();
 
     let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
-    assert!(home.turn_on(&device_info).is_ok());
-    assert!(home.turn_off(&device_info).is_ok());
+    assert!(owner.turn_on(&device_info).is_ok());
+    assert!(owner.turn_off(&device_info).is_ok());
 }
 #[test]
 fn turn_on_off_non_existing_device() {
     let mut home = Home::new(HOME_NAME);
-    assert!(home.add_room(ROOM_NAME).is_ok());
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.add_room(ROOM_NAME).is_ok());
     let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
-    assert!(home.turn_on(&device_info).is_err());
-    assert!(home.turn_off(&device_info).is_err());
+    assert!(owner.turn_on(&device_info).is_err());
+    assert!(owner.turn_off(&device_info).is_err());
+}
+#[test]
+fn query_devices() {
+    let mut kettle = Socket::new("kettle");
+    let mut lamp = Socket::new("lamp");
+    let mut heater = Socket::new("heater");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_room("kitchen_2").unwrap();
+    owner.add_room("garage").unwrap();
+    owner.add_device("kitchen", &mut kettle).unwrap();
+    owner.add_device("kitchen_2", &mut lamp).unwrap();
+    owner.add_device("garage", &mut heater).unwrap();
+    owner
+        .turn_on(&DeviceInfo::new("kettle", "kitchen"))
+        .unwrap();
+    owner.turn_on(&DeviceInfo::new("heater", "garage")).unwrap();
+
+    let query: Query = "state = off or room = kitchen in rooms matching kitchen* sort by device"
+        .parse()
+        .unwrap();
+    let matches: Vec<DeviceInfo> = home.query(&query).iter().map(|m| m.device_info()).collect();
+    assert_eq!(
+        matches,
+        vec![
+            DeviceInfo::new("kettle", "kitchen"),
+            DeviceInfo::new("lamp", "kitchen_2")
+        ]
+    );
+    let query = Query::new().filter(Filter::state(DeviceState::On)).limit(1);
+    assert_eq!(home.query(&query).len(), 1);
+}
+#[test]
+fn device_metadata() {
+    let mut fridge = Socket::new("fridge").with_metadata(
+        DeviceMetadata::new()
+            .with_tag("critical")
+            .with_vendor("Acme")
+            .with_model("F-100")
+            .with_serial_number("SN-42")
+            .installed_on("2023-04-01")
+            .with_notes("behind the cupboard"),
+    );
+    let mut lamp = Socket::new("lamp");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room(ROOM_NAME).unwrap();
+    owner.add_device(ROOM_NAME, &mut fridge).unwrap();
+    owner.add_device(ROOM_NAME, &mut lamp).unwrap();
+    let lamp_info = DeviceInfo::new("lamp", ROOM_NAME);
+    owner
+        .set_device_metadata(&lamp_info, DeviceMetadata::new().with_tag("decor"))
+        .unwrap();
+    assert!(owner
+        .get_device_metadata(&lamp_info)
+        .unwrap()
+        .has_tag("decor"));
+
+    let report = owner
+        .get_device_report(&DeviceInfo::new("fridge", ROOM_NAME))
+        .unwrap();
+    assert!(report.contains(
+        "tags: critical\nvendor: Acme\nmodel: F-100\nserial number: SN-42\n\
+         installed on: 2023-04-01\nnotes: behind the cupboard\n"
+    ));
+
+    let query: Query = "tag = critical".parse().unwrap();
+    let matches: Vec<DeviceInfo> = home.query(&query).iter().map(|m| m.device_info()).collect();
+    assert_eq!(matches, vec![DeviceInfo::new("fridge", ROOM_NAME)]);
+
+    let snapshot = home.snapshot();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored: HomeSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, snapshot);
+    assert_eq!(
+        restored
+            .device(&DeviceInfo::new("fridge", ROOM_NAME))
+            .unwrap()
+            .metadata
+            .serial_number
+            .as_deref(),
+        Some("SN-42")
+    );
+}
+#[test]
+fn iterate_rooms_and_devices() {
+    let mut kettle = Socket::new("kettle");
+    let mut thermo = Thermometer::new("thermo");
+    let mut heater = Socket::new("heater");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_room("garage").unwrap();
+    owner.add_room("attic").unwrap();
+    owner.add_device("kitchen", &mut kettle).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    owner.add_device("garage", &mut heater).unwrap();
+
+    let rooms: Vec<(&str, usize)> = home.rooms().map(|r| (r.name(), r.len())).collect();
+    assert_eq!(rooms, vec![("kitchen", 2), ("garage", 1), ("attic", 0)]);
+    let devices: Vec<(&str, &str)> = home.devices().map(|(r, d)| (r, d.name())).collect();
+    assert_eq!(
+        devices,
+        vec![
+            ("kitchen", "kettle"),
+            ("kitchen", "thermo"),
+            ("garage", "heater")
+        ]
+    );
+
+    let mut owner = home.acting_as(OWNER).unwrap();
+    for (room_name, device) in owner.devices_mut().unwrap() {
+        if room_name == "kitchen" {
+            device.turn_on();
+        }
+    }
+    let on: Vec<&str> = home
+        .devices()
+        .filter(|(_, d)| *d.state() == DeviceState::On)
+        .map(|(_, d)| d.name())
+        .collect();
+    assert_eq!(on, vec!["kettle", "thermo"]);
+}
+#[test]
+fn visit_home() {
+    /// Collects home outline
+    #[derive(Default)]
+    struct Outline(Vec<String>);
+
+    impl HomeVisitor for Outline {
+        fn visit_room(&mut self, room: RoomView) {
+            self.0.push(format!("{} [", room.name()));
+        }
+        fn visit_device(&mut self, room_name: &str, device: &dyn Device) {
+            self.0.push(format!("{}/{}", room_name, device.name()));
+        }
+        fn leave_room(&mut self, _room: RoomView) {
+            self.0.push("]".to_string());
+        }
+    }
+
+    let mut socket = Socket::new("socket");
+    let home = home! {
+        HOME_NAME => {
+            "kitchen" => [&mut socket],
+            "attic" => [],
+        }
+    }
+    .unwrap();
+    let mut outline = Outline::default();
+    home.accept(&mut outline);
+    assert_eq!(
+        outline.0,
+        vec!["kitchen [", "kitchen/socket", "]", "attic [", "]"]
+    );
+}
+#[test]
+fn temperature_units_and_calibration() {
+    let mut thermo = Thermometer::new("thermo").with_calibration(Calibration::new(100.0, 0.0));
+    let mut socket = Socket::new("socket");
+    let mut home = home! {
+        HOME_NAME => {
+            "kitchen" => [&mut thermo, &mut socket],
+        }
+    }
+    .unwrap();
+    let thermo_info = DeviceInfo::new("thermo", "kitchen");
+    let socket_info = DeviceInfo::new("socket", "kitchen");
+    home.acting_as(OWNER)
+        .unwrap()
+        .turn_on(&thermo_info)
+        .unwrap();
+    // zero scale makes every calibrated reading equal to the offset
+    let reading = |home: &Home| home.snapshot().device(&thermo_info).unwrap().reading;
+    assert_eq!(reading(&home), Some(Reading::Temperature(100.into())));
+
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner
+        .get_device_report(&thermo_info)
+        .unwrap()
+        .contains("current temperature: 100.0 °C\n"));
+    owner
+        .set_temperature_display(
+            TemperatureDisplay::new(TemperatureUnit::Fahrenheit).with_precision(2),
+        )
+        .unwrap();
+    assert!(owner
+        .get_device_report(&thermo_info)
+        .unwrap()
+        .contains("current temperature: 212.00 °F\n"));
+    assert!(owner.get_home_report().unwrap().contains("212.00 °F"));
+
+    let calibration = Calibration::new(-273.15, 0.0);
+    owner
+        .set_device_calibration(&thermo_info, calibration)
+        .unwrap();
+    owner
+        .set_temperature_display(TemperatureDisplay::new(TemperatureUnit::Kelvin).with_precision(0))
+        .unwrap();
+    assert!(owner
+        .get_device_report(&thermo_info)
+        .unwrap()
+        .contains("current temperature: 0 K\n"));
+    assert!(owner
+        .set_device_calibration(&socket_info, Calibration::identity())
+        .is_err());
+
+    assert_eq!(
+        home.get_device_calibration(&thermo_info).unwrap(),
+        Some(calibration)
+    );
+    assert_eq!(home.get_device_calibration(&socket_info).unwrap(), None);
 }
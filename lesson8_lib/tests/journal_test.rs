@@ -0,0 +1,283 @@
+use std::{fs, path::PathBuf};
+
+use lesson8_lib::*;
+
+const HOME_NAME: &str = "home";
+
+/// Returns empty directory unique for test
+fn temp_dir(test_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "lesson8_journal_{}_{}",
+        std::process::id(),
+        test_name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Journal does not record random readings
+fn without_readings(mut snapshot: HomeSnapshot) -> HomeSnapshot {
+    for room in snapshot.rooms.iter_mut() {
+        for device in room.devices.iter_mut() {
+            device.reading = None;
+        }
+    }
+    snapshot
+}
+
+fn entries() -> Vec<JournalEntry> {
+    let socket = DeviceInfo::new("socket", "kitchen");
+    vec![
+        JournalEntry::AddRoom {
+            room_name: "kitchen".to_string(),
+            index: 0,
+        },
+        JournalEntry::add_device("kitchen", 0, &Socket::new("socket")),
+        JournalEntry::add_device("kitchen", 1, &Thermometer::new("thermo")),
+        JournalEntry::SetState {
+            device: socket.clone(),
+            state: DeviceState::On,
+        },
+        JournalEntry::SetMetadata {
+            device: socket,
+            metadata: DeviceMetadata::new().with_tag("critical"),
+        },
+        JournalEntry::SetCalibration {
+            device: DeviceInfo::new("thermo", "kitchen"),
+            calibration: Calibration::new(0.5, 1.0),
+        },
+        JournalEntry::RemoveDevice(DeviceInfo::new("thermo", "kitchen")),
+        JournalEntry::AddRoom {
+            room_name: "garage".to_string(),
+            index: 1,
+        },
+    ]
+}
+
+/// Returns state after the first `count` entries
+fn replayed(entries: &[JournalEntry], count: usize) -> HomeSnapshot {
+    let mut snapshot = HomeSnapshot {
+        name: HOME_NAME.to_string(),
+        rooms: vec![],
+    };
+    entries[..count].iter().for_each(|e| e.apply(&mut snapshot));
+    snapshot
+}
+
+#[test]
+fn home_mutations_are_journaled_and_restored() {
+    let dir = temp_dir("restore");
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let mut heater = Socket::new("heater");
+    let mut home = Home::new(HOME_NAME);
+    home.attach_journal(Journal::open(&dir, HOME_NAME).unwrap());
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_room("garage").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    owner.add_device("garage", &mut heater).unwrap();
+    let socket_info = DeviceInfo::new("socket", "kitchen");
+    owner.turn_on(&socket_info).unwrap();
+    owner
+        .set_device_metadata(&socket_info, DeviceMetadata::new().with_vendor("Acme"))
+        .unwrap();
+    let thermo_info = DeviceInfo::new("thermo", "kitchen");
+    owner
+        .set_device_calibration(&thermo_info, Calibration::new(-1.25, 1.1))
+        .unwrap();
+    assert!(owner.add_room("kitchen").is_err());
+
+    let mut transaction = Transaction::new();
+    transaction
+        .remove_room("garage")
+        .turn_off(&socket_info)
+        .remove_device(&thermo_info);
+    owner.commit(transaction).unwrap();
+    owner.undo().unwrap();
+    owner.turn_on(&DeviceInfo::new("heater", "garage")).unwrap();
+
+    let expected = without_readings(home.snapshot());
+    assert_eq!(home.journal().unwrap().state(), &expected);
+    assert!(home.detach_journal().is_some());
+    drop(home);
+
+    let journal = Journal::open(&dir, HOME_NAME).unwrap();
+    assert_eq!(journal.state(), &expected);
+    assert_eq!(journal.sequence(), journal.records() as u64);
+
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let mut heater = Socket::new("heater");
+    let restored =
+        Home::restore(journal.state(), vec![&mut thermo, &mut socket, &mut heater]).unwrap();
+    assert_eq!(without_readings(restored.snapshot()), expected);
+    assert_eq!(
+        restored.get_device_metadata(&socket_info).unwrap().vendor,
+        Some("Acme".to_string())
+    );
+    assert_eq!(
+        restored.get_device_calibration(&thermo_info).unwrap(),
+        Some(Calibration::new(-1.25, 1.1))
+    );
+
+    let mut socket = Socket::new("socket");
+    let errors = Home::restore(journal.state(), vec![&mut socket]).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn trips_are_journaled_and_restored() {
+    let dir = temp_dir("trips");
+    let mut kettle = Socket::new("kettle").with_power_limit(0);
+    let mut lamp = Socket::new("lamp").with_power_limit(0);
+    let kettle_info = DeviceInfo::new("kettle", "kitchen");
+    let lamp_info = DeviceInfo::new("lamp", "kitchen");
+    let mut home = Home::new(HOME_NAME);
+    home.attach_journal(Journal::open(&dir, HOME_NAME).unwrap());
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut kettle).unwrap();
+    owner.add_device("kitchen", &mut lamp).unwrap();
+    owner.turn_on(&kettle_info).unwrap();
+    owner.turn_on(&lamp_info).unwrap();
+    assert_eq!(home.balance_power().unwrap().len(), 2);
+    assert!(home
+        .acting_as(OWNER)
+        .unwrap()
+        .reset_device(&lamp_info)
+        .unwrap());
+
+    let expected = without_readings(home.snapshot());
+    assert!(expected.device(&kettle_info).unwrap().tripped.is_some());
+    assert!(expected.device(&lamp_info).unwrap().tripped.is_none());
+    assert_eq!(home.journal().unwrap().state(), &expected);
+    drop(home);
+
+    let journal = Journal::open(&dir, HOME_NAME).unwrap();
+    assert_eq!(journal.state(), &expected);
+    let mut kettle = Socket::new("kettle");
+    let mut lamp = Socket::new("lamp");
+    let mut restored = Home::restore(journal.state(), vec![&mut kettle, &mut lamp]).unwrap();
+    let mut owner = restored.acting_as(OWNER).unwrap();
+    assert!(owner.turn_on(&kettle_info).is_err());
+    assert!(owner.turn_on(&lamp_info).is_ok());
+    assert!(kettle.is_tripped());
+
+    let mut thermo = Thermometer::new("kettle");
+    let mut lamp = Socket::new("lamp");
+    let errors = Home::restore(journal.state(), vec![&mut thermo, &mut lamp]).unwrap_err();
+    assert!(matches!(
+        errors[..],
+        [HomeErrors::InternalError(
+            RoomErrors::ProtectionNotSupported(_)
+        )]
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compaction() {
+    let dir = temp_dir("compaction");
+    let entries = entries();
+    let mut journal = Journal::open(&dir, HOME_NAME).unwrap().compact_every(3);
+    for entry in entries.iter() {
+        journal.append(entry.clone()).unwrap();
+    }
+    let uncompacted = entries.len() % 3;
+    assert_eq!(journal.records(), uncompacted);
+    assert_eq!(journal.sequence(), entries.len() as u64);
+    assert!(dir.join(SNAPSHOT_FILE).exists());
+    let lines = fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap();
+    assert_eq!(lines.lines().count(), uncompacted);
+
+    let expected = replayed(&entries, entries.len());
+    assert_eq!(journal.state(), &expected);
+    drop(journal);
+    let journal = Journal::open(&dir, HOME_NAME).unwrap();
+    assert_eq!(journal.state(), &expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn interrupted_compaction_is_not_replayed_twice() {
+    let dir = temp_dir("interrupted");
+    let entries = entries();
+    let mut journal = Journal::open(&dir, HOME_NAME).unwrap();
+    for entry in entries.iter() {
+        journal.append(entry.clone()).unwrap();
+    }
+    let journal_content = fs::read(dir.join(JOURNAL_FILE)).unwrap();
+    journal.compact().unwrap();
+    drop(journal);
+    // crash after snapshot was written but before journal was emptied
+    fs::write(dir.join(JOURNAL_FILE), journal_content).unwrap();
+
+    let journal = Journal::open(&dir, HOME_NAME).unwrap();
+    assert_eq!(journal.state(), &replayed(&entries, entries.len()));
+    assert_eq!(journal.sequence(), entries.len() as u64);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn torn_writes_are_dropped() {
+    let source = temp_dir("torn_source");
+    let entries = entries();
+    let mut journal = Journal::open(&source, HOME_NAME).unwrap();
+    for entry in entries.iter() {
+        journal.append(entry.clone()).unwrap();
+    }
+    drop(journal);
+    let content = fs::read(source.join(JOURNAL_FILE)).unwrap();
+    fs::remove_dir_all(&source).unwrap();
+
+    let dir = temp_dir("torn");
+    for length in 0..=content.len() {
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(JOURNAL_FILE), &content[..length]).unwrap();
+        let complete = content[..length].iter().filter(|b| **b == b'\n').count();
+
+        let mut journal = Journal::open(&dir, HOME_NAME).unwrap();
+        assert_eq!(journal.state(), &replayed(&entries, complete), "{length}");
+        assert_eq!(journal.sequence(), complete as u64);
+        let truncated = fs::read(dir.join(JOURNAL_FILE)).unwrap();
+        assert!(content.starts_with(&truncated));
+        assert!(truncated.is_empty() || truncated.ends_with(b"\n"));
+
+        // appending after recovery continues the journal
+        let entry = JournalEntry::AddRoom {
+            room_name: "attic".to_string(),
+            index: 0,
+        };
+        journal.append(entry.clone()).unwrap();
+        drop(journal);
+        let journal = Journal::open(&dir, HOME_NAME).unwrap();
+        let mut expected = replayed(&entries, complete);
+        entry.apply(&mut expected);
+        assert_eq!(journal.state(), &expected, "{length}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn corrupted_record_is_reported() {
+    let dir = temp_dir("corrupted");
+    let mut journal = Journal::open(&dir, HOME_NAME).unwrap();
+    for entry in entries() {
+        journal.append(entry).unwrap();
+    }
+    drop(journal);
+    let content = fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap();
+    fs::write(
+        dir.join(JOURNAL_FILE),
+        content.replacen("kitchen", "kitcheN", 1),
+    )
+    .unwrap();
+    assert!(matches!(
+        Journal::open(&dir, HOME_NAME),
+        Err(JournalErrors::Corrupted(_))
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
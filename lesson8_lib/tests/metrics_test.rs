@@ -0,0 +1,59 @@
+use lesson8_lib::*;
+
+#[test]
+fn render_home_metrics() {
+    let mut socket = Socket::new("socket1");
+    let mut thermo = Thermometer::new("thermo1");
+    let mut home = Home::new("home");
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    let socket_info = DeviceInfo::new("socket1", "kitchen");
+    owner.turn_on(&socket_info).unwrap();
+    owner.turn_off(&socket_info).unwrap();
+    owner.turn_off(&socket_info).unwrap();
+
+    assert_eq!(
+        render_metrics(&home),
+        r#"# HELP home_device_on Device state, 1 if device is on
+# TYPE home_device_on gauge
+home_device_on{home="home",room="kitchen",device="socket1"} 0
+home_device_on{home="home",room="kitchen",device="thermo1"} 0
+# HELP home_socket_power_watts Current socket power
+# TYPE home_socket_power_watts gauge
+# HELP home_thermometer_temperature_celsius Current thermometer temperature
+# TYPE home_thermometer_temperature_celsius gauge
+# HELP home_device_state_changes_total Number of device state changes
+# TYPE home_device_state_changes_total counter
+home_device_state_changes_total{home="home",room="kitchen",device="socket1"} 2
+home_device_state_changes_total{home="home",room="kitchen",device="thermo1"} 0
+"#
+    );
+}
+#[test]
+fn render_readings_of_turned_on_devices() {
+    let mut socket = Socket::new("socket1");
+    let mut thermo = Thermometer::new("thermo1");
+    let mut home = Home::new("home");
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    owner
+        .turn_on(&DeviceInfo::new("socket1", "kitchen"))
+        .unwrap();
+    owner
+        .turn_on(&DeviceInfo::new("thermo1", "kitchen"))
+        .unwrap();
+
+    let metrics = render_metrics(&home);
+    assert!(
+        metrics.contains("home_device_on{home=\"home\",room=\"kitchen\",device=\"socket1\"} 1\n")
+    );
+    assert!(metrics
+        .contains("\nhome_socket_power_watts{home=\"home\",room=\"kitchen\",device=\"socket1\"} "));
+    assert!(metrics.contains(
+        "\nhome_thermometer_temperature_celsius{home=\"home\",room=\"kitchen\",device=\"thermo1\"} "
+    ));
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use lesson8_lib::*;
+
+mod broker;
+
+use broker::MqttBroker;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Raw MQTT client observing the bridge
+struct TestClient {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl TestClient {
+    fn connect(address: SocketAddr, client_id: &str, will: Option<MqttMessage>) -> Self {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        MqttPacket::Connect {
+            client_id: client_id.to_string(),
+            keep_alive: 60,
+            clean_session: true,
+            will,
+        }
+        .write(&mut stream)
+        .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        assert!(matches!(
+            MqttPacket::read(&mut reader, MAX_PACKET).unwrap(),
+            MqttPacket::ConnAck { return_code: 0, .. }
+        ));
+        Self { stream, reader }
+    }
+    fn subscribe(&mut self, filter: &str) {
+        MqttPacket::Subscribe {
+            packet_id: 7,
+            filters: vec![filter.to_string()],
+        }
+        .write(&mut self.stream)
+        .unwrap();
+        assert!(matches!(
+            MqttPacket::read(&mut self.reader, MAX_PACKET).unwrap(),
+            MqttPacket::SubAck { packet_id: 7, .. }
+        ));
+    }
+    fn publish(&mut self, topic: &str, payload: &str) {
+        MqttPacket::Publish(MqttMessage::new(topic, payload.as_bytes(), false))
+            .write(&mut self.stream)
+            .unwrap();
+    }
+    /// Returns the next message
+    fn next(&mut self) -> MqttMessage {
+        loop {
+            if let MqttPacket::Publish(message) =
+                MqttPacket::read(&mut self.reader, MAX_PACKET).unwrap()
+            {
+                return message;
+            }
+        }
+    }
+    /// Returns the next message of topic skipping other ones
+    fn message(&mut self, topic: &str) -> MqttMessage {
+        loop {
+            let message = self.next();
+            if message.topic == topic {
+                return message;
+            }
+        }
+    }
+}
+
+#[test]
+fn bridge_publishes_retained_states() {
+    let broker = MqttBroker::bind("127.0.0.1:0").unwrap();
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let mut home = Home::new("home");
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    owner
+        .turn_on(&DeviceInfo::new("thermo", "kitchen"))
+        .unwrap();
+    owner
+        .set_temperature_display(TemperatureDisplay::new(TemperatureUnit::Kelvin).with_precision(2))
+        .unwrap();
+
+    let mut bridge = MqttBridge::connect(broker.local_addr(), "bridge", "home").unwrap();
+    bridge.publish_home(&home).unwrap();
+    let deadline = Instant::now() + TIMEOUT;
+    while broker.retained("home/kitchen/thermo/state").is_none() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    // retained messages are delivered to a client subscribed later
+    let mut client = TestClient::connect(broker.local_addr(), "client", None);
+    client.subscribe("home/#");
+    let retained: HashMap<String, MqttMessage> = (0..4)
+        .map(|_| client.next())
+        .map(|m| (m.topic.clone(), m))
+        .collect();
+    assert!(retained.values().all(|m| m.retain));
+    assert_eq!(retained["home/status"].payload, b"online");
+    assert_eq!(retained["home/kitchen/socket/state"].payload, b"Off");
+    assert_eq!(retained["home/kitchen/thermo/state"].payload, b"On");
+    let temperature = &retained["home/kitchen/thermo/temperature"].payload;
+    let temperature = String::from_utf8_lossy(temperature);
+    assert_eq!(temperature.split_once('.').unwrap().1.len(), 2);
+    let temperature: f64 = temperature.parse().unwrap();
+    assert!((243.15..313.15).contains(&temperature));
+    assert!(broker.retained("home/kitchen/socket/power").is_none());
+
+    bridge.disconnect().unwrap();
+    assert_eq!(client.message("home/status").payload, b"offline");
+}
+
+#[test]
+fn bridge_applies_commands() {
+    let broker = MqttBroker::bind("127.0.0.1:0").unwrap();
+    let mut socket = Socket::new("socket");
+    let mut home = Home::new("home");
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    let device_info = DeviceInfo::new("socket", "kitchen");
+
+    let mut bridge = MqttBridge::connect(broker.local_addr(), "bridge", "home").unwrap();
+    let mut client = TestClient::connect(broker.local_addr(), "client", None);
+    client.subscribe(&bridge.state_topic(&device_info));
+    client.publish(&bridge.command_topic(&device_info), "ON");
+    client.publish("home/garage/missing/set", "off");
+    client.publish("home/kitchen/socket/set", "toggle");
+
+    let mut commands = vec![];
+    let deadline = Instant::now() + TIMEOUT;
+    while commands.len() < 2 && Instant::now() < deadline {
+        commands.extend(bridge.poll(&mut owner, Duration::from_millis(100)).unwrap());
+    }
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0].device, device_info);
+    assert_eq!(commands[0].state, DeviceState::On);
+    assert!(commands[0].result.is_ok());
+    assert!(commands[1].result.is_err());
+    assert_eq!(home.get_state_changes(&device_info).unwrap(), 1);
+    assert_eq!(client.message("home/kitchen/socket/state").payload, b"On");
+}
+
+#[test]
+fn broker_publishes_last_will() {
+    let broker = MqttBroker::bind("127.0.0.1:0").unwrap();
+    let mut observer = TestClient::connect(broker.local_addr(), "observer", None);
+    observer.subscribe("home/status");
+
+    let bridge = MqttBridge::connect(broker.local_addr(), "bridge", "home").unwrap();
+    assert_eq!(observer.message("home/status").payload, b"online");
+    // connection is lost without disconnect
+    drop(bridge);
+    let will = observer.message("home/status");
+    assert_eq!(will.payload, b"offline");
+    assert_eq!(
+        broker.retained("home/status").unwrap().payload,
+        b"offline".to_vec()
+    );
+
+    let client = TestClient::connect(
+        broker.local_addr(),
+        "client",
+        Some(MqttMessage::new("clients/client", b"gone", false)),
+    );
+    observer.subscribe("clients/#");
+    MqttPacket::Disconnect.write(&mut &client.stream).unwrap();
+    drop(client);
+    let mut reconnected = TestClient::connect(broker.local_addr(), "client", None);
+    reconnected.publish("clients/client", "back");
+    assert_eq!(observer.message("clients/client").payload, b"back");
+}
@@ -0,0 +1,174 @@
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime},
+};
+
+use lesson8_lib::*;
+
+const ROOM_NAME: &str = "kitchen";
+
+/// Device consuming power set by test
+#[derive(Debug)]
+struct FixedLoad {
+    name: String,
+    state: DeviceState,
+    power: u32,
+    priority: u8,
+}
+
+impl FixedLoad {
+    fn new(name: &str, power: u32, priority: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            state: DeviceState::On,
+            power,
+            priority,
+        }
+    }
+}
+
+impl Display for FixedLoad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.power)
+    }
+}
+
+impl Device for FixedLoad {
+    fn turn_on(&mut self) {
+        self.state = DeviceState::On;
+    }
+    fn turn_off(&mut self) {
+        self.state = DeviceState::Off;
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+    fn reading(&self) -> Option<Reading> {
+        match self.state {
+            DeviceState::On => Some(Reading::Power(self.power)),
+            DeviceState::Off => None,
+        }
+    }
+    fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn socket_trips_and_stays_off_until_reset() {
+    let mut socket = Socket::new("kettle").with_power_limit(0);
+    let mut home = home! {
+        "home" => { ROOM_NAME => [&mut socket] }
+    }
+    .unwrap();
+    let kettle = DeviceInfo::new("kettle", ROOM_NAME);
+    home.acting_as(OWNER).unwrap().turn_on(&kettle).unwrap();
+
+    let events = home.balance_power_at(at(0)).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, PowerEventKind::Tripped);
+    assert!(matches!(events[0].reading, Some(Reading::Power(1..))));
+    assert!(events[0]
+        .to_string()
+        .starts_with("[Tripped] kitchen/kettle power "));
+    let device = home.snapshot().device(&kettle).unwrap().clone();
+    assert_eq!(device.state, DeviceState::Off);
+    // trip is latched
+    assert!(home.balance_power_at(at(1)).unwrap().is_empty());
+    assert!(matches!(
+        home.acting_as(OWNER).unwrap().turn_on(&kettle),
+        Err(HomeErrors::InternalError(_))
+    ));
+
+    let mut owner = home.acting_as(OWNER).unwrap();
+    assert!(owner.reset_device(&kettle).unwrap());
+    assert!(!owner.reset_device(&kettle).unwrap());
+    assert_eq!(
+        owner.home().snapshot().device(&kettle).unwrap().state,
+        DeviceState::Off
+    );
+    owner.turn_on(&kettle).unwrap();
+    let kinds: Vec<PowerEventKind> = home.power_events().iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, vec![PowerEventKind::Tripped, PowerEventKind::Reset]);
+}
+
+#[test]
+fn socket_within_limit_is_not_tripped() {
+    let mut socket = Socket::new("lamp").with_power_limit(100);
+    let mut home = home! {
+        "home" => { ROOM_NAME => [&mut socket] }
+    }
+    .unwrap();
+    let lamp = DeviceInfo::new("lamp", ROOM_NAME);
+    home.acting_as(OWNER).unwrap().turn_on(&lamp).unwrap();
+    for second in 0..20 {
+        assert!(home.balance_power_at(at(second)).unwrap().is_empty());
+    }
+    assert!(!socket.is_tripped());
+}
+
+#[test]
+fn load_over_budget_is_shed_by_priority() {
+    let mut heater = FixedLoad::new("heater", 50, 1);
+    let mut fridge = FixedLoad::new("fridge", 30, 9);
+    let mut kettle = FixedLoad::new("kettle", 20, 1);
+    let mut lamp = FixedLoad::new("lamp", 10, 5);
+    let mut home = home! {
+        "home" => { ROOM_NAME => [&mut heater, &mut fridge, &mut kettle, &mut lamp] }
+    }
+    .unwrap();
+
+    assert!(home.balance_power_at(at(0)).unwrap().is_empty());
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.set_power_budget(Some(40)).unwrap();
+    let events = home.balance_power_at(at(1)).unwrap();
+    let shed: Vec<(&str, PowerEventKind)> = events
+        .iter()
+        .map(|e| (e.device.device_name.as_str(), e.kind))
+        .collect();
+    assert_eq!(
+        shed,
+        vec![
+            (
+                "heater",
+                PowerEventKind::Shed {
+                    total: 110,
+                    budget: 40,
+                    priority: 1
+                }
+            ),
+            (
+                "kettle",
+                PowerEventKind::Shed {
+                    total: 60,
+                    budget: 40,
+                    priority: 1
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        events[0].to_string(),
+        "[Shed] kitchen/heater power 50, priority 1, home total 110 above budget 40"
+    );
+    assert_eq!(home.power_events(), events.as_slice());
+    let on: Vec<&str> = home
+        .devices()
+        .filter(|(_, d)| *d.state() == DeviceState::On)
+        .map(|(_, d)| d.name())
+        .collect();
+    assert_eq!(on, vec!["fridge", "lamp"]);
+    assert_eq!(
+        home.get_state_changes(&DeviceInfo::new("heater", ROOM_NAME))
+            .unwrap(),
+        1
+    );
+    assert!(home.balance_power_at(at(2)).unwrap().is_empty());
+}
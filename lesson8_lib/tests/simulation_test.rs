@@ -0,0 +1,165 @@
+use std::time::{Duration, SystemTime};
+
+use lesson8_lib::*;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+fn celsius(reading: Reading) -> f64 {
+    match reading {
+        Reading::Temperature(temperature) => temperature.celsius(),
+        reading => panic!("unexpected reading {}", reading),
+    }
+}
+
+#[test]
+fn heater_warms_its_room() {
+    let mut sim = Simulator::new(1)
+        .with_outdoor_temperature(0.0)
+        .with_initial_temperature(15.0);
+    let mut living_thermo = sim.thermometer("living_thermo");
+    let mut bedroom_thermo = sim.thermometer("bedroom_thermo");
+    let mut heater = sim.heater("heater", 2000);
+    let mut home = home! {
+        "home" => {
+            "living" => [&mut living_thermo, &mut heater],
+            "bedroom" => [&mut bedroom_thermo],
+        }
+    }
+    .unwrap();
+    let heater_info = DeviceInfo::new("heater", "living");
+    home.acting_as(OWNER)
+        .unwrap()
+        .turn_on(&heater_info)
+        .unwrap();
+
+    let trace = sim.run(&mut home, 24 * HOUR);
+    assert_eq!(sim.elapsed(), 24 * HOUR);
+    assert_eq!(sim.now(), SystemTime::UNIX_EPOCH + 24 * HOUR);
+
+    // steady state is 2 kW * 2 degrees per kWh * 4 hours
+    let living = trace.readings(&DeviceInfo::new("living_thermo", "living"));
+    assert_eq!(living.len(), 24 * 60);
+    assert!(living
+        .windows(2)
+        .all(|w| celsius(w[1].1) >= celsius(w[0].1)));
+    assert!((celsius(living.last().unwrap().1) - 16.0).abs() < 0.1);
+    let bedroom = trace.readings(&DeviceInfo::new("bedroom_thermo", "bedroom"));
+    assert!(bedroom
+        .windows(2)
+        .all(|w| celsius(w[1].1) <= celsius(w[0].1)));
+    assert!(celsius(bedroom.last().unwrap().1) < 0.1);
+    assert_eq!(
+        trace.readings(&heater_info)[0],
+        (Duration::from_secs(60), Reading::Power(2000))
+    );
+    assert_eq!(sim.room_temperature("living"), living_thermo_reading(&home));
+}
+
+fn living_thermo_reading(home: &Home) -> Option<Temperature> {
+    match home
+        .snapshot()
+        .device(&DeviceInfo::new("living_thermo", "living"))?
+        .reading
+    {
+        Some(Reading::Temperature(temperature)) => Some(temperature),
+        _ => None,
+    }
+}
+
+#[test]
+fn thermostat_automation_keeps_temperature() {
+    let mut sim = Simulator::new(2)
+        .with_outdoor_temperature(-5.0)
+        .with_initial_temperature(18.0)
+        .with_step(Duration::from_secs(30));
+    let mut thermo = sim.thermometer("thermo");
+    let mut heater = sim.heater("heater", 5000);
+    let mut home = home! {
+        "home" => { "living" => [&mut thermo, &mut heater] }
+    }
+    .unwrap();
+    let thermo_info = DeviceInfo::new("thermo", "living");
+    let heater_info = DeviceInfo::new("heater", "living");
+
+    let trace = sim.run_with(&mut home, 12 * HOUR, |home, _| {
+        let temperature = match home.snapshot().device(&thermo_info).unwrap().reading {
+            Some(Reading::Temperature(temperature)) => temperature.celsius(),
+            _ => return,
+        };
+        if temperature < 20.0 {
+            let _ = home.acting_as(OWNER).unwrap().turn_on(&heater_info);
+        } else if temperature > 21.0 {
+            let _ = home.acting_as(OWNER).unwrap().turn_off(&heater_info);
+        }
+    });
+
+    let changes = trace.state_changes(&heater_info);
+    assert_eq!(changes[0], (Duration::ZERO, DeviceState::On));
+    assert!(changes.len() > 4);
+    assert!(changes.windows(2).all(|w| w[0].1 != w[1].1));
+    let readings = trace.readings(&thermo_info);
+    assert!(readings
+        .iter()
+        .filter(|(elapsed, _)| *elapsed > HOUR)
+        .all(|(_, r)| (19.5..21.5).contains(&celsius(*r))));
+    assert!(trace
+        .to_string()
+        .starts_with("00:00:00 living/heater On\n00:00:30 living/thermo temperature "));
+}
+
+#[test]
+fn appliance_power_follows_occupants() {
+    let mut sim = Simulator::new(3)
+        .with_move_probability(1.0)
+        .with_occupant("alice", "kitchen");
+    let mut kettle = sim.appliance("kettle", 5, 100);
+    let mut home = home! {
+        "home" => {
+            "kitchen" => [&mut kettle],
+            "hall" => [],
+        }
+    }
+    .unwrap();
+    let kettle_info = DeviceInfo::new("kettle", "kitchen");
+    home.acting_as(OWNER)
+        .unwrap()
+        .turn_on(&kettle_info)
+        .unwrap();
+
+    let trace = sim.run(&mut home, Duration::from_secs(5 * 60));
+    let moves = trace.moves("alice");
+    assert_eq!(moves.len(), 5);
+    assert_eq!(moves[0], (Duration::from_secs(60), "kitchen", "hall"));
+    assert_eq!(moves[1], (Duration::from_secs(120), "hall", "kitchen"));
+    assert_eq!(sim.occupant_room("alice"), Some("hall"));
+    let power: Vec<Reading> = trace
+        .readings(&kettle_info)
+        .into_iter()
+        .map(|(_, r)| r)
+        .collect();
+    assert_eq!(power, [5, 105, 5, 105, 5].map(Reading::Power).to_vec());
+}
+
+#[test]
+fn same_seed_gives_same_trace() {
+    let run = |seed| {
+        let mut sim = Simulator::new(seed)
+            .with_move_probability(0.3)
+            .with_occupant("alice", "kitchen")
+            .with_occupant("bob", "hall");
+        let mut thermo = sim.thermometer("thermo");
+        let mut home = home! {
+            "home" => {
+                "kitchen" => [&mut thermo],
+                "hall" => [],
+                "attic" => [],
+            }
+        }
+        .unwrap();
+        sim.run(&mut home, 2 * HOUR)
+    };
+    let trace = run(7);
+    assert_eq!(trace, run(7));
+    assert!(!trace.moves("alice").is_empty());
+    assert!(!trace.moves("bob").is_empty());
+}
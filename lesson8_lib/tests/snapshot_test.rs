@@ -0,0 +1,98 @@
+use lesson8_lib::*;
+
+const HOME_NAME: &str = "home";
+
+#[test]
+fn snapshot_of_home() {
+    let mut socket = Socket::new("socket");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    let snapshot = home.snapshot();
+    assert_eq!(snapshot.name, HOME_NAME);
+    assert_eq!(snapshot.rooms.len(), 1);
+    let device = snapshot
+        .device(&DeviceInfo::new("socket", "kitchen"))
+        .unwrap();
+    assert_eq!(device.state, DeviceState::Off);
+    assert_eq!(device.reading, None);
+    assert_eq!(
+        snapshot.to_string(),
+        "Home name: home\nRoom name: kitchen\n\tsocket: Off, none\n"
+    );
+}
+#[test]
+fn diff_between_snapshots() {
+    let mut socket = Socket::new("socket");
+    let mut thermo = Thermometer::new("thermo");
+    let mut lamp = Socket::new("lamp");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    owner.add_room("garage").unwrap();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    owner.add_device("kitchen", &mut thermo).unwrap();
+    owner.add_device("garage", &mut lamp).unwrap();
+    let before = owner.home().snapshot();
+
+    owner.remove_room("garage").unwrap();
+    owner.add_room("hall").unwrap();
+    owner
+        .remove_device(&DeviceInfo::new("socket", "kitchen"))
+        .unwrap();
+    owner
+        .turn_on(&DeviceInfo::new("thermo", "kitchen"))
+        .unwrap();
+    let after = home.snapshot();
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.added_rooms, vec!["hall"]);
+    assert_eq!(diff.removed_rooms, vec!["garage"]);
+    assert!(diff.added_devices.is_empty());
+    assert_eq!(
+        diff.removed_devices,
+        vec![
+            DeviceInfo::new("socket", "kitchen"),
+            DeviceInfo::new("lamp", "garage")
+        ]
+    );
+    assert_eq!(diff.state_changes.len(), 1);
+    assert_eq!(diff.state_changes[0].from, DeviceState::Off);
+    assert_eq!(diff.state_changes[0].to, DeviceState::On);
+    assert_eq!(diff.reading_changes.len(), 1);
+    assert_eq!(diff.reading_changes[0].from, None);
+    assert!(matches!(
+        diff.reading_changes[0].to,
+        Some(Reading::Temperature(_))
+    ));
+
+    let report = diff.to_string();
+    assert!(report.starts_with(
+        "+ room hall\n- room garage\n- device kitchen/socket\n- device garage/lamp\n~ state kitchen/thermo: Off -> On\n~ reading kitchen/thermo: none -> temperature "
+    ));
+    assert!(after.diff(&after).is_empty());
+}
+#[test]
+fn serialize_snapshot_and_diff() {
+    let mut socket = Socket::new("socket");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room("kitchen").unwrap();
+    let before = owner.home().snapshot();
+    owner.add_device("kitchen", &mut socket).unwrap();
+    let after = home.snapshot();
+
+    let json = serde_json::to_string(&after).unwrap();
+    let restored: HomeSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, after);
+
+    let diff = before.diff(&after);
+    let json = serde_json::to_string(&diff).unwrap();
+    let restored: HomeDiff = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, diff);
+    assert_eq!(
+        restored.added_devices,
+        vec![DeviceInfo::new("socket", "kitchen")]
+    );
+}
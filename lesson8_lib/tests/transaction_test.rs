@@ -0,0 +1,108 @@
+use lesson8_lib::*;
+
+const HOME_NAME: &str = "home";
+const ROOM_NAME: &str = "room";
+const DEVICE_NAME: &str = "dev";
+
+fn is_on(home: &Home, device_info: &DeviceInfo) -> bool {
+    home.snapshot().device(device_info).unwrap().state == DeviceState::On
+}
+
+#[test]
+fn commit_transaction() {
+    let mut home = Home::new(HOME_NAME);
+    let mut device = Socket::new(DEVICE_NAME);
+    let device_info = DeviceInfo::new(DEVICE_NAME, ROOM_NAME);
+    let mut transaction = Transaction::new();
+    transaction
+        .add_room(ROOM_NAME)
+        .add_device(ROOM_NAME, &mut device)
+        .turn_on(&device_info);
+    let results = home.acting_as(OWNER).unwrap().commit(transaction);
+    assert!(results.is_ok());
+    assert_eq!(results.unwrap().len(), 3);
+    assert_eq!(
+        home.get_devices_in_room(ROOM_NAME).unwrap(),
+        vec![DEVICE_NAME]
+    );
+    assert!(is_on(&home, &device_info));
+}
+#[test]
+fn rejected_transaction_changes_nothing() {
+    let mut home = Home::new(HOME_NAME);
+    home.acting_as(OWNER).unwrap().add_room(ROOM_NAME).unwrap();
+    let mut device = Socket::new(DEVICE_NAME);
+    let mut transaction = Transaction::new();
+    transaction
+        .add_room("other")
+        .add_device(ROOM_NAME, &mut device)
+        .add_room(ROOM_NAME)
+        .remove_device(&DeviceInfo::new("missing", ROOM_NAME));
+    let result = home.acting_as(OWNER).unwrap().commit(transaction);
+    match result {
+        Err(HomeErrors::TransactionRejected(results)) => {
+            assert_eq!(results.len(), 4);
+            assert!(results[0].is_ok());
+            assert!(results[1].is_ok());
+            assert!(matches!(results[2], Err(HomeErrors::RoomNameExists(_))));
+            assert!(matches!(results[3], Err(HomeErrors::InternalError(_))));
+        }
+        _ => panic!("transaction should be rejected"),
+    }
+    assert_eq!(home.get_room_names(), vec![ROOM_NAME]);
+    assert!(home.get_devices_in_room(ROOM_NAME).unwrap().is_empty());
+    assert!(!home.can_undo());
+}
+#[test]
+fn validation_uses_previous_operations() {
+    let mut home = Home::new(HOME_NAME);
+    let mut transaction = Transaction::new();
+    transaction.add_room(ROOM_NAME).remove_room(ROOM_NAME);
+    assert!(transaction.validate(&home).iter().all(|r| r.is_ok()));
+    assert!(home.acting_as(OWNER).unwrap().commit(transaction).is_ok());
+    assert!(home.get_room_names().is_empty());
+}
+#[test]
+fn undo_last_transaction() {
+    let mut first = Socket::new("first");
+    let mut second = Thermometer::new("second");
+    let mut home = Home::new(HOME_NAME);
+    let mut owner = home.acting_as(OWNER).unwrap();
+    owner.add_room(ROOM_NAME).unwrap();
+    owner.add_device(ROOM_NAME, &mut first).unwrap();
+    owner.add_device(ROOM_NAME, &mut second).unwrap();
+    let first_info = DeviceInfo::new("first", ROOM_NAME);
+    let second_info = DeviceInfo::new("second", ROOM_NAME);
+
+    let mut transaction = Transaction::new();
+    transaction
+        .turn_on(&second_info)
+        .remove_device(&first_info)
+        .add_room("other")
+        .remove_room(ROOM_NAME);
+    assert!(home.acting_as(OWNER).unwrap().commit(transaction).is_ok());
+    assert_eq!(home.get_room_names(), vec!["other"]);
+
+    assert!(home.acting_as(OWNER).unwrap().undo().is_ok());
+    assert_eq!(home.get_room_names(), vec![ROOM_NAME]);
+    assert_eq!(
+        home.get_devices_in_room(ROOM_NAME).unwrap(),
+        vec!["first", "second"]
+    );
+    assert!(!is_on(&home, &second_info));
+    assert!(matches!(
+        home.acting_as(OWNER).unwrap().undo(),
+        Err(HomeErrors::NothingToUndo)
+    ));
+}
+#[test]
+fn direct_change_drops_undo() {
+    let mut home = Home::new(HOME_NAME);
+    let mut transaction = Transaction::new();
+    transaction.add_room(ROOM_NAME);
+    home.acting_as(OWNER).unwrap().commit(transaction).unwrap();
+    assert!(home.can_undo());
+    home.acting_as(OWNER).unwrap().add_room("other").unwrap();
+    assert!(!home.can_undo());
+    assert!(home.acting_as(OWNER).unwrap().undo().is_err());
+}